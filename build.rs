@@ -1,19 +1,57 @@
 #![expect(clippy::unwrap_used, reason = "panic is ok at build time")]
 
-use chrono::NaiveDate;
+// The same parser that the server uses to render gemtext as HTML
+#[path = "functions/gemtext/parser.rs"]
+mod parser;
+use parser::{Line, parse};
+
+// The capsule's routes that the website doesn't have
+#[path = "functions/gemtext/capsule_routes.rs"]
+mod capsule_routes;
+use capsule_routes::is_capsule_route;
+
+// The same escaping that the server uses
+#[path = "functions/utils/escape.rs"]
+mod escape;
+use escape::escape_html;
+
+// The same tokenizer that the capsule uses to search this index
+#[path = "functions/capsule/search/terms.rs"]
+mod terms;
+use terms::search_terms;
+
+use chrono::{DateTime, NaiveDate, Utc};
 use core::cmp::Ordering;
+use flate2::{Compression, write::GzEncoder};
 use markdown::{Constructs, ParseOptions, mdast::Node};
 use regex_static::lazy_regex;
 use serde::Deserialize;
-use std::{env, fs, io::ErrorKind, path::Path};
+use sha2::{Digest, Sha256};
+use std::{
+	collections::BTreeMap,
+	env, fs,
+	io::{ErrorKind, Write},
+	path::{Path, PathBuf},
+};
 
 fn main() {
 	// Rebuild if either Ways or this script change
 	println!("cargo::rerun-if-changed=src/content/ways");
 	println!("cargo::rerun-if-changed=build.rs");
+	println!("cargo::rerun-if-changed=functions/gemtext/parser.rs");
+	println!("cargo::rerun-if-changed=functions/gemtext/capsule_routes.rs");
+	println!("cargo::rerun-if-changed=functions/capsule/search/terms.rs");
 
 	let out_dir = env::var_os("OUT_DIR").unwrap();
 
+	// Set when the website embeds the site that Astro built, rather than the fallback
+	println!("cargo::rustc-check-cfg=cfg(dist)");
+
+	// Only the website embeds dist
+	if env::var_os("CARGO_FEATURE_HTTP").is_some() {
+		write_dist_metadata(Path::new(&out_dir));
+	}
+
 	// Construct a ways.gmi index file:
 	let ways = "src/content/ways";
 	let original_ways = fs::read_dir(ways)
@@ -41,16 +79,21 @@ fn main() {
 		.collect::<Vec<_>>()
 		.join("\n");
 
+	// Date-prefixed links make this a gemfeed. See gemini://geminiprotocol.net/docs/companion/subscription.gmi
 	let ways_root = format!(
 		"# Ways
 
 {articles_list}
 
+=> /search Search the Ways
+=> /ways/atom.xml Atom feed
 => / Return home
 "
 	);
 	let ways_gmi = Path::new(&out_dir).join("ways.gmi");
 	fs::write(ways_gmi, ways_root).unwrap();
+	write_ways_feed(Path::new(&out_dir), &ways_meta);
+	write_search_index(Path::new(&out_dir), Path::new(ways), &ways_meta);
 
 	// Transform src/content/ways/*.md into ./ways/*.gmi files
 	let ways_container = Path::new(&out_dir).join("ways");
@@ -114,6 +157,453 @@ fn ways_from_slug(slug: &str) -> Option<&'static str> {{
 "
 	);
 	fs::write(&dest_path, ways_from_slug_fn).unwrap();
+
+	// Only the website renders pages as text
+	if env::var_os("CARGO_FEATURE_HTTP").is_some() {
+		write_text_pages(Path::new(&out_dir));
+	}
+}
+
+/// The origin in generated links to the capsule, which the server replaces with
+/// the capsule's own.
+const CAPSULE_ORIGIN: &str = "gemini://capsule.invalid";
+
+/// The name of the Ways feed's author, which the capsule replaces with the profile's.
+const FEED_AUTHOR: &str = "{author}";
+
+/// Writes an Atom feed of the given Ways, newest first, to `ways_atom.xml`.
+fn write_ways_feed(out_dir: &Path, ways_meta: &[WaysMetaWithSlug]) {
+	println!("cargo::rerun-if-changed=functions/utils/escape.rs");
+
+	let updated = |date: NaiveDate| format!("{date}T00:00:00Z");
+	let entries = ways_meta
+		.iter()
+		.map(|WaysMetaWithSlug(slug, meta)| {
+			let url = format!("{CAPSULE_ORIGIN}/ways/{slug}");
+			format!(
+				"	<entry>
+		<title>{}</title>
+		<link href=\"{url}\" />
+		<id>{url}</id>
+		<updated>{}</updated>
+		<summary>{}</summary>
+	</entry>",
+				escape_html(&meta.title),
+				updated(meta.date),
+				escape_html(&meta.description),
+			)
+		})
+		.collect::<Vec<_>>()
+		.join("\n");
+	let feed_updated = ways_meta
+		.iter()
+		.map(|WaysMetaWithSlug(_, meta)| meta.date)
+		.max()
+		.map_or_else(|| "1970-01-01T00:00:00Z".to_owned(), updated);
+
+	let feed = format!(
+		"<?xml version=\"1.0\" encoding=\"utf-8\"?>
+<feed xmlns=\"http://www.w3.org/2005/Atom\">
+	<title>Ways</title>
+	<link href=\"{CAPSULE_ORIGIN}/ways/atom.xml\" rel=\"self\" />
+	<link href=\"{CAPSULE_ORIGIN}/ways\" />
+	<id>{CAPSULE_ORIGIN}/ways</id>
+	<updated>{feed_updated}</updated>
+	<author><name>{FEED_AUTHOR}</name></author>
+{entries}
+</feed>
+"
+	);
+	fs::write(out_dir.join("ways_atom.xml"), feed).unwrap();
+	fs::write(
+		out_dir.join("ways_feed.rs"),
+		format!(
+			"/// The origin in the Ways feed's links, which should be replaced with the capsule's.
+const FEED_ORIGIN: &str = {CAPSULE_ORIGIN:?};
+
+/// The name of the Ways feed's author, which should be replaced with the profile's.
+const FEED_AUTHOR: &str = {FEED_AUTHOR:?};
+"
+		),
+	)
+	.unwrap();
+}
+
+/// Writes a `search_index.rs` with the text of each of the given Ways, and an
+/// inverted index from each term in them to the Ways that use it, weighted so
+/// that the capsule can rank results without indexing anything at runtime.
+fn write_search_index(out_dir: &Path, ways_dir: &Path, ways_meta: &[WaysMetaWithSlug]) {
+	let mut documents = Vec::new();
+	let mut index: BTreeMap<String, Vec<(usize, u32)>> = BTreeMap::new();
+	for (number, WaysMetaWithSlug(slug, meta)) in ways_meta.iter().enumerate() {
+		let markdown_text = fs::read_to_string(ways_dir.join(format!("{slug}.md"))).unwrap();
+		let text = plain_text(&markdown_ast(&markdown_text));
+
+		// Terms in the title count most, then those in the description
+		let mut counts: BTreeMap<String, u32> = BTreeMap::new();
+		for (field, weight) in [(&meta.title, 3), (&meta.description, 2), (&text, 1)] {
+			for term in search_terms(field) {
+				let count = counts.entry(term).or_default();
+				*count = count.saturating_add(weight);
+			}
+		}
+		for (term, count) in counts {
+			index.entry(term).or_default().push((number, count));
+		}
+
+		documents.push(format!(
+			"\tSearchDocument {{ path: \"/ways/{slug}\", title: {:?}, date: \"{}\", text: {text:?} }},",
+			meta.title, meta.date
+		));
+	}
+
+	// Terms that fewer Ways use say more about the ones that do
+	let terms = index
+		.iter()
+		.map(|(term, postings)| {
+			let rarity = ways_meta
+				.len()
+				.saturating_sub(postings.len())
+				.saturating_add(1);
+			let rarity = u32::try_from(rarity).unwrap();
+			let postings = postings
+				.iter()
+				.map(|(number, count)| format!("({number}, {})", count.saturating_mul(rarity)))
+				.collect::<Vec<_>>()
+				.join(", ");
+			format!("\t({term:?}, &[{postings}]),")
+		})
+		.collect::<Vec<_>>()
+		.join("\n");
+	let documents = documents.join("\n");
+
+	let search_index = format!(
+		"/// The Ways that search covers, newest first.
+static SEARCH_DOCUMENTS: &[SearchDocument] = &[
+{documents}
+];
+
+/// Each term in the Ways, in order, with the index in `SEARCH_DOCUMENTS` of each
+/// Way that uses it, and how much the term counts toward that Way's score.
+static SEARCH_TERMS: &[(&str, &[(usize, u32)])] = &[
+{terms}
+];
+"
+	);
+	fs::write(out_dir.join("search_index.rs"), search_index).unwrap();
+}
+
+/// The text of the given Markdown document, without its frontmatter or formatting.
+fn plain_text(node: &Node) -> String {
+	let mut text = Vec::new();
+	collect_text(node, &mut text);
+	text.concat()
+		.split_whitespace()
+		.collect::<Vec<_>>()
+		.join(" ")
+}
+
+/// Adds the text of the given Markdown node and its children to `text`, with
+/// blocks kept apart by spaces.
+fn collect_text<'a>(node: &'a Node, text: &mut Vec<&'a str>) {
+	match node {
+		Node::Yaml(_) => {}
+		Node::Text(node) => text.push(&node.value),
+		Node::InlineCode(node) => text.push(&node.value),
+		Node::Code(node) => text.extend([node.value.as_str(), " "]),
+		_ => {
+			for child in node.children().into_iter().flatten() {
+				collect_text(child, text);
+			}
+			if matches!(
+				node,
+				Node::Paragraph(_) | Node::Heading(_) | Node::ListItem(_) | Node::TableCell(_)
+			) {
+				text.push(" ");
+			}
+		}
+	}
+}
+
+/// Extensions of the dist files that are worth compressing.
+static COMPRESSIBLE_EXTENSIONS: &[&str] = &[
+	"css",
+	"html",
+	"js",
+	"json",
+	"map",
+	"mjs",
+	"otf",
+	"svg",
+	"ttf",
+	"txt",
+	"webmanifest",
+	"xml",
+	"xsl",
+];
+
+/// A minimal site to embed when Astro hasn't built `dist` yet.
+const FALLBACK_DIST: &str = "functions/fallback-dist";
+
+/// A file that the Astro build touches whenever it has built `dist`.
+const DIST_STAMP: &str = ".dist-stamp";
+
+/// Picks the directory of static files to embed, sets `DIST_DIR` to its absolute
+/// path, and writes a `dist_metadata` function that returns the strong `ETag` and
+/// `Last-Modified` date of each file in it, so that clients can revalidate what
+/// they've cached, along with precompressed variants of the files that are worth
+/// compressing.
+fn write_dist_metadata(out_dir: &Path) {
+	println!("cargo::rerun-if-changed={FALLBACK_DIST}");
+
+	let dist = if Path::new("dist").is_dir() {
+		println!("cargo::rerun-if-changed=dist");
+		println!("cargo::rustc-cfg=dist");
+		Path::new("dist")
+	} else {
+		// Cargo would rerun this script on every build to watch a missing path, and
+		// watching the project root catches changes to `target` too, so watch the
+		// stamp that `deno task build` touches once it has built dist. The stamp
+		// isn't checked in, so make it if it's missing, without touching it otherwise
+		_ = fs::File::create_new(DIST_STAMP);
+		println!("cargo::rerun-if-changed={DIST_STAMP}");
+		println!(
+			"cargo::warning=dist not found, so the fallback site will be embedded. Run `deno task build` to build the real one."
+		);
+		Path::new(FALLBACK_DIST)
+	};
+	let manifest_dir = PathBuf::from(env::var_os("CARGO_MANIFEST_DIR").unwrap());
+	let dist_dir = manifest_dir.join(dist);
+	println!("cargo::rustc-env=DIST_DIR={}", dist_dir.to_str().unwrap());
+
+	let compressed_dir = out_dir.join("dist");
+	let mut files = Vec::new();
+	collect_files(dist, &mut files);
+	let mut entries = files
+		.iter()
+		.map(|file| {
+			let relative_path = file.strip_prefix(dist).unwrap();
+			let path = relative_path.to_str().unwrap().to_owned();
+			let contents = fs::read(file).unwrap();
+			let hash = format!("{:x}", Sha256::digest(&contents));
+			let modified = DateTime::<Utc>::from(fs::metadata(file).unwrap().modified().unwrap());
+			let last_modified = modified.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+
+			let is_compressible = relative_path
+				.extension()
+				.and_then(|extension| extension.to_str())
+				.is_some_and(|extension| COMPRESSIBLE_EXTENSIONS.contains(&extension));
+			let variants = if is_compressible {
+				let dest_path = compressed_dir.join(relative_path);
+				fs::create_dir_all(dest_path.parent().unwrap()).unwrap();
+				compressed_variants(&contents)
+					.into_iter()
+					.map(|(coding, extension, compressed)| {
+						let mut variant_path = dest_path.clone().into_os_string();
+						variant_path.push(format!(".{extension}"));
+						fs::write(&variant_path, compressed).unwrap();
+						let variant_path = variant_path.into_string().unwrap();
+						let etag = format!("\"{hash}-{extension}\"");
+						format!(
+							"\t\tVariant::new(ContentCoding::{coding}, Validators::new({etag:?}, {last_modified:?}), include_bytes!({variant_path:?})),"
+						)
+					})
+					.collect()
+			} else {
+				Vec::new()
+			};
+
+			let etag = format!("\"{hash}\"");
+			(path, etag, last_modified, variants)
+		})
+		.collect::<Vec<_>>();
+	entries.sort(); // for binary search
+
+	let list = entries
+		.iter()
+		.map(|(path, etag, last_modified, variants)| {
+			let variants = if variants.is_empty() {
+				String::new()
+			} else {
+				format!("\n{}\n\t", variants.join("\n"))
+			};
+			format!("\t({path:?}, Validators::new({etag:?}, {last_modified:?}), &[{variants}]),")
+		})
+		.collect::<Vec<_>>()
+		.join("\n");
+
+	let dist_metadata_fn = format!(
+		"static DIST_METADATA: &[(&str, Validators, &[Variant])] = &[
+{list}
+];
+
+/// Returns the validators and precompressed variants of the dist file at the given path
+/// (relative to dist).
+fn dist_metadata(path: &str) -> Option<(Validators, &'static [Variant])> {{
+	let index = DIST_METADATA
+		.binary_search_by_key(&path, |(path, _, _)| path)
+		.ok()?;
+	DIST_METADATA
+		.get(index)
+		.map(|(_, validators, variants)| (*validators, *variants))
+}}
+"
+	);
+	fs::write(out_dir.join("dist_metadata.rs"), dist_metadata_fn).unwrap();
+}
+
+/// Compresses the given file contents with each content coding we support, and
+/// returns the name, file extension, and output of each coding that made them smaller.
+fn compressed_variants(contents: &[u8]) -> Vec<(&'static str, &'static str, Vec<u8>)> {
+	let mut gzip = GzEncoder::new(Vec::new(), Compression::best());
+	gzip.write_all(contents).unwrap();
+	let gzip = gzip.finish().unwrap();
+
+	let mut brotli = Vec::new();
+	{
+		let mut writer = brotli::CompressorWriter::new(&mut brotli, 4096, 11, 22);
+		writer.write_all(contents).unwrap();
+	}
+
+	let zstd = zstd::encode_all(contents, 19).unwrap();
+
+	[
+		("Brotli", "br", brotli),
+		("Gzip", "gz", gzip),
+		("Zstd", "zst", zstd),
+	]
+	.into_iter()
+	.filter(|(_, _, compressed)| compressed.len() < contents.len())
+	.collect()
+}
+
+/// Renders each page of the capsule (the gemtext pages, and the Ways written in
+/// `main`) as text for terminal clients, and writes a `text_page` function that
+/// returns the renderings of the dist page at the given path.
+fn write_text_pages(out_dir: &Path) {
+	println!("cargo::rerun-if-changed=src/content/gemtext");
+
+	// The dist path of each page, and its gemtext
+	let mut sources = vec![("ways.html".to_owned(), out_dir.join("ways.gmi"))];
+	let ways_dir = out_dir.join("ways");
+	for (dir, prefix) in [
+		(Path::new("src/content/gemtext"), ""),
+		(ways_dir.as_path(), "ways/"),
+	] {
+		for entry in fs::read_dir(dir).unwrap().filter_map(Result::ok) {
+			let path = entry.path();
+			if path.extension().is_some_and(|extension| extension == "gmi") {
+				let stem = path.file_stem().unwrap().to_str().unwrap();
+				sources.push((format!("{prefix}{stem}.html"), path));
+			}
+		}
+	}
+	sources.sort(); // for binary search
+
+	let text_dir = out_dir.join("text");
+	let list = sources
+		.iter()
+		.map(|(dist_path, source)| {
+			let gemtext = fs::read_to_string(source).unwrap();
+			let dest_path = text_dir.join(dist_path);
+			fs::create_dir_all(dest_path.parent().unwrap()).unwrap();
+			let plain_path = dest_path.with_extension("txt");
+			let ansi_path = dest_path.with_extension("ansi");
+			fs::write(&plain_path, text_from_gemtext(&gemtext, false)).unwrap();
+			fs::write(&ansi_path, text_from_gemtext(&gemtext, true)).unwrap();
+			let plain_path = plain_path.to_str().unwrap();
+			let ansi_path = ansi_path.to_str().unwrap();
+			format!("\t({dist_path:?}, include_str!({plain_path:?}), include_str!({ansi_path:?})),")
+		})
+		.collect::<Vec<_>>()
+		.join("\n");
+
+	let text_page_fn = format!(
+		"/// The origin in the text renderings' links to the capsule, which should be
+/// replaced with the capsule's.
+const TEXT_CAPSULE_ORIGIN: &str = {CAPSULE_ORIGIN:?};
+
+static TEXT_PAGES: &[(&str, &str, &str)] = &[
+{list}
+];
+
+/// Returns the plain and ANSI-styled text renderings of the dist page at the given
+/// path (relative to dist), if it has them.
+fn text_page(path: &str) -> Option<(&'static str, &'static str)> {{
+	let index = TEXT_PAGES
+		.binary_search_by_key(&path, |(path, _, _)| path)
+		.ok()?;
+	TEXT_PAGES.get(index).map(|(_, plain, ansi)| (*plain, *ansi))
+}}
+"
+	);
+	fs::write(out_dir.join("text_pages.rs"), text_page_fn).unwrap();
+}
+
+/// Renders the given gemtext as text to read in a terminal. If `ansi` is set,
+/// headings, links, and quotes are styled with ANSI escape codes. Otherwise,
+/// headings are underlined with `=` or `-`.
+fn text_from_gemtext(gemtext: &str, ansi: bool) -> String {
+	let style = |codes: &str, text: &str| {
+		if ansi {
+			format!("\x1b[{codes}m{text}\x1b[0m")
+		} else {
+			text.to_owned()
+		}
+	};
+	let underline = |text: &str, mark: &str| {
+		let mut heading = text.to_owned();
+		if !ansi {
+			heading.push('\n');
+			heading.push_str(&mark.repeat(text.chars().count()));
+		}
+		heading
+	};
+
+	let mut lines = Vec::new();
+	for line in parse(gemtext) {
+		let line = match line {
+			Line::Preformatted { lines: block, .. } => {
+				lines.extend(block.into_iter().map(str::to_owned));
+				continue;
+			}
+			Line::Heading { level: 3, text } => style("1", text),
+			Line::Heading { level: 2, text } => style("1", &underline(text, "-")),
+			Line::Heading { text, .. } => style("1;4", &underline(text, "=")),
+			Line::Link { url, label } => {
+				// The website has nothing at the capsule's other routes
+				let url = if is_capsule_route(url) {
+					format!("{CAPSULE_ORIGIN}{url}")
+				} else {
+					url.to_owned()
+				};
+				let url = style("2", &format!("<{url}>"));
+				match label {
+					Some(label) => format!("→ {} {url}", style("4", label)),
+					None => format!("→ {url}"),
+				}
+			}
+			Line::ListItem(item) => format!("• {item}"),
+			Line::Quote(quote) => style("3", &format!("│ {quote}")),
+			Line::Text(text) => text.to_owned(),
+		};
+		lines.push(line);
+	}
+	let mut text = lines.join("\n");
+	text.push('\n');
+	text
+}
+
+/// Adds the paths of the files in the given directory and its subdirectories to `files`.
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) {
+	for entry in fs::read_dir(dir).unwrap().filter_map(Result::ok) {
+		let path = entry.path();
+		if entry.file_type().unwrap().is_dir() {
+			collect_files(&path, files);
+		} else {
+			files.push(path);
+		}
+	}
 }
 
 type Lazy<T> = regex_static::once_cell::sync::Lazy<T>;
@@ -187,8 +677,7 @@ impl core::fmt::Display for WaysMetaWithSlug {
 	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
 		let slug = &self.0;
 		let file = &self.1;
-		let date = file.date.format("%b %e, %Y");
-		write!(f, "=> /ways/{} {} ({})", slug, file.title, date)
+		write!(f, "=> /ways/{} {} - {}", slug, file.date, file.title)
 	}
 }
 
@@ -1,81 +1,131 @@
-use crate::config::Config;
+mod certs;
+pub use certs::CertError;
+use certs::Certs;
+
+mod guestbook;
+use guestbook::Guestbook;
+
+mod search;
+
+mod server;
+use server::Server;
+
+use crate::AppError;
+use crate::config::{Config, RedirectRule, RedirectStatus, RedirectTable};
+use crate::gemtext;
+use crate::shutdown::Shutdown;
+use crate::utils::escape_html;
+use core::{net::SocketAddr, pin::pin, time::Duration};
 use fluffer::Status;
+use futures::future::try_join_all;
 use rust_embed::Embed;
-use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::{net::TcpListener, sync::watch};
 use url::Host;
 
-/// Launches a Gemini capsule with the given configuration.
-pub async fn gemini_service(config: &Config) -> Result<(), fluffer::AppErr> {
-	let address = format!("[::]:{}", config.gemini_port);
-	let Certs { key, cert } = Certs::from_config(config);
+/// Launches a Gemini capsule at each of the configured addresses, and stops them
+/// gracefully when `shutdown` is triggered.
+///
+/// Renewed certificates are picked up for new connections without a restart.
+pub async fn gemini_service(config: &Config, shutdown: &Shutdown) -> Result<(), AppError> {
+	let certs = Certs::from_config(config)?;
+	let acceptor = certs.acceptor()?;
+	println!("[gemini] Using {}", certs::describe(&acceptor));
+	let (reloads, acceptor) = watch::channel(acceptor);
+	let grace = Duration::from_secs(config.shutdown.grace_secs.into());
+	let guestbook = Arc::new(Guestbook::new(&config.guestbook));
+
+	let capsules = config.gemini.addresses.iter().map(|&address| {
+		let address = SocketAddr::new(address, config.gemini.port);
+		let acceptor = acceptor.clone();
+		let guestbook = Arc::clone(&guestbook);
+		async move {
+			let listener = TcpListener::bind(address)
+				.await
+				.map_err(fluffer::AppErr::Bind)?;
+			println!("[gemini] Serving on {address}");
+			capsule(config, guestbook)
+				.serve(listener, acceptor, shutdown, grace)
+				.await;
+			Ok::<(), AppError>(())
+		}
+	});
+	let mut capsules = pin!(try_join_all(capsules));
 
-	println!("Gemini: Serving on port {}", config.gemini_port);
+	tokio::select! {
+		result = &mut capsules => {
+			result?;
+		}
+		result = certs.watch(&reloads, certs::POLL_INTERVAL, shutdown) => {
+			result.map_err(AppError::Signal)?;
+			// Shutdown was triggered, so let the capsules finish up
+			capsules.await?;
+		}
+	}
+	Ok(())
+}
 
-	fluffer::App::default()
-		.state(config.clone())
-		.address(address)
-		.path_to_key(key)
-		.path_to_cert(cert)
-		.route("/", |client| route(client, |_| root()))
+/// Builds a Gemini capsule that keeps the given guestbook.
+fn capsule(config: &Config, guestbook: Arc<Guestbook>) -> Server<Config> {
+	let signing_guestbook = Arc::clone(&guestbook);
+	Server::new(config.clone())
+		.redirects(redirect_table(config), &config.profile.origin, check_url)
+		.route("/", document)
 		.route("/robots.txt", static_txt) // See gemini://geminiprotocol.net/docs/companion/robots.gmi
 		.route("/humans.txt", static_txt)
-		.route("/contact", static_gmi)
-		.route("/support", static_gmi)
-		.route("/ways", |client| route(client, |_| ways()))
-		.route("/ways/:slug", ways_content)
-		.run()
-		.await
+		.route("/contact", document)
+		.route("/support", document)
+		.route("/ways", document)
+		.route("/ways/atom.xml", ways_feed)
+		.route("/ways/:slug", document)
+		.route("/guestbook", move |client| {
+			guestbook_entries(client, Arc::clone(&guestbook))
+		})
+		.route("/guestbook/sign", move |client| {
+			sign_guestbook(client, Arc::clone(&signing_guestbook))
+		})
+		.route("/search", search_ways)
 }
 
 // MARK: Static files
 
-// Generated in build.rs:
-include!(concat!(env!("OUT_DIR"), "/ways.rs"));
-
-/// Serves the capsule index file.
-#[expect(clippy::unused_async, reason = "fluffer requirement")]
-async fn root() -> &'static str {
-	include_str!("../src/content/gemtext/index.gmi")
-}
-
-/// Serves the Ways index file.
-#[expect(clippy::unused_async, reason = "fluffer requirement")]
-async fn ways() -> &'static str {
-	include_str!(concat!(env!("OUT_DIR"), "/ways.gmi"))
-}
-
-/// Serves a static file from `$OUT_DIR/ways/{slug}.gmi` if the current path matches.
-async fn ways_content(client: fluffer::Client<Config>) -> Result<Vec<u8>, RequestError> {
+/// Serves the capsule document at the current path, from `src/content/gemtext` or
+/// the generated Ways documents.
+async fn document(client: fluffer::Client<Config>) -> Result<Vec<u8>, RequestError> {
 	route(client, async |client: fluffer::Client<Config>| {
-		let slug = client.url.path();
-		ways_from_slug(slug).ok_or(RequestError::NotFound)
+		let path = client.url.path();
+		let data = gemtext::document(path, client.state.gemini.serve_from_disk)
+			.await
+			.ok_or(RequestError::NotFound)?;
+		let Ok(data) = String::from_utf8(data.into_owned()) else {
+			return Err(RequestError::TemporaryFailure);
+		};
+
+		Ok(data) // text/gemini
 	})
 	.await
 }
 
-/// Serves a static file from `src/content/gemtext/{slug}.gmi` if the current path matches.
-async fn static_gmi(client: fluffer::Client<Config>) -> Result<Vec<u8>, RequestError> {
-	route(client, async |client: fluffer::Client<Config>| {
-		let file_path = {
-			let mut str = client.url.path().to_owned();
-			str.push_str(".gmi");
-			str
-		};
-		let asset = GemtextAsset::get(&file_path).ok_or(RequestError::NotFound)?;
-		let Ok(data) = String::from_utf8(asset.data.to_vec()) else {
-			return Err(RequestError::TemporaryFailure);
-		};
+// Generated in build.rs:
+include!(concat!(env!("OUT_DIR"), "/ways_feed.rs"));
 
-		Ok(data) // text/gemini
+/// Serves the Atom feed of the Ways.
+async fn ways_feed(client: fluffer::Client<Config>) -> Result<Vec<u8>, RequestError> {
+	route(client, async |client: fluffer::Client<Config>| {
+		let config = &client.state;
+		let feed = ways_feed_at(&config.gemini_hostname(), &config.profile.name);
+		(Status::Success, "application/atom+xml", feed)
 	})
 	.await
 }
 
-#[derive(Embed)]
-#[folder = "src/content/gemtext/"]
-#[prefix = "/"]
-#[include = "*.gmi"]
-struct GemtextAsset;
+/// The Atom feed of the Ways, with links to the capsule at the given hostname, and
+/// credited to the given author.
+fn ways_feed_at(hostname: &str, author: &str) -> String {
+	let feed = include_str!(concat!(env!("OUT_DIR"), "/ways_atom.xml"));
+	feed.replace(FEED_ORIGIN, &format!("gemini://{hostname}"))
+		.replace(FEED_AUTHOR, &escape_html(author))
+}
 
 /// Serves a static file from `public/{slug}.txt` if the current path matches.
 async fn static_txt(client: fluffer::Client<Config>) -> Result<Vec<u8>, RequestError> {
@@ -97,37 +147,63 @@ async fn static_txt(client: fluffer::Client<Config>) -> Result<Vec<u8>, RequestE
 #[include = "*.txt"]
 struct PublicAsset;
 
-// MARK: Certs
+// MARK: Guestbook
 
-struct Certs {
-	/// The path to key.pem
-	key: PathBuf,
+/// Serves the guestbook's entries.
+async fn guestbook_entries(
+	client: fluffer::Client<Config>,
+	guestbook: Arc<Guestbook>,
+) -> Result<Vec<u8>, RequestError> {
+	route(client, move |_| {
+		let guestbook = Arc::clone(&guestbook);
+		async move {
+			match guestbook.entries().await {
+				Ok(entries) => Ok(guestbook::page(&entries)), // text/gemini
+				Err(err) => {
+					eprintln!("[gemini] Couldn't read the guestbook: {err}");
+					Err(RequestError::TemporaryFailure)
+				}
+			}
+		}
+	})
+	.await
+}
 
-	/// The path to cert.pem
-	cert: PathBuf,
+/// Asks for a message with status 10, then adds it to the guestbook and sends the
+/// client back to the entries.
+async fn sign_guestbook(
+	client: fluffer::Client<Config>,
+	guestbook: Arc<Guestbook>,
+) -> Result<Vec<u8>, RequestError> {
+	route(client, move |client: fluffer::Client<Config>| {
+		let guestbook = Arc::clone(&guestbook);
+		async move {
+			let message = client.input();
+			let signed = guestbook.sign(client.ip.ip(), message.as_deref()).await;
+			if let Err(guestbook::SignError::Io(err)) = &signed {
+				eprintln!("[gemini] Couldn't sign the guestbook: {err}");
+			}
+			signed?;
+			Ok::<_, guestbook::SignError>(Redirect {
+				location: "/guestbook".to_owned(),
+				permanent: false,
+			})
+		}
+	})
+	.await
 }
 
-impl Certs {
-	fn from_config(config: &Config) -> Self {
-		let certs_dir = config
-			.gemini_certs_dir
-			.as_ref()
-			.expect("Missing Gemini certs directory config");
-		assert!(
-			certs_dir.is_dir(),
-			"Gemini certs directory not found: {}",
-			certs_dir.display()
-		);
-		let key = certs_dir.join("key.pem");
-		let cert = certs_dir.join("cert.pem");
-		assert!(
-			!(!key.exists() || !cert.exists()),
-			"Gemini certs files not found in {}",
-			certs_dir.display()
-		);
+// MARK: Search
 
-		Self { key, cert }
-	}
+/// Asks for a query with status 10, then lists the Ways that match it.
+async fn search_ways(client: fluffer::Client<Config>) -> Result<Vec<u8>, RequestError> {
+	route(client, async |client: fluffer::Client<Config>| {
+		match client.input() {
+			Some(query) if !query.trim().is_empty() => Ok(search::page(&query)), // text/gemini
+			_ => Err((Status::Input, "Search the Ways")),
+		}
+	})
+	.await
 }
 
 // MARK: Hostname
@@ -159,48 +235,89 @@ impl fluffer::GemBytes for RequestError {
 	}
 }
 
+/// The configured redirects, plus the profile's `/@{handle}` aliases, like the website's.
+fn redirect_table(config: &Config) -> RedirectTable {
+	let mut table = config.redirects.clone();
+	let profile = &config.profile;
+	table
+		.rules
+		.extend(profile.aliases.iter().map(|handle| RedirectRule {
+			from: format!("/@{handle}"),
+			to: profile.fediverse.profile_page.clone(),
+			status: RedirectStatus::Found,
+			html: true,
+		}));
+	table
+}
+
+/// A response that sends the client to another page.
+struct Redirect {
+	/// The capsule path or absolute URL to go to.
+	location: String,
+
+	/// Whether clients should remember the new location.
+	permanent: bool,
+}
+
+#[fluffer::async_trait]
+impl fluffer::GemBytes for Redirect {
+	async fn gem_bytes(self) -> Vec<u8> {
+		let status = if self.permanent {
+			Status::RedirectPermanent
+		} else {
+			Status::RedirectTemporary
+		};
+		(status, self.location).gem_bytes().await
+	}
+}
+
 const URL_MAX_BYTES: usize = 1024;
 
 async fn route(
 	client: fluffer::Client<Config>,
 	handler: impl fluffer::GemCall<Config> + 'static + Sync + Send,
 ) -> Result<Vec<u8>, RequestError> {
-	let config = &client.state;
-	let hostname = &config.gemini_hostname;
+	check_url(&client.state, &client.url)?;
+	Ok(handler.gem_call(client).await)
+}
+
+/// Makes sure that the given request URL is one that this capsule serves.
+fn check_url(config: &Config, url: &url::Url) -> Result<(), RequestError> {
+	let hostname = config.gemini_hostname();
 
 	// Make sure the URL is the correct size
-	if client.url.as_str().len() > URL_MAX_BYTES {
+	if url.as_str().len() > URL_MAX_BYTES {
 		return Err(RequestError::BadRequest);
 	}
 
 	// Make sure the caller is using the correct scheme
-	if client.url.scheme() != "gemini" {
+	if url.scheme() != "gemini" {
 		return Err(RequestError::WrongHost);
 	}
 
 	// Make sure the port number is correct, if given
-	if let Some(port) = client.url.port()
-		&& port != config.gemini_port
+	if let Some(port) = url.port()
+		&& port != config.gemini.port
 	{
 		return Err(RequestError::WrongHost);
 	}
 
 	// Make sure the caller found us through the correct domain!
-	match client.url.host() {
+	match url.host() {
 		Some(url::Host::Domain(req_host)) if req_host == hostname => {
 			// Known host
-			Ok(handler.gem_call(client).await)
+			Ok(())
 		}
 		Some(host) if host.is_loopback() => {
 			// Localhost
-			Ok(handler.gem_call(client).await)
+			Ok(())
 		}
 		Some(url::Host::Domain(domain)) => {
-			eprintln!("Caller requested an unknown domain {domain}");
+			eprintln!("[gemini] Caller requested an unknown domain {domain}");
 			Err(RequestError::WrongHost)
 		}
 		Some(url::Host::Ipv4(_) | url::Host::Ipv6(_)) | None => {
-			eprintln!("Caller requested an unknown domain");
+			eprintln!("[gemini] Caller requested an unknown domain");
 			Err(RequestError::WrongHost)
 		}
 	}
@@ -220,3 +337,35 @@ impl LoopbackTest for url::Host<&str> {
 		}
 	}
 }
+
+// MARK: - Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	#[cfg(feature = "http")]
+	fn serves_capsule_routes() {
+		let config = Config::default();
+		let guestbook = Arc::new(Guestbook::new(&config.guestbook));
+		let capsule = capsule(&config, guestbook);
+		for path in gemtext::CAPSULE_ROUTES {
+			assert!(capsule.has_route(path), "{path} should have a route");
+		}
+	}
+
+	#[test]
+	fn links_feed_to_capsule() {
+		let feed = ways_feed_at("average.name", "Ada & <Co>'s");
+		assert!(
+			feed.contains("<link href=\"gemini://average.name/ways/atom.xml\" rel=\"self\" />")
+		);
+		assert!(feed.contains("<id>gemini://average.name/ways/ways</id>"));
+		assert!(feed.contains("<title>How to use a Ways folder</title>"));
+		assert!(feed.contains("<updated>2024-02-06T00:00:00Z</updated>"));
+		assert!(feed.contains("<author><name>Ada &amp; &lt;Co&gt;&#39;s</name></author>"));
+		assert!(!feed.contains(FEED_ORIGIN));
+		assert!(!feed.contains(FEED_AUTHOR));
+	}
+}
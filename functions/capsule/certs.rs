@@ -0,0 +1,549 @@
+mod error;
+pub use error::*;
+
+use crate::config::Config;
+use crate::shutdown::Shutdown;
+use core::time::Duration;
+use openssl::{
+	asn1::Asn1Time,
+	bn::{BigNum, MsbOption},
+	ec::{EcGroup, EcKey},
+	error::ErrorStack,
+	hash::MessageDigest,
+	nid::Nid,
+	pkey::{PKey, Private},
+	ssl::{SslAcceptor, SslMethod, SslVerifyMode},
+	x509::{X509, X509Name, extension::SubjectAlternativeName},
+};
+use std::{
+	fs, io,
+	io::Write,
+	path::{Path, PathBuf},
+	time::SystemTime,
+};
+use tokio::sync::watch;
+
+/// How often to check the certificate files for changes.
+pub const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long a generated certificate lasts. Gemini clients pin the certificate
+/// they first see, so it should outlive the capsule.
+const GENERATED_LIFETIME_DAYS: u32 = 100 * 365;
+
+pub struct Certs {
+	/// The path to key.pem
+	key: PathBuf,
+
+	/// The path to cert.pem
+	cert: PathBuf,
+}
+
+impl Certs {
+	/// Finds the key and certificate files in the configured directory, first
+	/// generating them if they don't exist and `generate_certificate` is set.
+	pub fn from_config(config: &Config) -> Result<Self, CertError> {
+		let certs_dir = config
+			.gemini
+			.certs_dir
+			.as_ref()
+			.ok_or(CertError::NoCertsDir)?;
+		if config.gemini.generate_certificate {
+			generate_files(certs_dir, &config.gemini_hostname())?;
+		}
+		if !certs_dir.is_dir() {
+			return Err(CertError::DirNotFound {
+				path: certs_dir.clone(),
+			});
+		}
+
+		let certs = Self {
+			key: certs_dir.join("key.pem"),
+			cert: certs_dir.join("cert.pem"),
+		};
+		for path in [&certs.key, &certs.cert] {
+			if !path.exists() {
+				return Err(CertError::FileNotFound { path: path.clone() });
+			}
+		}
+		Ok(certs)
+	}
+
+	/// Builds a TLS acceptor from the key and certificate files, after checking
+	/// that they parse and belong together.
+	pub fn acceptor(&self) -> Result<SslAcceptor, CertError> {
+		let key = PKey::private_key_from_pem(&read(&self.key)?).map_err(|source| {
+			CertError::ParseKey {
+				path: self.key.clone(),
+				source,
+			}
+		})?;
+		let cert = X509::from_pem(&read(&self.cert)?).map_err(|source| CertError::ParseCert {
+			path: self.cert.clone(),
+			source,
+		})?;
+		if !cert.public_key()?.public_eq(&key) {
+			return Err(CertError::KeyMismatch {
+				key: self.key.clone(),
+				cert: self.cert.clone(),
+			});
+		}
+
+		let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls_server())?;
+		builder.set_private_key(&key)?;
+		builder.set_certificate(&cert)?;
+		builder.check_private_key()?;
+
+		// Ask for client certificates, but accept any, since Gemini uses them as identities
+		builder.set_verify_callback(SslVerifyMode::PEER, |_, _| true);
+		builder.set_session_id_context(env!("CARGO_PKG_NAME").as_bytes())?;
+
+		Ok(builder.build())
+	}
+
+	/// Rebuilds the TLS acceptor whenever the key or certificate file changes, or
+	/// when the process receives SIGHUP, until shutdown is triggered.
+	///
+	/// Connections that are already open keep the acceptor they started with.
+	pub async fn watch(
+		&self,
+		acceptor: &watch::Sender<SslAcceptor>,
+		interval: Duration,
+		shutdown: &Shutdown,
+	) -> io::Result<()> {
+		let mut hangups = Hangups::new()?;
+		let mut modified = self.modified();
+
+		loop {
+			tokio::select! {
+				() = hangups.next() => {
+					println!("[gemini] Received SIGHUP; reloading certificates");
+				}
+				() = tokio::time::sleep(interval) => {
+					let now = self.modified();
+					if now == modified {
+						continue;
+					}
+					modified = now;
+				}
+				() = shutdown.triggered() => return Ok(()),
+			}
+			self.reload(acceptor);
+		}
+	}
+
+	/// Replaces the given acceptor with one built from the files, or keeps the
+	/// current one if the files are unusable.
+	fn reload(&self, acceptor: &watch::Sender<SslAcceptor>) {
+		match self.acceptor() {
+			Ok(new) => {
+				println!("[gemini] Reloaded {}", describe(&new));
+				acceptor.send_replace(new);
+			}
+			Err(err) => {
+				eprintln!("[gemini] Keeping the current certificate; failed to reload: {err}");
+			}
+		}
+	}
+
+	/// The modification times of the key and certificate files, if they're readable.
+	fn modified(&self) -> [Option<SystemTime>; 2] {
+		[&self.key, &self.cert].map(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+	}
+}
+
+/// Describes the acceptor's certificate by its SHA-256 fingerprint and expiry, for logging.
+pub fn describe(acceptor: &SslAcceptor) -> String {
+	let Some(cert) = acceptor.context().certificate() else {
+		return "certificate (none)".to_owned();
+	};
+	let fingerprint = match cert.digest(MessageDigest::sha256()) {
+		Ok(digest) => digest
+			.iter()
+			.map(|byte| format!("{byte:02X}"))
+			.collect::<Vec<_>>()
+			.join(":"),
+		Err(err) => format!("(unknown: {err})"),
+	};
+	format!(
+		"certificate with SHA-256 fingerprint {fingerprint}, expiring {}",
+		cert.not_after()
+	)
+}
+
+/// Listens for SIGHUP, which asks us to reload certificates right away.
+struct Hangups(#[cfg(unix)] tokio::signal::unix::Signal);
+
+impl Hangups {
+	#[cfg(unix)]
+	fn new() -> io::Result<Self> {
+		use tokio::signal::unix::{SignalKind, signal};
+		Ok(Self(signal(SignalKind::hangup())?))
+	}
+
+	#[cfg(not(unix))]
+	#[expect(clippy::unnecessary_wraps, reason = "matches the unix version")]
+	fn new() -> io::Result<Self> {
+		Ok(Self())
+	}
+
+	/// Waits for the next SIGHUP. Never returns where there's no such thing.
+	async fn next(&mut self) {
+		#[cfg(unix)]
+		if self.0.recv().await.is_some() {
+			return;
+		}
+		core::future::pending::<()>().await;
+	}
+}
+
+/// Writes a new self-signed key and certificate to the given directory, unless
+/// either file is already there.
+fn generate_files(dir: &Path, hostname: &str) -> Result<(), CertError> {
+	let key_path = dir.join("key.pem");
+	let cert_path = dir.join("cert.pem");
+	if key_path.exists() || cert_path.exists() {
+		return Ok(());
+	}
+
+	let (key, cert) = generate(hostname)?;
+	fs::create_dir_all(dir).map_err(|source| CertError::WriteFile {
+		path: dir.to_owned(),
+		source,
+	})?;
+	write_new(&key_path, &key.private_key_to_pem_pkcs8()?, 0o600)?;
+	write_new(&cert_path, &cert.to_pem()?, 0o644)?;
+	println!(
+		"[gemini] Generated a self-signed certificate for {hostname} in {}",
+		dir.display()
+	);
+	Ok(())
+}
+
+/// Writes the given contents to a file that must not already exist, with the
+/// given permissions where that means something.
+fn write_new(path: &Path, contents: &[u8], mode: u32) -> Result<(), CertError> {
+	let mut options = fs::File::options();
+	options.write(true).create_new(true);
+	#[cfg(unix)]
+	std::os::unix::fs::OpenOptionsExt::mode(&mut options, mode);
+	#[cfg(not(unix))]
+	let _ = mode;
+	options
+		.open(path)
+		.and_then(|mut file| file.write_all(contents))
+		.map_err(|source| CertError::WriteFile {
+			path: path.to_owned(),
+			source,
+		})
+}
+
+/// Reads the given key or certificate file.
+fn read(path: &Path) -> Result<Vec<u8>, CertError> {
+	fs::read(path).map_err(|source| CertError::ReadFile {
+		path: path.to_owned(),
+		source,
+	})
+}
+
+/// Generates a long-lived self-signed key and certificate for the given hostname
+/// and `localhost`.
+pub fn generate(hostname: &str) -> Result<(PKey<Private>, X509), ErrorStack> {
+	let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+	let key = PKey::from_ec_key(EcKey::generate(&group)?)?;
+
+	let mut name = X509Name::builder()?;
+	name.append_entry_by_nid(Nid::COMMONNAME, hostname)?;
+	let name = name.build();
+
+	let mut serial = BigNum::new()?;
+	serial.rand(128, MsbOption::MAYBE_ZERO, false)?;
+
+	let mut cert = X509::builder()?;
+	cert.set_version(2)?; // X.509 v3
+	cert.set_serial_number(serial.to_asn1_integer()?.as_ref())?;
+	cert.set_subject_name(&name)?;
+	cert.set_issuer_name(&name)?;
+	cert.set_pubkey(&key)?;
+	cert.set_not_before(Asn1Time::days_from_now(0)?.as_ref())?;
+	cert.set_not_after(Asn1Time::days_from_now(GENERATED_LIFETIME_DAYS)?.as_ref())?;
+	let names = SubjectAlternativeName::new()
+		.dns(hostname)
+		.dns("localhost")
+		.build(&cert.x509v3_context(None, None))?;
+	cert.append_extension(names)?;
+	cert.sign(&key, MessageDigest::sha256())?;
+
+	Ok((key, cert.build()))
+}
+
+// MARK: - Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use openssl::x509::X509Ref;
+	use tokio::time::timeout;
+
+	/// Writes a fresh identity into the given directory, dated `offset` into the future
+	/// so that the change is visible even on filesystems with coarse timestamps.
+	fn write_identity(dir: &Path, offset: Duration) -> Vec<u8> {
+		let (key, cert) = generate("localhost").expect("Identity should generate");
+		let files = [
+			("key.pem", key.private_key_to_pem_pkcs8()),
+			("cert.pem", cert.to_pem()),
+		];
+		for (name, pem) in files {
+			let path = dir.join(name);
+			fs::write(&path, pem.expect("PEM should encode")).expect("File should write");
+			let file = fs::File::options()
+				.write(true)
+				.open(&path)
+				.expect("File should open");
+			let time = SystemTime::now()
+				.checked_add(offset)
+				.expect("Time should not overflow");
+			file.set_modified(time).expect("Time should set");
+		}
+		cert.digest(MessageDigest::sha256())
+			.expect("Digest should compute")
+			.to_vec()
+	}
+
+	/// A temporary directory, which is removed when it's dropped.
+	struct TempDir(PathBuf);
+
+	impl Drop for TempDir {
+		fn drop(&mut self) {
+			_ = fs::remove_dir_all(&self.0);
+		}
+	}
+
+	/// A fresh, empty temporary directory just for this test run.
+	fn temp_dir(name: &str) -> TempDir {
+		let dir =
+			std::env::temp_dir().join(format!("portfolio-certs-{}-{name}", std::process::id()));
+		_ = fs::remove_dir_all(&dir);
+		fs::create_dir_all(&dir).expect("Temp dir should exist");
+		TempDir(dir)
+	}
+
+	/// Cert paths in a fresh temporary directory, which lives as long as the guard.
+	fn temp_certs(name: &str) -> (TempDir, Certs) {
+		let dir = temp_dir(name);
+		let certs = Certs {
+			key: dir.0.join("key.pem"),
+			cert: dir.0.join("cert.pem"),
+		};
+		(dir, certs)
+	}
+
+	/// A config that looks for certs in the given directory, without generating any.
+	fn config_for(dir: Option<&Path>) -> Config {
+		let mut config = Config::default();
+		config.gemini.certs_dir = dir.map(Path::to_owned);
+		config.gemini.generate_certificate = false;
+		config
+	}
+
+	fn fingerprint(acceptor: &SslAcceptor) -> Vec<u8> {
+		acceptor
+			.context()
+			.certificate()
+			.expect("Acceptor should have a certificate")
+			.digest(MessageDigest::sha256())
+			.expect("Digest should compute")
+			.to_vec()
+	}
+
+	#[tokio::test]
+	async fn reloads_changed_certificates() {
+		let (_dir, certs) = temp_certs("reload");
+		let dir = certs.cert.parent().expect("Cert should be in a directory");
+		let first = write_identity(dir, Duration::ZERO);
+		let (sender, mut receiver) = watch::channel(certs.acceptor().expect("Certs should load"));
+		assert_eq!(fingerprint(&receiver.borrow_and_update()), first);
+
+		let shutdown = Shutdown::default();
+		let interval = Duration::from_millis(10);
+		let watcher = certs.watch(&sender, interval, &shutdown);
+		let renewal = async {
+			let second = write_identity(dir, Duration::from_secs(1));
+			timeout(Duration::from_secs(5), receiver.changed())
+				.await
+				.expect("Certs should reload")
+				.expect("Sender should live");
+			assert_eq!(fingerprint(&receiver.borrow()), second);
+			shutdown.trigger();
+		};
+		let (result, ()) = tokio::join!(watcher, renewal);
+		result.expect("Watcher should stop cleanly");
+	}
+
+	#[test]
+	fn keeps_current_certificate_when_reload_fails() {
+		let (_dir, certs) = temp_certs("broken");
+		let dir = certs.cert.parent().expect("Cert should be in a directory");
+		let first = write_identity(dir, Duration::ZERO);
+		let (sender, receiver) = watch::channel(certs.acceptor().expect("Certs should load"));
+
+		fs::write(&certs.cert, "not a certificate").expect("File should write");
+		certs.reload(&sender);
+		assert!(
+			!receiver.has_changed().expect("Sender should live"),
+			"Broken certs should not replace good ones"
+		);
+		assert_eq!(fingerprint(&receiver.borrow()), first);
+	}
+
+	#[test]
+	fn generates_stable_certificate_when_missing() {
+		let temp = temp_dir("generated");
+		let dir = temp.0.join("certs");
+		let mut config = Config::default();
+		config.gemini.certs_dir = Some(dir.clone());
+		config.gemini.generate_certificate = true;
+
+		let certs = Certs::from_config(&config).expect("Certs should generate");
+		let acceptor = certs.acceptor().expect("Generated certs should load");
+		let names = acceptor
+			.context()
+			.certificate()
+			.and_then(X509Ref::subject_alt_names)
+			.expect("Cert should have alternative names");
+		let names = names
+			.iter()
+			.filter_map(|name| name.dnsname())
+			.collect::<Vec<_>>();
+		assert_eq!(names, ["average.name", "localhost"]);
+
+		// Later runs reuse the same certificate
+		let again = Certs::from_config(&config).expect("Certs should load");
+		let again = again.acceptor().expect("Certs should load");
+		assert_eq!(fingerprint(&again), fingerprint(&acceptor));
+
+		#[cfg(unix)]
+		{
+			use std::os::unix::fs::PermissionsExt;
+			let metadata = fs::metadata(dir.join("key.pem")).expect("Key should exist");
+			assert_eq!(
+				metadata.permissions().mode() & 0o777,
+				0o600,
+				"Key should be private"
+			);
+		}
+	}
+
+	#[test]
+	fn rejects_missing_certs_dir_config() {
+		let result = Certs::from_config(&config_for(None));
+		assert!(
+			matches!(result, Err(CertError::NoCertsDir)),
+			"Missing certs_dir should fail"
+		);
+	}
+
+	#[test]
+	fn rejects_missing_certs_dir() {
+		let dir = Path::new("/nonexistent/certs");
+		let Err(CertError::DirNotFound { path }) = Certs::from_config(&config_for(Some(dir)))
+		else {
+			panic!("Expected a missing directory error");
+		};
+		assert_eq!(path, dir);
+	}
+
+	#[test]
+	fn rejects_missing_files() {
+		let (_dir, certs) = temp_certs("missing");
+		let dir = certs.cert.parent().expect("Cert should be in a directory");
+		let Err(CertError::FileNotFound { path }) = Certs::from_config(&config_for(Some(dir)))
+		else {
+			panic!("Expected a missing file error");
+		};
+		assert_eq!(path, certs.key);
+
+		write_identity(dir, Duration::ZERO);
+		fs::remove_file(&certs.cert).expect("Cert should be removable");
+		let Err(CertError::FileNotFound { path }) = Certs::from_config(&config_for(Some(dir)))
+		else {
+			panic!("Expected a missing file error");
+		};
+		assert_eq!(path, certs.cert);
+	}
+
+	#[test]
+	fn rejects_unreadable_files() {
+		let (_dir, certs) = temp_certs("unreadable");
+		let dir = certs.cert.parent().expect("Cert should be in a directory");
+		write_identity(dir, Duration::ZERO);
+		fs::remove_file(&certs.key).expect("Key should be removable");
+		fs::create_dir_all(&certs.key).expect("Directory should be creatable");
+
+		let certs = Certs::from_config(&config_for(Some(dir))).expect("Files should exist");
+		let Err(CertError::ReadFile { path, .. }) = certs.acceptor() else {
+			panic!("Expected a read error");
+		};
+		assert_eq!(path, certs.key);
+	}
+
+	#[test]
+	fn rejects_malformed_key() {
+		let (_dir, certs) = temp_certs("malformed-key");
+		let dir = certs.cert.parent().expect("Cert should be in a directory");
+		write_identity(dir, Duration::ZERO);
+		fs::write(&certs.key, "not a key").expect("File should write");
+
+		let Err(CertError::ParseKey { path, .. }) = certs.acceptor() else {
+			panic!("Expected a key parse error");
+		};
+		assert_eq!(path, certs.key);
+	}
+
+	#[test]
+	fn rejects_malformed_cert() {
+		let (_dir, certs) = temp_certs("malformed-cert");
+		let dir = certs.cert.parent().expect("Cert should be in a directory");
+		write_identity(dir, Duration::ZERO);
+		fs::write(&certs.cert, "not a certificate").expect("File should write");
+
+		let Err(CertError::ParseCert { path, .. }) = certs.acceptor() else {
+			panic!("Expected a certificate parse error");
+		};
+		assert_eq!(path, certs.cert);
+	}
+
+	#[test]
+	fn rejects_mismatched_key() {
+		let (_dir, certs) = temp_certs("mismatched");
+		let dir = certs.cert.parent().expect("Cert should be in a directory");
+		write_identity(dir, Duration::ZERO);
+		let (other_key, _) = generate("localhost").expect("Identity should generate");
+		let pem = other_key
+			.private_key_to_pem_pkcs8()
+			.expect("PEM should encode");
+		fs::write(&certs.key, pem).expect("File should write");
+
+		let Err(CertError::KeyMismatch { key, cert }) = certs.acceptor() else {
+			panic!("Expected a key mismatch error");
+		};
+		assert_eq!(key, certs.key);
+		assert_eq!(cert, certs.cert);
+	}
+
+	#[test]
+	fn reports_unwritable_generated_files() {
+		let (_dir, certs) = temp_certs("unwritable");
+		let dir = certs.cert.parent().expect("Cert should be in a directory");
+		let blocker = dir.join("file");
+		fs::write(&blocker, "").expect("File should write");
+
+		// A directory can't be created inside a regular file
+		let certs_dir = blocker.join("certs");
+		let mut config = config_for(Some(&certs_dir));
+		config.gemini.generate_certificate = true;
+		let Err(CertError::WriteFile { path, .. }) = Certs::from_config(&config) else {
+			panic!("Expected a write error");
+		};
+		assert_eq!(path, certs_dir);
+	}
+}
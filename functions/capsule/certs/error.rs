@@ -0,0 +1,87 @@
+use openssl::error::ErrorStack;
+use std::{io, path::PathBuf};
+
+/// A problem with the Gemini capsule's TLS key or certificate.
+#[derive(Debug)]
+pub enum CertError {
+	/// No certs directory was configured.
+	NoCertsDir,
+
+	/// The configured certs directory does not exist.
+	DirNotFound { path: PathBuf },
+
+	/// The key or certificate file does not exist.
+	FileNotFound { path: PathBuf },
+
+	/// The key or certificate file could not be read.
+	ReadFile { path: PathBuf, source: io::Error },
+
+	/// The key file does not contain a PEM-encoded private key.
+	ParseKey { path: PathBuf, source: ErrorStack },
+
+	/// The certificate file does not contain a PEM-encoded certificate.
+	ParseCert { path: PathBuf, source: ErrorStack },
+
+	/// The private key does not belong to the certificate.
+	KeyMismatch { key: PathBuf, cert: PathBuf },
+
+	/// A generated key or certificate could not be saved.
+	WriteFile { path: PathBuf, source: io::Error },
+
+	/// OpenSSL failed to generate a certificate or set up TLS.
+	Tls(ErrorStack),
+}
+
+impl core::fmt::Display for CertError {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		match self {
+			Self::NoCertsDir => write!(f, "No Gemini certs directory is configured"),
+			Self::DirNotFound { path } => {
+				write!(f, "Gemini certs directory not found: {}", path.display())
+			}
+			Self::FileNotFound { path } => {
+				write!(f, "Gemini certs file not found: {}", path.display())
+			}
+			Self::ReadFile { path, source } => {
+				write!(f, "Failed to read {}: {source}", path.display())
+			}
+			Self::ParseKey { path, source } => {
+				write!(f, "Invalid private key in {}: {source}", path.display())
+			}
+			Self::ParseCert { path, source } => {
+				write!(f, "Invalid certificate in {}: {source}", path.display())
+			}
+			Self::KeyMismatch { key, cert } => write!(
+				f,
+				"The key in {} does not match the certificate in {}",
+				key.display(),
+				cert.display()
+			),
+			Self::WriteFile { path, source } => {
+				write!(f, "Failed to write {}: {source}", path.display())
+			}
+			Self::Tls(source) => write!(f, "TLS setup failed: {source}"),
+		}
+	}
+}
+
+impl core::error::Error for CertError {
+	fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+		match self {
+			Self::ReadFile { source, .. } | Self::WriteFile { source, .. } => Some(source),
+			Self::ParseKey { source, .. } | Self::ParseCert { source, .. } | Self::Tls(source) => {
+				Some(source)
+			}
+			Self::NoCertsDir
+			| Self::DirNotFound { .. }
+			| Self::FileNotFound { .. }
+			| Self::KeyMismatch { .. } => None,
+		}
+	}
+}
+
+impl From<ErrorStack> for CertError {
+	fn from(value: ErrorStack) -> Self {
+		Self::Tls(value)
+	}
+}
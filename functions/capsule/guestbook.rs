@@ -0,0 +1,339 @@
+use crate::config::GuestbookConfig;
+use core::{net::IpAddr, time::Duration};
+use fluffer::Status;
+use std::collections::HashMap;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tokio::{fs, io::AsyncWriteExt, sync::Mutex};
+
+/// A signed guestbook entry.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Entry {
+	/// The entry's line in the entries file, counting from 1. Moderators hide
+	/// entries by listing their numbers in the hide-list.
+	pub number: usize,
+
+	/// When the entry was signed, in seconds since the Unix epoch.
+	pub signed_at: u64,
+
+	/// What the signer wrote, on one line.
+	pub message: String,
+}
+
+/// Why a message couldn't be added to the guestbook.
+#[derive(Debug)]
+pub enum SignError {
+	/// The message was missing or blank. Holds the most characters allowed.
+	Empty(usize),
+
+	/// The message was longer than the given number of characters.
+	TooLong(usize),
+
+	/// The client signed too recently, and should wait the given number of seconds.
+	SlowDown(u64),
+
+	/// The entries file couldn't be written.
+	Io(io::Error),
+}
+
+#[fluffer::async_trait]
+impl fluffer::GemBytes for SignError {
+	async fn gem_bytes(self) -> Vec<u8> {
+		match self {
+			Self::Empty(max_length) => (
+				Status::Input,
+				format!("Sign the guestbook (up to {max_length} characters)"),
+			)
+				.gem_bytes(),
+			Self::TooLong(max_length) => (
+				Status::Input,
+				format!("That's too long. Try again, in up to {max_length} characters"),
+			)
+				.gem_bytes(),
+			Self::SlowDown(secs) => (Status::SlowDown, secs.to_string()).gem_bytes(),
+			Self::Io(_) => (Status::TemporaryFailure, "Temporary failure.".to_owned()).gem_bytes(),
+		}
+		.await
+	}
+}
+
+/// A guestbook kept in a local file, which each client may sign once per cooldown.
+pub struct Guestbook {
+	entries_path: PathBuf,
+	hidden_path: PathBuf,
+	max_length: usize,
+	cooldown: Duration,
+
+	/// When each client last signed. Held while appending, so that entries don't interleave.
+	last_signed: Mutex<HashMap<IpAddr, Instant>>,
+}
+
+impl Guestbook {
+	pub fn new(config: &GuestbookConfig) -> Self {
+		Self {
+			entries_path: config.entries_path.clone(),
+			hidden_path: config.hidden_path.clone(),
+			max_length: config.max_length,
+			cooldown: Duration::from_secs(config.cooldown_secs.into()),
+			last_signed: Mutex::new(HashMap::new()),
+		}
+	}
+
+	/// The entries that moderators haven't hidden, newest first.
+	pub async fn entries(&self) -> io::Result<Vec<Entry>> {
+		let hidden = read_if_present(&self.hidden_path).await?;
+		let hidden = hidden
+			.lines()
+			.filter_map(|line| line.split('#').next()?.trim().parse::<usize>().ok())
+			.collect::<Vec<_>>();
+
+		let entries = read_if_present(&self.entries_path).await?;
+		let mut entries = entries
+			.lines()
+			.enumerate()
+			.filter_map(|(index, line)| {
+				let (signed_at, message) = line.split_once('\t')?;
+				Some(Entry {
+					number: index.saturating_add(1),
+					signed_at: signed_at.parse().ok()?,
+					message: message.to_owned(),
+				})
+			})
+			.filter(|entry| !hidden.contains(&entry.number))
+			.collect::<Vec<_>>();
+		entries.reverse();
+		Ok(entries)
+	}
+
+	/// Adds the given message to the guestbook, unless it's blank or too long, or
+	/// the client at the given address signed within the cooldown.
+	pub async fn sign(&self, client: IpAddr, message: Option<&str>) -> Result<(), SignError> {
+		let message = one_line(message.unwrap_or_default());
+		if message.is_empty() {
+			return Err(SignError::Empty(self.max_length));
+		}
+		if message.chars().count() > self.max_length {
+			return Err(SignError::TooLong(self.max_length));
+		}
+
+		let client = client.to_canonical();
+		let now = Instant::now();
+		let mut last_signed = self.last_signed.lock().await;
+		if let Some(&at) = last_signed.get(&client) {
+			let wait = self.cooldown.saturating_sub(now.duration_since(at));
+			if !wait.is_zero() {
+				// Round up, so that the client doesn't come back too early
+				let secs = wait
+					.as_secs()
+					.saturating_add(u64::from(wait.subsec_nanos() > 0));
+				return Err(SignError::SlowDown(secs));
+			}
+		}
+
+		let signed_at = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.unwrap_or_default()
+			.as_secs();
+		append(&self.entries_path, &format!("{signed_at}\t{message}\n"))
+			.await
+			.map_err(SignError::Io)?;
+
+		// Forget clients whose cooldown is over, so the map doesn't grow forever
+		last_signed.retain(|_, at| now.duration_since(*at) < self.cooldown);
+		last_signed.insert(client, now);
+		Ok(())
+	}
+}
+
+/// The guestbook page, listing the given entries.
+pub fn page(entries: &[Entry]) -> String {
+	let mut lines = vec![
+		"# Guestbook".to_owned(),
+		String::new(),
+		"=> /guestbook/sign Sign the guestbook".to_owned(),
+	];
+	if entries.is_empty() {
+		lines.push(String::new());
+		lines.push("No one has signed yet. Be the first!".to_owned());
+	}
+	for entry in entries {
+		let signed_at = UNIX_EPOCH
+			.checked_add(Duration::from_secs(entry.signed_at))
+			.map(httpdate::fmt_http_date)
+			.unwrap_or_default();
+		lines.push(String::new());
+		lines.push(format!("### #{} · {signed_at}", entry.number));
+		lines.push(format!("> {}", entry.message));
+	}
+	lines.push(String::new());
+	lines.push("=> / Return home".to_owned());
+	lines.push(String::new());
+	lines.join("\n")
+}
+
+/// The given text with control characters (like line breaks and tabs) replaced
+/// by spaces, and without surrounding whitespace, so that it fits on one line of
+/// the entries file and of gemtext.
+fn one_line(text: &str) -> String {
+	let text = text
+		.chars()
+		.map(|char| if char.is_control() { ' ' } else { char })
+		.collect::<String>();
+	text.trim().to_owned()
+}
+
+/// The contents of the file at the given path, or nothing if there's no such file.
+async fn read_if_present(path: &Path) -> io::Result<String> {
+	match fs::read_to_string(path).await {
+		Ok(text) => Ok(text),
+		Err(err) if err.kind() == ErrorKind::NotFound => Ok(String::new()),
+		Err(err) => Err(err),
+	}
+}
+
+/// Appends the given text to the file at the given path, creating the file and
+/// its directory if needed.
+async fn append(path: &Path, text: &str) -> io::Result<()> {
+	if let Some(dir) = path.parent()
+		&& !dir.as_os_str().is_empty()
+	{
+		fs::create_dir_all(dir).await?;
+	}
+	let mut file = fs::OpenOptions::new()
+		.create(true)
+		.append(true)
+		.open(path)
+		.await?;
+	file.write_all(text.as_bytes()).await?;
+	file.flush().await
+}
+
+// MARK: - Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use core::net::{Ipv4Addr, Ipv6Addr};
+	use test_case::test_case;
+
+	const CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+
+	/// A guestbook in a fresh temporary directory, which is removed when it's dropped.
+	struct TempGuestbook {
+		book: Guestbook,
+		dir: PathBuf,
+	}
+
+	impl Drop for TempGuestbook {
+		fn drop(&mut self) {
+			_ = std::fs::remove_dir_all(&self.dir);
+		}
+	}
+
+	impl core::ops::Deref for TempGuestbook {
+		type Target = Guestbook;
+
+		fn deref(&self) -> &Guestbook {
+			&self.book
+		}
+	}
+
+	fn guestbook(name: &str, cooldown_secs: u32) -> TempGuestbook {
+		let dir =
+			std::env::temp_dir().join(format!("portfolio-guestbook-{}-{name}", std::process::id()));
+		_ = std::fs::remove_dir_all(&dir);
+		let book = Guestbook::new(&GuestbookConfig {
+			entries_path: dir.join("entries.tsv"),
+			hidden_path: dir.join("hidden.txt"),
+			max_length: 10,
+			cooldown_secs,
+		});
+		TempGuestbook { book, dir }
+	}
+
+	#[test_case("Hi!", "Hi!" ; "plain")]
+	#[test_case("  Hi\tthere\r\n=> /evil  ", "Hi there  => /evil" ; "control characters")]
+	fn keeps_message_on_one_line(message: &str, expected: &str) {
+		assert_eq!(one_line(message), expected);
+	}
+
+	#[tokio::test]
+	async fn lists_entries_newest_first() {
+		let book = guestbook("lists", 0);
+		assert_eq!(book.entries().await.expect("Entries should read"), vec![]);
+
+		for (client, message) in [(CLIENT, "First"), (CLIENT, "Second")] {
+			book.sign(client, Some(message))
+				.await
+				.expect("Entry should save");
+		}
+		let entries = book.entries().await.expect("Entries should read");
+		let messages = entries
+			.iter()
+			.map(|entry| (entry.number, entry.message.as_str()))
+			.collect::<Vec<_>>();
+		assert_eq!(messages, vec![(2, "Second"), (1, "First")]);
+
+		let page = page(&entries);
+		assert!(page.starts_with("# Guestbook\n\n=> /guestbook/sign Sign the guestbook\n"));
+		assert!(page.contains("\n### #2 · "));
+		assert!(page.contains("\n> Second\n"));
+	}
+
+	#[tokio::test]
+	async fn hides_listed_entries() {
+		let book = guestbook("hides", 0);
+		for message in ["Nice site", "Spam", "Thanks"] {
+			book.sign(CLIENT, Some(message))
+				.await
+				.expect("Entry should save");
+		}
+		std::fs::write(&book.hidden_path, "# Spam\n2\n\nnot a number\n")
+			.expect("Hide-list should write");
+
+		let entries = book.entries().await.expect("Entries should read");
+		let numbers = entries.iter().map(|entry| entry.number).collect::<Vec<_>>();
+		assert_eq!(numbers, vec![3, 1]);
+	}
+
+	#[test_case(None ; "missing")]
+	#[test_case(Some(" \n ") ; "blank")]
+	#[tokio::test]
+	async fn rejects_empty_message(message: Option<&str>) {
+		let book = guestbook(&format!("rejects-empty-{}", message.is_some()), 0);
+		let result = book.sign(CLIENT, message).await;
+		assert!(matches!(result, Err(SignError::Empty(10))), "{result:?}");
+		assert_eq!(book.entries().await.expect("Entries should read"), vec![]);
+	}
+
+	#[tokio::test]
+	async fn rejects_long_message() {
+		let book = guestbook("rejects-long", 0);
+		let result = book.sign(CLIENT, Some("Eleven char")).await;
+		assert!(matches!(result, Err(SignError::TooLong(10))), "{result:?}");
+		assert_eq!(book.entries().await.expect("Entries should read"), vec![]);
+	}
+
+	#[tokio::test]
+	async fn throttles_each_client() {
+		let book = guestbook("throttles", 60);
+		book.sign(CLIENT, Some("Hello"))
+			.await
+			.expect("First entry should save");
+
+		let result = book.sign(CLIENT, Some("Again")).await;
+		assert!(matches!(result, Err(SignError::SlowDown(60))), "{result:?}");
+
+		// The same client over IPv6 is still the same client
+		let mapped = IpAddr::V6(Ipv4Addr::new(192, 0, 2, 1).to_ipv6_mapped());
+		let result = book.sign(mapped, Some("Again")).await;
+		assert!(matches!(result, Err(SignError::SlowDown(_))), "{result:?}");
+
+		book.sign(IpAddr::V6(Ipv6Addr::LOCALHOST), Some("Hi"))
+			.await
+			.expect("Other client should sign");
+		let entries = book.entries().await.expect("Entries should read");
+		assert_eq!(entries.len(), 2);
+	}
+}
@@ -0,0 +1,237 @@
+mod terms;
+use terms::search_terms;
+
+use std::collections::BTreeMap;
+
+/// A Way that search covers.
+struct SearchDocument {
+	path: &'static str,
+	title: &'static str,
+	date: &'static str,
+
+	/// The Way's text, without its frontmatter or formatting.
+	text: &'static str,
+}
+
+// Generated in build.rs:
+include!(concat!(env!("OUT_DIR"), "/search_index.rs"));
+
+/// The most results to list.
+const MAX_RESULTS: usize = 20;
+
+/// How many words of context to show before the first match in a snippet.
+const SNIPPET_WORDS_BEFORE: usize = 8;
+
+/// How many words to show in a snippet, including the match and its context.
+const SNIPPET_WORDS: usize = 24;
+
+/// A Way that matches a search.
+struct Hit {
+	document: &'static SearchDocument,
+
+	/// How many of the query's terms the Way matches.
+	matched_terms: usize,
+
+	/// How well the Way matches, summed over the query's terms.
+	score: u32,
+}
+
+/// The search results page for the given query.
+pub fn page(query: &str) -> String {
+	// The query goes into headings, so keep it on one line
+	let query = query.split_whitespace().collect::<Vec<_>>().join(" ");
+	let terms = query_terms(&query);
+	let hits = search(&terms);
+
+	let mut lines = vec![
+		format!("# Search results for “{query}”"),
+		String::new(),
+		"=> /search Search again".to_owned(),
+	];
+	if hits.is_empty() {
+		lines.push(String::new());
+		lines.push("No Ways match that search.".to_owned());
+	}
+	for hit in hits {
+		let document = hit.document;
+		lines.push(String::new());
+		lines.push(format!(
+			"=> {} {} - {}",
+			document.path, document.date, document.title
+		));
+		lines.push(format!("> {}", snippet(document.text, &terms)));
+	}
+	lines.push(String::new());
+	lines.push("=> /ways Back to Ways".to_owned());
+	lines.push(String::new());
+	lines.join("\n")
+}
+
+/// The Ways that match any of the given terms, best first: those that match more
+/// of the terms, then those with the higher score.
+fn search(terms: &[String]) -> Vec<Hit> {
+	let mut hits: BTreeMap<usize, Hit> = BTreeMap::new();
+	for term in terms {
+		let mut matched = Vec::new();
+		for (_, postings) in indexed_terms_matching(term) {
+			for &(number, weight) in *postings {
+				let Some(document) = SEARCH_DOCUMENTS.get(number) else {
+					continue;
+				};
+				let hit = hits.entry(number).or_insert(Hit {
+					document,
+					matched_terms: 0,
+					score: 0,
+				});
+				hit.score = hit.score.saturating_add(weight);
+				if !matched.contains(&number) {
+					matched.push(number);
+					hit.matched_terms = hit.matched_terms.saturating_add(1);
+				}
+			}
+		}
+	}
+
+	// Ties keep the index's order, which is newest first
+	let mut hits = hits.into_values().collect::<Vec<_>>();
+	hits.sort_by(|a, b| {
+		b.matched_terms
+			.cmp(&a.matched_terms)
+			.then(b.score.cmp(&a.score))
+	});
+	hits.truncate(MAX_RESULTS);
+	hits
+}
+
+/// The indexed terms that the given query term matches: the term itself, and any
+/// that start with it, if it's long enough that those are likely what was meant.
+fn indexed_terms_matching(term: &str) -> &'static [(&'static str, &'static [(usize, u32)])] {
+	let start = SEARCH_TERMS.partition_point(|(indexed, _)| *indexed < term);
+	let rest = SEARCH_TERMS.get(start..).unwrap_or_default();
+	let count = rest.partition_point(|(indexed, _)| term_matches(indexed, term));
+	rest.get(..count).unwrap_or_default()
+}
+
+/// Whether the given query term matches the given term from a document.
+fn term_matches(indexed: &str, term: &str) -> bool {
+	if term.chars().nth(2).is_some() {
+		indexed.starts_with(term)
+	} else {
+		indexed == term
+	}
+}
+
+/// The distinct search terms in the given query.
+fn query_terms(query: &str) -> Vec<String> {
+	let mut terms = Vec::new();
+	for term in search_terms(query) {
+		if !terms.contains(&term) {
+			terms.push(term);
+		}
+	}
+	terms
+}
+
+/// A few words of the given text around its first word that matches one of the
+/// given terms, or from its start if none does.
+fn snippet(text: &str, terms: &[String]) -> String {
+	let words = text.split_whitespace().collect::<Vec<_>>();
+	let first_match = words
+		.iter()
+		.position(|word| {
+			search_terms(word)
+				.any(|word_term| terms.iter().any(|term| term_matches(&word_term, term)))
+		})
+		.unwrap_or_default();
+
+	let start = first_match.saturating_sub(SNIPPET_WORDS_BEFORE);
+	let end = start.saturating_add(SNIPPET_WORDS).min(words.len());
+	let snippet = words.get(start..end).unwrap_or_default().join(" ");
+	let before = if start > 0 { "… " } else { "" };
+	let after = if end < words.len() { " …" } else { "" };
+	format!("{before}{snippet}{after}")
+}
+
+// MARK: - Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use test_case::test_case;
+
+	#[test]
+	fn index_is_sorted() {
+		assert!(!SEARCH_DOCUMENTS.is_empty());
+		assert!(SEARCH_TERMS.windows(2).all(|pair| match pair {
+			[(a, _), (b, _)] => a < b,
+			_ => false,
+		}));
+	}
+
+	#[test_case("Docker  firewall!", &["docker", "firewall"] ; "words")]
+	#[test_case("a B b ÜBER", &["über"] ; "short and repeated")]
+	#[test_case("--", &[] ; "no words")]
+	fn splits_query(query: &str, expected: &[&str]) {
+		assert_eq!(query_terms(query), expected);
+	}
+
+	#[test_case("ways", "way", true ; "prefix")]
+	#[test_case("ways", "ways", true ; "whole term")]
+	#[test_case("ways", "wa", false ; "short prefix")]
+	#[test_case("wa", "wa", true ; "short whole term")]
+	#[test_case("way", "ways", false ; "longer term")]
+	fn matches_term(indexed: &str, term: &str, expected: bool) {
+		assert_eq!(term_matches(indexed, term), expected);
+	}
+
+	#[test]
+	fn ranks_title_matches_first() {
+		for document in SEARCH_DOCUMENTS {
+			let terms = query_terms(document.title);
+			let hits = search(&terms);
+			let best = hits.first().expect("Title should match its Way");
+			assert_eq!(best.matched_terms, terms.len(), "{}", document.title);
+			assert!(hits.len() <= MAX_RESULTS);
+		}
+	}
+
+	#[test]
+	fn finds_nothing() {
+		let page = page("zzzqqqxxx");
+		assert!(page.starts_with("# Search results for “zzzqqqxxx”\n\n=> /search Search again\n"));
+		assert!(page.contains("\nNo Ways match that search.\n"));
+	}
+
+	#[test]
+	fn lists_results() {
+		let document = SEARCH_DOCUMENTS.first().expect("There should be Ways");
+		let page = page(&format!("{}\n=> /evil", document.title));
+		assert!(page.starts_with("# Search results for “"));
+		assert!(!page.contains("\n=> /evil"));
+		assert!(page.contains(&format!(
+			"\n=> {} {} - {}\n> ",
+			document.path, document.date, document.title
+		)));
+	}
+
+	#[test_case("w20", Some(12), 35 ; "middle")]
+	#[test_case("w3", None, 24 ; "near start")]
+	#[test_case("W39", Some(31), 40 ; "near end")]
+	#[test_case("nothing", None, 24 ; "no match")]
+	fn snips_text(query: &str, first: Option<u8>, last: u8) {
+		let text = (1..=40)
+			.map(|n| format!("w{n}"))
+			.collect::<Vec<_>>()
+			.join(" \n ");
+		let words = (first.unwrap_or(1)..=last)
+			.map(|n| format!("w{n}"))
+			.collect::<Vec<_>>()
+			.join(" ");
+		let before = if first.is_some() { "… " } else { "" };
+		let after = if last < 40 { " …" } else { "" };
+		assert_eq!(
+			snippet(&text, &query_terms(query)),
+			format!("{before}{words}{after}")
+		);
+	}
+}
@@ -0,0 +1,7 @@
+/// Splits the given text into lowercase search terms. Both the index that build.rs
+/// writes and the queries against it are split this way.
+pub fn search_terms(text: &str) -> impl Iterator<Item = String> {
+	text.split(|char: char| !char.is_alphanumeric())
+		.filter(|term| term.chars().nth(1).is_some())
+		.map(str::to_lowercase)
+}
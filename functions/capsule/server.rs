@@ -0,0 +1,502 @@
+use super::{Redirect, RequestError, URL_MAX_BYTES};
+use crate::config::RedirectTable;
+use crate::shutdown::Shutdown;
+use core::{net::SocketAddr, pin::Pin, time::Duration};
+use fluffer::{Client, GemBytes, GemCall, Status};
+use matchit::Router;
+use openssl::{
+	ssl::{Ssl, SslAcceptor},
+	x509::X509,
+};
+use percent_encoding::percent_decode_str;
+use std::{io, sync::Arc};
+use tokio::{
+	io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader},
+	net::{TcpListener, TcpStream},
+	sync::watch,
+	task::JoinSet,
+	time::{sleep, timeout},
+};
+use tokio_openssl::SslStream;
+use url::Url;
+
+/// How long a client has to finish the TLS handshake and send its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long to wait before accepting again after accepting a connection fails.
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Accepts a request URL that the server should answer, or says why not.
+type UrlCheck<S> = fn(&S, &Url) -> Result<(), RequestError>;
+
+/// Serves Gemini requests like `fluffer::App` does, but stops gracefully on shutdown.
+pub struct Server<S> {
+	state: S,
+	routes: Router<Box<dyn GemCall<S> + Send + Sync>>,
+
+	/// The redirect table, the web origin for targets that no route serves, and
+	/// the check that a request's URL must pass before it's redirected.
+	redirects: Option<(RedirectTable, String, UrlCheck<S>)>,
+}
+
+impl<S> Server<S>
+where
+	S: Clone + Send + Sync + 'static,
+{
+	/// Creates a server with no routes, whose handlers receive the given state.
+	pub fn new(state: S) -> Self {
+		Self {
+			state,
+			routes: Router::new(),
+			redirects: None,
+		}
+	}
+
+	/// Answers requests that match the given redirect table with a redirect, before
+	/// routing them. Local targets that no route serves point to the given web origin.
+	///
+	/// Only requests whose URL passes `check` are redirected, so give it the same
+	/// check that the route handlers make.
+	pub fn redirects(mut self, table: RedirectTable, web_origin: &str, check: UrlCheck<S>) -> Self {
+		self.redirects = Some((table, web_origin.to_owned(), check));
+		self
+	}
+
+	/// Sends requests whose path matches the given route to the given handler.
+	pub fn route(mut self, path: &str, handler: impl GemCall<S> + Send + Sync + 'static) -> Self {
+		self.routes
+			.insert(path, Box::new(handler))
+			.expect("Gemini routes should be valid and not conflict");
+		self
+	}
+
+	/// Serves connections from the given listener until shutdown is triggered,
+	/// then waits up to `grace` for in-flight requests to finish.
+	///
+	/// Each new connection uses the latest acceptor from the given channel.
+	pub async fn serve(
+		self,
+		listener: TcpListener,
+		acceptor: watch::Receiver<SslAcceptor>,
+		shutdown: &Shutdown,
+		grace: Duration,
+	) {
+		let server = Arc::new(self);
+		let mut connections = JoinSet::new();
+
+		loop {
+			let (stream, peer) = tokio::select! {
+				accepted = listener.accept() => match accepted {
+					Ok(accepted) => accepted,
+					Err(err) => {
+						// Likely out of file descriptors, so give some connections a moment to close
+						eprintln!("[gemini] Couldn't accept a connection: {err}");
+						sleep(ACCEPT_BACKOFF).await;
+						continue;
+					}
+				},
+				() = shutdown.triggered() => break,
+			};
+			let ssl = match Ssl::new(acceptor.borrow().context()) {
+				Ok(ssl) => ssl,
+				Err(err) => {
+					eprintln!("[gemini] Couldn't accept a connection from {peer}: {err}");
+					continue;
+				}
+			};
+
+			// Forget connections that have already finished
+			while connections.try_join_next().is_some() {}
+
+			let server = Arc::clone(&server);
+			connections.spawn(async move {
+				// Clients that hang up or botch the handshake aren't our problem
+				_ = server.handle(stream, ssl, peer).await;
+			});
+		}
+
+		// Stop accepting new connections, and let the current ones finish
+		drop(listener);
+		let drain = async { while connections.join_next().await.is_some() {} };
+		if timeout(grace, drain).await.is_err() {
+			eprintln!(
+				"[gemini] Closing {} connection(s) that didn't finish in time",
+				connections.len()
+			);
+		}
+	}
+
+	/// Reads one request from the given connection, and writes the response.
+	async fn handle(&self, stream: TcpStream, ssl: Ssl, peer: SocketAddr) -> io::Result<()> {
+		let mut stream = SslStream::new(ssl, stream)?;
+		let request = timeout(REQUEST_TIMEOUT, async {
+			Pin::new(&mut stream)
+				.accept()
+				.await
+				.map_err(io::Error::other)?;
+			read_request(&mut stream).await
+		});
+
+		let response = match request.await?? {
+			Some(url) => {
+				let cert = stream.ssl().peer_certificate();
+				self.respond(url, cert, peer).await
+			}
+			None => RequestError::BadRequest.gem_bytes().await,
+		};
+
+		stream.write_all(&response).await?;
+		stream.shutdown().await
+	}
+
+	/// Routes the given request to its handler, and returns the response.
+	async fn respond(&self, mut url: Url, cert: Option<X509>, peer: SocketAddr) -> Vec<u8> {
+		if url.path().is_empty() {
+			url.set_path("/");
+		}
+		match self.redirect(&url) {
+			Ok(Some(redirect)) => return redirect.gem_bytes().await,
+			Ok(None) => {}
+			Err(err) => return err.gem_bytes().await,
+		}
+		let Ok(path) = percent_decode_str(url.path()).decode_utf8() else {
+			return RequestError::BadRequest.gem_bytes().await;
+		};
+		let path = path.into_owned();
+		let Ok(route) = self.routes.at(&path) else {
+			return RequestError::NotFound.gem_bytes().await;
+		};
+
+		match Client::new(self.state.clone(), url, cert, &route.params, peer, None) {
+			Ok(client) => route.value.gem_call(client).await,
+			Err(err) => {
+				(Status::CertificateNotValid, err.to_string())
+					.gem_bytes()
+					.await
+			}
+		}
+	}
+
+	/// Whether a route serves the given percent-encoded path.
+	pub fn has_route(&self, path: &str) -> bool {
+		percent_decode_str(path)
+			.decode_utf8()
+			.is_ok_and(|path| self.routes.at(&path).is_ok())
+	}
+
+	/// Where the redirect table sends requests for the given URL, if anywhere, or
+	/// the error for a URL that fails the redirect check.
+	fn redirect(&self, url: &Url) -> Result<Option<Redirect>, RequestError> {
+		let Some((table, web_origin, check)) = &self.redirects else {
+			return Ok(None);
+		};
+		let Some(redirection) = table.resolve(url.path(), url.query()) else {
+			return Ok(None);
+		};
+		check(&self.state, url)?;
+
+		let served = redirection
+			.local_path()
+			.is_none_or(|path| self.has_route(path));
+
+		let location = if served {
+			redirection.location
+		} else {
+			format!("{web_origin}{}", redirection.location)
+		};
+		Ok(Some(Redirect {
+			location,
+			permanent: redirection.status.is_permanent(),
+		}))
+	}
+}
+
+/// Reads a request line, and returns its URL, or `None` if the request is malformed.
+async fn read_request<R>(stream: &mut R) -> io::Result<Option<Url>>
+where
+	R: AsyncRead + Unpin,
+{
+	// Leave room for the "\r\n"
+	let limit = URL_MAX_BYTES.saturating_add(2);
+	let mut reader = BufReader::new(stream.take(u64::try_from(limit).unwrap_or(u64::MAX)));
+	let mut line = Vec::new();
+	reader.read_until(b'\n', &mut line).await?;
+
+	let Some(line) = line.strip_suffix(b"\r\n") else {
+		return Ok(None);
+	};
+	let Ok(line) = str::from_utf8(line) else {
+		return Ok(None);
+	};
+	Ok(Url::parse(line).ok())
+}
+
+// MARK: - Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::capsule::certs::generate;
+	use core::net::Ipv4Addr;
+	use openssl::{
+		hash::MessageDigest,
+		ssl::{SslConnector, SslMethod, SslVerifyMode},
+	};
+	use test_case::test_case;
+	use tokio::{sync::Notify, task::JoinHandle};
+
+	const GRACE: Duration = Duration::from_secs(5);
+
+	/// Builds a TLS acceptor with a throwaway self-signed certificate.
+	fn acceptor() -> SslAcceptor {
+		let (key, cert) = generate("localhost").expect("Identity should generate");
+		let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls_server())
+			.expect("Acceptor should build");
+		builder.set_private_key(&key).expect("Key should load");
+		builder.set_certificate(&cert).expect("Cert should load");
+		builder.build()
+	}
+
+	/// Starts a server on a random local port.
+	async fn start(
+		server: Server<Arc<Notify>>,
+		acceptor: watch::Receiver<SslAcceptor>,
+		shutdown: &Shutdown,
+	) -> (SocketAddr, JoinHandle<()>) {
+		let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+			.await
+			.expect("Listener should bind");
+		let address = listener
+			.local_addr()
+			.expect("Listener should have an address");
+		let shutdown = shutdown.clone();
+		let task = tokio::spawn(async move {
+			server.serve(listener, acceptor, &shutdown, GRACE).await;
+		});
+		(address, task)
+	}
+
+	/// Opens a TLS connection to the given server, and sends the given request line.
+	async fn request(address: SocketAddr, url: &str) -> io::Result<SslStream<TcpStream>> {
+		let mut connector = SslConnector::builder(SslMethod::tls_client())?;
+		connector.set_verify(SslVerifyMode::NONE);
+		let ssl = connector
+			.build()
+			.configure()?
+			.verify_hostname(false)
+			.into_ssl("localhost")?;
+
+		let mut stream = SslStream::new(ssl, TcpStream::connect(address).await?)?;
+		Pin::new(&mut stream)
+			.connect()
+			.await
+			.map_err(io::Error::other)?;
+		stream.write_all(format!("{url}\r\n").as_bytes()).await?;
+		Ok(stream)
+	}
+
+	/// The fingerprint of the certificate that the server presented.
+	fn peer_fingerprint(stream: &SslStream<TcpStream>) -> Vec<u8> {
+		stream
+			.ssl()
+			.peer_certificate()
+			.expect("Server should present a certificate")
+			.digest(MessageDigest::sha256())
+			.expect("Digest should compute")
+			.to_vec()
+	}
+
+	async fn read_response(mut stream: SslStream<TcpStream>) -> String {
+		let mut response = String::new();
+		stream
+			.read_to_string(&mut response)
+			.await
+			.expect("Response should be readable");
+		response
+	}
+
+	/// A handler that announces that it started, then takes a moment to respond.
+	async fn slow(client: Client<Arc<Notify>>) -> &'static str {
+		client.state.notify_one();
+		tokio::time::sleep(Duration::from_millis(100)).await;
+		"Done"
+	}
+
+	#[test_case(b"gemini://localhost/\r\n", Some("gemini://localhost/") ; "request")]
+	#[test_case(b"gemini://localhost/\r\nmore", Some("gemini://localhost/") ; "trailing bytes")]
+	#[test_case(b"gemini://localhost/\n", None ; "missing carriage return")]
+	#[test_case(b"gemini://localhost/", None ; "unterminated")]
+	#[test_case(b"not a url\r\n", None ; "not a url")]
+	#[tokio::test]
+	async fn reads_request(request: &[u8], expected: Option<&str>) {
+		let url = read_request(&mut &request[..])
+			.await
+			.expect("Request should read");
+		assert_eq!(url.as_ref().map(Url::as_str), expected);
+	}
+
+	#[tokio::test]
+	async fn rejects_long_request() {
+		let path = "a".repeat(URL_MAX_BYTES);
+		let request = format!("gemini://localhost/{path}\r\n");
+		let url = read_request(&mut request.as_bytes())
+			.await
+			.expect("Request should read");
+		assert_eq!(url, None);
+	}
+
+	#[tokio::test]
+	async fn serves_requests() {
+		let shutdown = Shutdown::default();
+		let server = Server::new(Arc::new(Notify::new())).route("/", async |_| "Hello");
+		let (address, _task) = start(server, watch::channel(acceptor()).1, &shutdown).await;
+
+		let stream = request(address, "gemini://localhost/")
+			.await
+			.expect("Request should send");
+		assert_eq!(read_response(stream).await, "20 text/gemini\r\nHello");
+
+		let stream = request(address, "gemini://localhost/nope")
+			.await
+			.expect("Request should send");
+		assert_eq!(read_response(stream).await, "51 Page not found.\r\n");
+
+		let stream = request(address, "not a url")
+			.await
+			.expect("Request should send");
+		assert_eq!(read_response(stream).await, "59 Bad request.\r\n");
+	}
+
+	/// Accepts only Gemini requests for localhost, like the capsule's own check.
+	fn only_localhost(_: &Arc<Notify>, url: &Url) -> Result<(), RequestError> {
+		if url.scheme() == "gemini" && url.host_str() == Some("localhost") {
+			Ok(())
+		} else {
+			Err(RequestError::WrongHost)
+		}
+	}
+
+	#[tokio::test]
+	async fn redirects_before_routing() {
+		let shutdown = Shutdown::default();
+		let table = toml::from_str(
+			"[[redirect]]\nfrom = \"/how\"\nto = \"/ways\"\n\n[[redirect]]\nfrom = \"/pronouns\"\nto = \"/.well-known/pronouns\"\n\n[[redirect]]\nfrom = \"/ip\"\nto = \"https://ip.example.com\"\nstatus = 308\n\n[[redirect]]\nfrom = \"/old\"\nto = \"/ways\"\n",
+		)
+		.expect("Table should parse");
+		let server = Server::new(Arc::new(Notify::new()))
+			.redirects(table, "https://example.com", only_localhost)
+			.route("/ways", async |_| "Ways")
+			.route("/old", async |_| "Shadowed");
+		let (address, _task) = start(server, watch::channel(acceptor()).1, &shutdown).await;
+
+		for (url, expected) in [
+			("gemini://localhost/how", "30 /ways\r\n"),
+			("gemini://localhost/how?q", "30 /ways?q\r\n"),
+			(
+				"gemini://localhost/pronouns",
+				"30 https://example.com/.well-known/pronouns\r\n",
+			),
+			("gemini://localhost/ip", "31 https://ip.example.com\r\n"),
+			("gemini://localhost/old", "30 /ways\r\n"),
+			("gemini://localhost/ways", "20 text/gemini\r\nWays"),
+			("gemini://other.example/how", "53 Wrong host.\r\n"),
+			("https://localhost/how", "53 Wrong host.\r\n"),
+		] {
+			let stream = request(address, url).await.expect("Request should send");
+			assert_eq!(read_response(stream).await, expected, "Response to {url}");
+		}
+	}
+
+	#[tokio::test]
+	async fn finishes_in_flight_requests_on_shutdown() {
+		let started = Arc::new(Notify::new());
+		let shutdown = Shutdown::default();
+		let server = Server::new(Arc::clone(&started)).route("/slow", slow);
+		let (address, task) = start(server, watch::channel(acceptor()).1, &shutdown).await;
+
+		let stream = request(address, "gemini://localhost/slow")
+			.await
+			.expect("Request should send");
+		started.notified().await;
+		shutdown.trigger();
+
+		assert_eq!(read_response(stream).await, "20 text/gemini\r\nDone");
+		timeout(GRACE, task)
+			.await
+			.expect("Server should stop after draining")
+			.expect("Server should not panic");
+		assert!(
+			request(address, "gemini://localhost/slow").await.is_err(),
+			"Server should refuse new connections"
+		);
+	}
+
+	#[tokio::test]
+	async fn uses_new_certificates_for_new_connections() {
+		let started = Arc::new(Notify::new());
+		let shutdown = Shutdown::default();
+		let (certs, receiver) = watch::channel(acceptor());
+		let server = Server::new(Arc::clone(&started)).route("/slow", slow);
+		let (address, _task) = start(server, receiver, &shutdown).await;
+
+		let old = request(address, "gemini://localhost/slow")
+			.await
+			.expect("Request should send");
+		started.notified().await;
+		certs.send_replace(acceptor());
+		let new = request(address, "gemini://localhost/slow")
+			.await
+			.expect("Request should send");
+
+		assert_ne!(
+			peer_fingerprint(&old),
+			peer_fingerprint(&new),
+			"New connection should get the new certificate"
+		);
+		assert_eq!(read_response(old).await, "20 text/gemini\r\nDone");
+		assert_eq!(read_response(new).await, "20 text/gemini\r\nDone");
+	}
+
+	#[tokio::test]
+	async fn closes_stuck_requests_after_grace_period() {
+		let started = Arc::new(Notify::new());
+		let shutdown = Shutdown::default();
+		let server = Server::new(Arc::clone(&started)).route(
+			"/stuck",
+			async |client: Client<Arc<Notify>>| {
+				client.state.notify_one();
+				futures::future::pending::<&str>().await
+			},
+		);
+		let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+			.await
+			.expect("Listener should bind");
+		let address = listener
+			.local_addr()
+			.expect("Listener should have an address");
+		let task = tokio::spawn({
+			let shutdown = shutdown.clone();
+			async move {
+				let grace = Duration::from_millis(50);
+				server
+					.serve(listener, watch::channel(acceptor()).1, &shutdown, grace)
+					.await;
+			}
+		});
+
+		let mut stream = request(address, "gemini://localhost/stuck")
+			.await
+			.expect("Request should send");
+		started.notified().await;
+		shutdown.trigger();
+		timeout(GRACE, task)
+			.await
+			.expect("Server should stop after the grace period")
+			.expect("Server should not panic");
+
+		// The connection closes, possibly without a proper TLS goodbye
+		let mut response = String::new();
+		_ = stream.read_to_string(&mut response).await;
+		assert_eq!(response, "", "Stuck request gets no response");
+	}
+}
@@ -1,63 +1,995 @@
-use core::net::{IpAddr, Ipv4Addr};
-use std::{env, path::PathBuf};
+mod args;
+pub use args::*;
 
-#[derive(Clone)]
+mod error;
+pub use error::*;
+
+mod profile;
+pub use profile::*;
+
+mod redirects;
+pub use redirects::*;
+
+mod service;
+pub use service::*;
+
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use core::str::FromStr;
+use serde::Deserialize;
+use std::{
+	env,
+	ffi::OsString,
+	fs,
+	path::{Path, PathBuf},
+};
+
+/// The server's configuration.
+///
+/// Values are layered: built-in defaults first, then the TOML config file (if one
+/// is given with `--config` or `CONFIG_PATH`), then environment variables, then
+/// command-line flags. Each environment variable may instead be given as
+/// `{NAME}_FILE`, naming a file that contains the value, so that container secrets
+/// can be mounted.
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
+	/// The webservers to run. Defaults to every service included in this build.
+	/// Overridden by `SERVICES`, a comma-separated list, and by `--services`.
+	pub services: Vec<Service>,
+
+	/// Who the site belongs to.
+	pub profile: Profile,
+
+	/// A TOML file of redirects to use instead of the built-in table.
+	/// Overridden by `REDIRECTS_PATH`.
+	pub redirects_path: Option<PathBuf>,
+
+	/// The redirects read from `redirects_path`, or the built-in table.
+	#[serde(skip)]
+	pub redirects: RedirectTable,
+
+	/// Settings for the Gemini capsule.
+	pub gemini: GeminiConfig,
+
+	/// Settings for the HTTP website.
+	pub http: HttpConfig,
+
+	/// Settings for the capsule's guestbook.
+	pub guestbook: GuestbookConfig,
+
+	/// Settings for restarting failed services.
+	pub supervisor: SupervisorConfig,
+
+	/// Settings for stopping the services.
+	pub shutdown: ShutdownConfig,
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		Self {
+			services: Service::all(),
+			profile: Profile::default(),
+			redirects_path: None,
+			redirects: RedirectTable::default(),
+			gemini: GeminiConfig::default(),
+			http: HttpConfig::default(),
+			guestbook: GuestbookConfig::default(),
+			supervisor: SupervisorConfig::default(),
+			shutdown: ShutdownConfig::default(),
+		}
+	}
+}
+
+/// The `[gemini]` section of the config file.
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GeminiConfig {
 	/// The port on which the Gemini webserver should listen.
-	pub gemini_port: u16,
+	/// Overridden by `GEMINI_PORT`.
+	pub port: u16,
 
-	/// The hostname at which the Gemini webserver should listen.
-	/// Certificates are not generated automatically.
-	pub gemini_hostname: String,
+	/// The addresses at which the Gemini webserver should listen.
+	/// Overridden by `GEMINI_ADDRESSES`, a comma-separated list.
+	pub addresses: Vec<IpAddr>,
+
+	/// The hostname at which the Gemini webserver should listen. Defaults to the
+	/// domain of the profile's origin. Overridden by `GEMINI_HOSTNAME`.
+	pub hostname: Option<String>,
 
 	/// The local directory in which to look for TLS certificates for the Gemini webserver.
-	pub gemini_certs_dir: Option<PathBuf>,
+	/// Overridden by `GEMINI_CERTS_DIR`.
+	pub certs_dir: Option<PathBuf>,
 
+	/// Whether to generate a self-signed certificate for `hostname` and `localhost`
+	/// in `certs_dir` if there isn't one yet. On by default in debug builds.
+	/// Overridden by `GEMINI_GENERATE_CERTIFICATE`.
+	pub generate_certificate: bool,
+
+	/// Whether to read gemtext from `src/content/gemtext` for each request, instead
+	/// of the copies built into the server. On by default in debug builds.
+	/// Overridden by `GEMINI_SERVE_FROM_DISK`.
+	pub serve_from_disk: bool,
+}
+
+impl Default for GeminiConfig {
+	fn default() -> Self {
+		Self {
+			port: 1965,
+			addresses: vec![IpAddr::V6(Ipv6Addr::UNSPECIFIED)], // [::]
+			hostname: None,
+			certs_dir: Some(PathBuf::from(".certs")),
+			generate_certificate: cfg!(debug_assertions),
+			serve_from_disk: cfg!(all(debug_assertions, not(test))), // Tests check the embedded files
+		}
+	}
+}
+
+/// The `[http]` section of the config file.
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpConfig {
 	/// The port on which the HTTP webserver should listen.
-	pub http_port: u16,
+	/// Overridden by `HTTP_PORT`.
+	pub port: u16,
 
-	/// The address at which both the HTTP webserver should listen.
-	pub http_hostname: IpAddr,
+	/// The addresses at which the HTTP webserver should listen.
+	/// Overridden by `HTTP_ADDRESSES`, a comma-separated list.
+	pub addresses: Vec<IpAddr>,
+
+	/// Whether to read static files from `dist` for each request, instead of the
+	/// copies built into the server. On by default in debug builds.
+	/// Overridden by `HTTP_SERVE_FROM_DISK`.
+	pub serve_from_disk: bool,
 }
 
-impl Default for Config {
+impl Default for HttpConfig {
 	fn default() -> Self {
-		let gemini_hostname =
-			env::var("GEMINI_HOSTNAME").unwrap_or_else(|_| "average.name".to_owned());
-		let gemini_certs_dir = env::var("GEMINI_CERTS_DIR")
-			.ok()
-			.or_else(|| Some(".certs".to_owned()))
-			.map(PathBuf::from);
-		let gemini_port = env::var("GEMINI_PORT")
-			.ok()
-			.and_then(|p| p.parse::<u16>().ok())
-			.unwrap_or(1965);
-		let http_port = env::var("HTTP_PORT")
-			.ok()
-			.and_then(|p| p.parse::<u16>().ok())
-			.unwrap_or(8787);
+		Self {
+			port: 8787,
+			addresses: vec![IpAddr::V4(Ipv4Addr::UNSPECIFIED)], // 0.0.0.0
+			serve_from_disk: cfg!(all(debug_assertions, not(test))), // Tests check the embedded files
+		}
+	}
+}
+
+/// The `[guestbook]` section of the config file.
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GuestbookConfig {
+	/// The file that signed entries are appended to, one per line.
+	/// Overridden by `GUESTBOOK_ENTRIES_PATH`.
+	pub entries_path: PathBuf,
+
+	/// A file listing the numbers of entries to hide, one per line.
+	/// Overridden by `GUESTBOOK_HIDDEN_PATH`.
+	pub hidden_path: PathBuf,
+
+	/// The most characters that an entry may have.
+	/// Overridden by `GUESTBOOK_MAX_LENGTH`.
+	pub max_length: usize,
 
+	/// Seconds that a client must wait between signing the guestbook.
+	/// Overridden by `GUESTBOOK_COOLDOWN_SECS`.
+	pub cooldown_secs: u32,
+}
+
+impl Default for GuestbookConfig {
+	fn default() -> Self {
 		Self {
-			gemini_port,
-			gemini_hostname,
-			gemini_certs_dir,
-			http_port,
-			http_hostname: IpAddr::V4(Ipv4Addr::UNSPECIFIED), // 0.0.0.0
+			entries_path: PathBuf::from("guestbook/entries.tsv"),
+			hidden_path: PathBuf::from("guestbook/hidden.txt"),
+			max_length: 280,
+			cooldown_secs: 300,
 		}
 	}
 }
 
+/// The `[supervisor]` section of the config file.
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SupervisorConfig {
+	/// The number of consecutive times a failed service may be restarted before the
+	/// program exits. Overridden by `SUPERVISOR_MAX_RESTARTS`.
+	pub max_restarts: u32,
+
+	/// Milliseconds to wait before restarting a failed service, doubling with each
+	/// consecutive failure. Overridden by `SUPERVISOR_INITIAL_BACKOFF_MS`.
+	pub initial_backoff_ms: u64,
+
+	/// The most milliseconds to wait before restarting a failed service.
+	/// Overridden by `SUPERVISOR_MAX_BACKOFF_MS`.
+	pub max_backoff_ms: u64,
+}
+
+impl Default for SupervisorConfig {
+	fn default() -> Self {
+		Self {
+			max_restarts: 5,
+			initial_backoff_ms: 1_000,
+			max_backoff_ms: 60_000,
+		}
+	}
+}
+
+/// The `[shutdown]` section of the config file.
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShutdownConfig {
+	/// Seconds to let in-flight requests finish after SIGTERM or SIGINT before
+	/// closing their connections. Overridden by `SHUTDOWN_GRACE_SECS`.
+	pub grace_secs: u32,
+}
+
+impl Default for ShutdownConfig {
+	fn default() -> Self {
+		Self { grace_secs: 5 }
+	}
+}
+
 impl Config {
-	pub fn rocket_config(&self) -> rocket::Config {
+	/// Loads the configuration from the process's command-line arguments,
+	/// config file, and environment variables.
+	pub fn load() -> Result<Self, ConfigError> {
+		let args = Args::parse(env::args_os().skip(1))?;
+		Self::load_with(&args, |name| env::var_os(name))
+	}
+
+	/// Loads the configuration using the given arguments and environment lookup.
+	fn load_with<F>(args: &Args, lookup: F) -> Result<Self, ConfigError>
+	where
+		F: Fn(&str) -> Option<OsString>,
+	{
+		let env = Env(lookup);
+		let config_path = match &args.config_path {
+			Some(path) => Some(path.clone()),
+			None => env.parse::<PathBuf>("CONFIG_PATH")?,
+		};
+
+		let mut config = match config_path {
+			Some(path) => Self::from_file(&path)?,
+			None => Self::default(),
+		};
+		config.apply_env(&env)?;
+		if let Some(services) = &args.services {
+			config.services.clone_from(services);
+		}
+		if let Some(path) = &config.redirects_path {
+			config.redirects = RedirectTable::from_file(path)?;
+		}
+		config.validate()?;
+		Ok(config)
+	}
+
+	/// Reads the config file at the given path. Keys missing from the file take
+	/// their default values, and unknown keys are an error.
+	fn from_file(path: &Path) -> Result<Self, ConfigError> {
+		let text = fs::read_to_string(path).map_err(|source| ConfigError::ReadFile {
+			path: path.to_owned(),
+			source,
+		})?;
+		toml::from_str(&text).map_err(|source| ConfigError::ParseFile {
+			path: path.to_owned(),
+			source,
+		})
+	}
+
+	/// Overrides config values with those from environment variables.
+	fn apply_env<F>(&mut self, env: &Env<F>) -> Result<(), ConfigError>
+	where
+		F: Fn(&str) -> Option<OsString>,
+	{
+		env.parse_list_into("SERVICES", &mut self.services)?;
+		env.parse_into("PROFILE_NAME", &mut self.profile.name)?;
+		env.parse_into("PROFILE_ORIGIN", &mut self.profile.origin)?;
+		env.parse_list_into(
+			"PROFILE_WEBFINGER_DOMAINS",
+			&mut self.profile.webfinger_domains,
+		)?;
+		env.parse_list_into("PROFILE_ALIASES", &mut self.profile.aliases)?;
+		env.parse_into("PROFILE_PRONOUNS", &mut self.profile.pronouns)?;
+		env.parse_list_into("PROFILE_MEMORIALS", &mut self.profile.memorials)?;
+		env.parse_into(
+			"PROFILE_FEDIVERSE_ACCOUNT",
+			&mut self.profile.fediverse.account,
+		)?;
+		env.parse_into(
+			"PROFILE_FEDIVERSE_PROFILE_PAGE",
+			&mut self.profile.fediverse.profile_page,
+		)?;
+		env.parse_into("PROFILE_FEDIVERSE_ACTOR", &mut self.profile.fediverse.actor)?;
+		env.parse_into(
+			"PROFILE_FEDIVERSE_NODEINFO",
+			&mut self.profile.fediverse.nodeinfo,
+		)?;
+		if let Some(redirects_path) = env.parse("REDIRECTS_PATH")? {
+			self.redirects_path = Some(redirects_path);
+		}
+		env.parse_into("GEMINI_PORT", &mut self.gemini.port)?;
+		env.parse_list_into("GEMINI_ADDRESSES", &mut self.gemini.addresses)?;
+		if let Some(hostname) = env.parse("GEMINI_HOSTNAME")? {
+			self.gemini.hostname = Some(hostname);
+		}
+		if let Some(certs_dir) = env.parse("GEMINI_CERTS_DIR")? {
+			self.gemini.certs_dir = Some(certs_dir);
+		}
+		env.parse_into(
+			"GEMINI_GENERATE_CERTIFICATE",
+			&mut self.gemini.generate_certificate,
+		)?;
+		env.parse_into("GEMINI_SERVE_FROM_DISK", &mut self.gemini.serve_from_disk)?;
+		env.parse_into("HTTP_PORT", &mut self.http.port)?;
+		env.parse_list_into("HTTP_ADDRESSES", &mut self.http.addresses)?;
+		env.parse_into("HTTP_SERVE_FROM_DISK", &mut self.http.serve_from_disk)?;
+		env.parse_into("GUESTBOOK_ENTRIES_PATH", &mut self.guestbook.entries_path)?;
+		env.parse_into("GUESTBOOK_HIDDEN_PATH", &mut self.guestbook.hidden_path)?;
+		env.parse_into("GUESTBOOK_MAX_LENGTH", &mut self.guestbook.max_length)?;
+		env.parse_into("GUESTBOOK_COOLDOWN_SECS", &mut self.guestbook.cooldown_secs)?;
+		env.parse_into("SUPERVISOR_MAX_RESTARTS", &mut self.supervisor.max_restarts)?;
+		env.parse_into(
+			"SUPERVISOR_INITIAL_BACKOFF_MS",
+			&mut self.supervisor.initial_backoff_ms,
+		)?;
+		env.parse_into(
+			"SUPERVISOR_MAX_BACKOFF_MS",
+			&mut self.supervisor.max_backoff_ms,
+		)?;
+		env.parse_into("SHUTDOWN_GRACE_SECS", &mut self.shutdown.grace_secs)?;
+		Ok(())
+	}
+
+	/// Checks values that parsed correctly but still make no sense.
+	fn validate(&self) -> Result<(), ConfigError> {
+		self.profile.validate()?;
+		self.redirects.validate()?;
+
+		if let Some(hostname) = &self.gemini.hostname
+			&& !matches!(url::Host::parse(hostname), Ok(url::Host::Domain(_)))
+		{
+			return Err(ConfigError::InvalidValue {
+				key: "gemini.hostname".to_owned(),
+				value: hostname.clone(),
+				reason: "expected a domain name".to_owned(),
+			});
+		}
+
+		validate_list("services", &self.services)?;
+		validate_list("gemini.addresses", &self.gemini.addresses)?;
+		validate_list("http.addresses", &self.http.addresses)?;
+
+		if self.guestbook.max_length == 0 {
+			return Err(ConfigError::InvalidValue {
+				key: "guestbook.max_length".to_owned(),
+				value: self.guestbook.max_length.to_string(),
+				reason: "expected at least 1".to_owned(),
+			});
+		}
+
+		if self.supervisor.initial_backoff_ms > self.supervisor.max_backoff_ms {
+			return Err(ConfigError::InvalidValue {
+				key: "supervisor.initial_backoff_ms".to_owned(),
+				value: self.supervisor.initial_backoff_ms.to_string(),
+				reason: "must not exceed supervisor.max_backoff_ms".to_owned(),
+			});
+		}
+
+		Ok(())
+	}
+
+	/// The hostname at which the Gemini webserver should listen.
+	pub fn gemini_hostname(&self) -> String {
+		match &self.gemini.hostname {
+			Some(hostname) => hostname.clone(),
+			None => self.profile.domain(),
+		}
+	}
+
+	/// Returns the Rocket config for an HTTP webserver listening at the given address.
+	#[cfg(feature = "http")]
+	pub fn rocket_config(&self, address: IpAddr) -> rocket::Config {
 		#[cfg(debug_assertions)]
 		let defaults = rocket::Config::debug_default();
 		#[cfg(not(debug_assertions))]
 		let defaults = rocket::Config::release_default();
 
 		rocket::Config {
-			port: self.http_port,
-			address: self.http_hostname,
+			port: self.http.port,
+			address,
+			shutdown: rocket::config::Shutdown {
+				// We catch signals ourselves, so that every service stops together
+				ctrlc: false,
+				#[cfg(unix)]
+				signals: std::collections::HashSet::new(),
+				grace: self.shutdown.grace_secs,
+				..defaults.shutdown.clone()
+			},
 			..defaults
 		}
 	}
 }
+
+/// Ensures that the given list is nonempty and has no duplicates.
+fn validate_list<T>(key: &str, list: &[T]) -> Result<(), ConfigError>
+where
+	T: PartialEq + ToString,
+{
+	let invalid = |reason: &str| ConfigError::InvalidValue {
+		key: key.to_owned(),
+		value: list
+			.iter()
+			.map(ToString::to_string)
+			.collect::<Vec<_>>()
+			.join(","),
+		reason: reason.to_owned(),
+	};
+
+	if list.is_empty() {
+		return Err(invalid("expected at least one item"));
+	}
+	for (index, item) in list.iter().enumerate() {
+		if list.iter().skip(index.saturating_add(1)).any(|i| i == item) {
+			return Err(invalid("items must not repeat"));
+		}
+	}
+
+	Ok(())
+}
+
+/// Reads environment variables through a lookup function, so that tests may supply their own.
+struct Env<F>(F);
+
+impl<F> Env<F>
+where
+	F: Fn(&str) -> Option<OsString>,
+{
+	/// Returns the value of the variable `name`, or the contents of the file
+	/// named by `{name}_FILE` without trailing whitespace.
+	fn get(&self, name: &str) -> Result<Option<String>, ConfigError> {
+		let file_var = format!("{name}_FILE");
+		match ((self.0)(name), (self.0)(&file_var)) {
+			(Some(_), Some(_)) => Err(ConfigError::ConflictingVars {
+				name: name.to_owned(),
+			}),
+			(Some(value), None) => match value.into_string() {
+				Ok(value) => Ok(Some(value)),
+				Err(_) => Err(ConfigError::NonUnicodeVar {
+					name: name.to_owned(),
+				}),
+			},
+			(None, Some(path)) => {
+				let path = PathBuf::from(path);
+				match fs::read_to_string(&path) {
+					Ok(value) => Ok(Some(value.trim_end().to_owned())),
+					Err(source) => Err(ConfigError::ReadVarFile {
+						name: name.to_owned(),
+						path,
+						source,
+					}),
+				}
+			}
+			(None, None) => Ok(None),
+		}
+	}
+
+	/// Parses the value of the variable `name`, if it is set.
+	fn parse<T>(&self, name: &str) -> Result<Option<T>, ConfigError>
+	where
+		T: FromStr<Err: core::fmt::Display>,
+	{
+		let Some(value) = self.get(name)? else {
+			return Ok(None);
+		};
+		match value.parse() {
+			Ok(parsed) => Ok(Some(parsed)),
+			Err(err) => Err(ConfigError::InvalidValue {
+				key: name.to_owned(),
+				value,
+				reason: err.to_string(),
+			}),
+		}
+	}
+
+	/// Parses the value of the variable `name` as a comma-separated list, if it is set.
+	fn parse_list<T>(&self, name: &str) -> Result<Option<Vec<T>>, ConfigError>
+	where
+		T: FromStr<Err: core::fmt::Display>,
+	{
+		let Some(value) = self.get(name)? else {
+			return Ok(None);
+		};
+		let mut list = Vec::new();
+		for item in value
+			.split(',')
+			.map(str::trim)
+			.filter(|item| !item.is_empty())
+		{
+			match item.parse::<T>() {
+				Ok(parsed) => list.push(parsed),
+				Err(err) => {
+					return Err(ConfigError::InvalidValue {
+						key: name.to_owned(),
+						value: value.clone(),
+						reason: err.to_string(),
+					});
+				}
+			}
+		}
+		Ok(Some(list))
+	}
+
+	/// Replaces `target` with the parsed list in the variable `name`, if it is set.
+	fn parse_list_into<T>(&self, name: &str, target: &mut Vec<T>) -> Result<(), ConfigError>
+	where
+		T: FromStr<Err: core::fmt::Display>,
+	{
+		if let Some(list) = self.parse_list(name)? {
+			*target = list;
+		}
+		Ok(())
+	}
+
+	/// Replaces `target` with the parsed value of the variable `name`, if it is set.
+	fn parse_into<T>(&self, name: &str, target: &mut T) -> Result<(), ConfigError>
+	where
+		T: FromStr<Err: core::fmt::Display>,
+	{
+		if let Some(value) = self.parse(name)? {
+			*target = value;
+		}
+		Ok(())
+	}
+}
+
+// MARK: - Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use core::net::Ipv6Addr;
+	use std::collections::HashMap;
+
+	fn load(args: &Args, vars: &[(&str, &str)]) -> Result<Config, ConfigError> {
+		let vars: HashMap<String, OsString> = vars
+			.iter()
+			.map(|(k, v)| ((*k).to_owned(), OsString::from(v)))
+			.collect();
+		Config::load_with(args, |name| vars.get(name).cloned())
+	}
+
+	/// A temporary file, which is removed when it's dropped.
+	struct TempFile(PathBuf);
+
+	impl Drop for TempFile {
+		fn drop(&mut self) {
+			_ = fs::remove_file(&self.0);
+		}
+	}
+
+	impl core::ops::Deref for TempFile {
+		type Target = Path;
+
+		fn deref(&self) -> &Path {
+			&self.0
+		}
+	}
+
+	/// Writes the given contents to a uniquely-named temporary file.
+	fn temp_file(name: &str, contents: &str) -> TempFile {
+		let path = env::temp_dir().join(format!("portfolio-config-{}-{name}", std::process::id()));
+		fs::write(&path, contents).expect("Temp file should be writable");
+		TempFile(path)
+	}
+
+	#[test]
+	fn uses_defaults_without_file_or_env() {
+		let config = load(&Args::default(), &[]).expect("Defaults should load");
+		assert_eq!(config.services, Service::all());
+		assert_eq!(config.gemini.port, 1965);
+		assert_eq!(
+			config.gemini.addresses,
+			vec![IpAddr::V6(Ipv6Addr::UNSPECIFIED)]
+		);
+		assert_eq!(config.gemini_hostname(), "average.name");
+		assert_eq!(config.gemini.certs_dir, Some(PathBuf::from(".certs")));
+		assert_eq!(config.http.port, 8787);
+		assert_eq!(
+			config.http.addresses,
+			vec![IpAddr::V4(Ipv4Addr::UNSPECIFIED)]
+		);
+	}
+
+	#[test]
+	fn reads_config_file() {
+		let path = temp_file(
+			"reads.toml",
+			"[gemini]\nport = 1966\ncerts_dir = \"/run/certs\"\n\n[http]\naddresses = [\"::1\", \"127.0.0.1\"]\n",
+		);
+		let args = Args {
+			config_path: Some(path.to_path_buf()),
+			..Args::default()
+		};
+		let config = load(&args, &[]).expect("Config file should load");
+		assert_eq!(config.gemini.port, 1966);
+		assert_eq!(config.gemini_hostname(), "average.name");
+		assert_eq!(config.gemini.certs_dir, Some(PathBuf::from("/run/certs")));
+		assert_eq!(config.http.port, 8787);
+		assert_eq!(
+			config.http.addresses,
+			vec![
+				IpAddr::V6(Ipv6Addr::LOCALHOST),
+				IpAddr::V4(Ipv4Addr::LOCALHOST)
+			]
+		);
+	}
+
+	#[test]
+	fn reads_config_path_from_env() {
+		let path = temp_file("env-path.toml", "[http]\nport = 8080\n");
+		let path = path.to_str().expect("Temp path should be Unicode");
+		let config = load(&Args::default(), &[("CONFIG_PATH", path)]).expect("Config should load");
+		assert_eq!(config.http.port, 8080);
+	}
+
+	#[test]
+	fn env_overrides_config_file() {
+		let path = temp_file("layered.toml", "[gemini]\nport = 1966\n");
+		let args = Args {
+			config_path: Some(path.to_path_buf()),
+			..Args::default()
+		};
+		let config = load(&args, &[("GEMINI_PORT", "1967"), ("HTTP_PORT", "80")])
+			.expect("Config should load");
+		assert_eq!(config.gemini.port, 1967);
+		assert_eq!(config.http.port, 80);
+	}
+
+	#[test]
+	fn reads_file_variants_of_env_vars() {
+		let path = temp_file("hostname-secret", "example.com\n");
+		let path = path.to_str().expect("Temp path should be Unicode");
+		let config =
+			load(&Args::default(), &[("GEMINI_HOSTNAME_FILE", path)]).expect("Config should load");
+		assert_eq!(config.gemini_hostname(), "example.com");
+	}
+
+	#[test]
+	fn rejects_var_and_file_variant_together() {
+		let result = load(
+			&Args::default(),
+			&[("HTTP_PORT", "80"), ("HTTP_PORT_FILE", "/dev/null")],
+		);
+		let Err(ConfigError::ConflictingVars { name }) = result else {
+			panic!("Expected a conflict error");
+		};
+		assert_eq!(name, "HTTP_PORT");
+	}
+
+	#[test]
+	fn rejects_missing_file_variant() {
+		let result = load(
+			&Args::default(),
+			&[("HTTP_PORT_FILE", "/nonexistent/portfolio/port")],
+		);
+		assert!(
+			matches!(result, Err(ConfigError::ReadVarFile { .. })),
+			"Expected a read error"
+		);
+	}
+
+	#[test]
+	fn rejects_malformed_port() {
+		let result = load(&Args::default(), &[("GEMINI_PORT", "19650")]);
+		assert!(
+			matches!(result, Ok(ref config) if config.gemini.port == 19650),
+			"In-range port should parse"
+		);
+
+		let result = load(&Args::default(), &[("GEMINI_PORT", "1965O")]);
+		let Err(ConfigError::InvalidValue { key, value, .. }) = result else {
+			panic!("Expected an invalid value error");
+		};
+		assert_eq!(key, "GEMINI_PORT");
+		assert_eq!(value, "1965O");
+
+		let result = load(&Args::default(), &[("HTTP_PORT", "65536")]);
+		assert!(
+			matches!(result, Err(ConfigError::InvalidValue { .. })),
+			"Out-of-range port should fail"
+		);
+	}
+
+	#[test]
+	fn reads_certificate_generation_from_env() {
+		for (value, expected) in [("true", true), ("false", false)] {
+			let config = load(&Args::default(), &[("GEMINI_GENERATE_CERTIFICATE", value)])
+				.expect("Config should load");
+			assert_eq!(config.gemini.generate_certificate, expected);
+		}
+		assert!(
+			load(&Args::default(), &[("GEMINI_GENERATE_CERTIFICATE", "yes")]).is_err(),
+			"Only true and false should parse"
+		);
+	}
+
+	#[test]
+	fn reads_serve_from_disk_from_env() {
+		let config = load(
+			&Args::default(),
+			&[
+				("GEMINI_SERVE_FROM_DISK", "true"),
+				("HTTP_SERVE_FROM_DISK", "true"),
+			],
+		)
+		.expect("Config should load");
+		assert!(config.gemini.serve_from_disk);
+		assert!(config.http.serve_from_disk);
+
+		let config = load(&Args::default(), &[]).expect("Config should load");
+		assert!(
+			!config.gemini.serve_from_disk,
+			"Tests should use embedded files"
+		);
+		assert!(
+			!config.http.serve_from_disk,
+			"Tests should use embedded files"
+		);
+	}
+
+	#[test]
+	fn rejects_malformed_hostname() {
+		let result = load(&Args::default(), &[("GEMINI_HOSTNAME", "")]);
+		assert!(
+			matches!(result, Err(ConfigError::InvalidValue { .. })),
+			"Empty hostname should fail"
+		);
+
+		let result = load(&Args::default(), &[("GEMINI_HOSTNAME", "127.0.0.1")]);
+		assert!(
+			matches!(result, Err(ConfigError::InvalidValue { .. })),
+			"IP address hostname should fail"
+		);
+	}
+
+	#[test]
+	fn reads_profile_section() {
+		let path = temp_file(
+			"profile.toml",
+			"[profile]\norigin = \"https://example.com\"\naliases = [\"me\"]\n\n[profile.fediverse]\naccount = \"me@social.example.com\"\n",
+		);
+		let args = Args {
+			config_path: Some(path.to_path_buf()),
+			..Args::default()
+		};
+		let config = load(
+			&args,
+			&[
+				("PROFILE_NAME", "Ada"),
+				("PROFILE_PRONOUNS", "they/them"),
+				("PROFILE_MEMORIALS", "Ada Lovelace, Alan Turing"),
+			],
+		)
+		.expect("Config should load");
+		assert_eq!(config.profile.name, "Ada");
+		assert_eq!(config.profile.origin, "https://example.com");
+		assert_eq!(config.profile.aliases, vec!["me"]);
+		assert_eq!(config.profile.pronouns, "they/them");
+		assert_eq!(
+			config.profile.memorials,
+			vec!["Ada Lovelace", "Alan Turing"]
+		);
+		assert_eq!(config.profile.fediverse.account, "me@social.example.com");
+		assert_eq!(
+			config.profile.fediverse.actor, "https://gts.average.name/users/avghelper",
+			"Unset keys should keep their defaults"
+		);
+		assert_eq!(
+			config.gemini_hostname(),
+			"example.com",
+			"Gemini hostname should follow the profile's origin"
+		);
+	}
+
+	#[test]
+	fn rejects_malformed_profile() {
+		for (name, value) in [
+			("PROFILE_ORIGIN", "example.com"),
+			("PROFILE_ORIGIN", "https://example.com/"),
+			("PROFILE_ORIGIN", "https://127.0.0.1"),
+			("PROFILE_FEDIVERSE_ACCOUNT", "@example.com"),
+			("PROFILE_FEDIVERSE_ACCOUNT", "me"),
+			("PROFILE_FEDIVERSE_ACTOR", "/users/me"),
+			("PROFILE_FEDIVERSE_NODEINFO", "ftp://example.com/nodeinfo"),
+		] {
+			let result = load(&Args::default(), &[(name, value)]);
+			assert!(
+				matches!(result, Err(ConfigError::InvalidValue { .. })),
+				"{name}='{value}' should fail"
+			);
+		}
+	}
+
+	#[test]
+	fn reads_address_lists_from_env() {
+		let config = load(
+			&Args::default(),
+			&[
+				("GEMINI_ADDRESSES", "::1"),
+				("HTTP_ADDRESSES", "192.0.2.1, 2001:db8::1"),
+			],
+		)
+		.expect("Config should load");
+		assert_eq!(
+			config.gemini.addresses,
+			vec![IpAddr::V6(Ipv6Addr::LOCALHOST)]
+		);
+		assert_eq!(
+			config.http.addresses,
+			vec![
+				IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)),
+				IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)),
+			]
+		);
+	}
+
+	#[test]
+	fn rejects_malformed_address_lists() {
+		for addresses in ["", "::1,::1", "localhost", "0.0.0.0:8787"] {
+			let result = load(&Args::default(), &[("HTTP_ADDRESSES", addresses)]);
+			assert!(
+				matches!(result, Err(ConfigError::InvalidValue { .. })),
+				"'{addresses}' should fail"
+			);
+		}
+	}
+
+	#[test]
+	fn selects_services_from_env_and_args() {
+		for service in Service::all() {
+			let config = load(&Args::default(), &[("SERVICES", &service.to_string())])
+				.expect("Config should load");
+			assert_eq!(config.services, vec![service]);
+
+			let args = Args {
+				services: Some(vec![service]),
+				..Args::default()
+			};
+			let config = load(&args, &[("SERVICES", "")]).expect("--services should override");
+			assert_eq!(config.services, vec![service]);
+		}
+	}
+
+	#[test]
+	fn rejects_unknown_or_empty_services() {
+		for services in ["", "gopher", "gemini,http,gemini"] {
+			let result = load(&Args::default(), &[("SERVICES", services)]);
+			assert!(
+				matches!(result, Err(ConfigError::InvalidValue { .. })),
+				"'{services}' should fail"
+			);
+		}
+	}
+
+	#[test]
+	#[cfg(feature = "http")]
+	fn rocket_config_uses_given_address() {
+		let config = Config::default();
+		let rocket_config = config.rocket_config(IpAddr::V6(Ipv6Addr::LOCALHOST));
+		assert_eq!(rocket_config.address, IpAddr::V6(Ipv6Addr::LOCALHOST));
+		assert_eq!(rocket_config.port, 8787);
+		assert!(
+			!rocket_config.shutdown.ctrlc,
+			"Rocket should leave Ctrl-C to us"
+		);
+		assert_eq!(rocket_config.shutdown.grace, 5);
+	}
+
+	#[test]
+	fn reads_shutdown_grace_from_env() {
+		let config =
+			load(&Args::default(), &[("SHUTDOWN_GRACE_SECS", "30")]).expect("Config should load");
+		assert_eq!(config.shutdown.grace_secs, 30);
+	}
+
+	#[test]
+	fn reads_guestbook_section() {
+		let path = temp_file(
+			"guestbook.toml",
+			"[guestbook]\nentries_path = \"book.tsv\"\nmax_length = 100\n",
+		);
+		let args = Args {
+			config_path: Some(path.to_path_buf()),
+			..Args::default()
+		};
+		let config = load(&args, &[("GUESTBOOK_COOLDOWN_SECS", "60")]).expect("Config should load");
+		assert_eq!(config.guestbook.entries_path, PathBuf::from("book.tsv"));
+		assert_eq!(
+			config.guestbook.hidden_path,
+			PathBuf::from("guestbook/hidden.txt")
+		);
+		assert_eq!(config.guestbook.max_length, 100);
+		assert_eq!(config.guestbook.cooldown_secs, 60);
+
+		let result = load(&args, &[("GUESTBOOK_MAX_LENGTH", "0")]);
+		assert!(
+			matches!(result, Err(ConfigError::InvalidValue { .. })),
+			"Empty entries should not be allowed"
+		);
+	}
+
+	#[test]
+	fn reads_supervisor_section() {
+		let path = temp_file(
+			"supervisor.toml",
+			"[supervisor]\nmax_restarts = 2\ninitial_backoff_ms = 10\n",
+		);
+		let args = Args {
+			config_path: Some(path.to_path_buf()),
+			..Args::default()
+		};
+		let config =
+			load(&args, &[("SUPERVISOR_MAX_BACKOFF_MS", "20")]).expect("Config should load");
+		assert_eq!(config.supervisor.max_restarts, 2);
+		assert_eq!(config.supervisor.initial_backoff_ms, 10);
+		assert_eq!(config.supervisor.max_backoff_ms, 20);
+
+		let result = load(&args, &[("SUPERVISOR_MAX_BACKOFF_MS", "5")]);
+		assert!(
+			matches!(result, Err(ConfigError::InvalidValue { .. })),
+			"Initial backoff above max should fail"
+		);
+	}
+
+	#[test]
+	fn reads_redirects_file() {
+		let redirects = temp_file(
+			"redirects.toml",
+			"[[redirect]]\nfrom = \"/a\"\nto = \"/b\"\nstatus = 308\n",
+		);
+		let redirects = redirects.to_str().expect("Temp path should be Unicode");
+		let config =
+			load(&Args::default(), &[("REDIRECTS_PATH", redirects)]).expect("Config should load");
+		let [ref rule] = config.redirects.rules[..] else {
+			panic!("Expected exactly 1 rule");
+		};
+		assert_eq!(rule.from, "/a");
+		assert_eq!(rule.to, "/b");
+		assert_eq!(rule.status, RedirectStatus::PermanentRedirect);
+
+		let looping = temp_file(
+			"looping-redirects.toml",
+			"[[redirect]]\nfrom = \"/a\"\nto = \"/a\"\n",
+		);
+		let looping = looping.to_str().expect("Temp path should be Unicode");
+		assert!(
+			matches!(
+				load(&Args::default(), &[("REDIRECTS_PATH", looping)]),
+				Err(ConfigError::InvalidRedirect { .. })
+			),
+			"Redirect loop should fail"
+		);
+	}
+
+	#[test]
+	fn rejects_unknown_config_keys() {
+		let path = temp_file("unknown.toml", "[gemini]\nprot = 1966\n");
+		let args = Args {
+			config_path: Some(path.to_path_buf()),
+			..Args::default()
+		};
+		assert!(
+			matches!(load(&args, &[]), Err(ConfigError::ParseFile { .. })),
+			"Misspelled key should fail"
+		);
+	}
+
+	#[test]
+	fn rejects_missing_config_file() {
+		let args = Args {
+			config_path: Some(PathBuf::from("/nonexistent/portfolio.toml")),
+			..Args::default()
+		};
+		assert!(
+			matches!(load(&args, &[]), Err(ConfigError::ReadFile { .. })),
+			"Missing config file should fail"
+		);
+	}
+}
@@ -0,0 +1,153 @@
+use super::{ConfigError, Service};
+use std::{ffi::OsString, path::PathBuf};
+
+/// Options given on the command line.
+#[derive(Default)]
+pub struct Args {
+	/// The path to a TOML config file, from `--config <path>`.
+	pub config_path: Option<PathBuf>,
+
+	/// The webservers to run, from `--services <list>`.
+	pub services: Option<Vec<Service>>,
+}
+
+impl Args {
+	/// Parses the given command-line arguments, excluding the program name.
+	pub fn parse<I: IntoIterator<Item = OsString>>(args: I) -> Result<Self, ConfigError> {
+		let mut result = Self::default();
+		let mut args = args.into_iter();
+
+		while let Some(arg) = args.next() {
+			let arg = unicode(arg)?;
+			match arg.split_once('=') {
+				Some(("--config", path)) => result.config_path = Some(PathBuf::from(path)),
+				None if arg == "--config" || arg == "-c" => {
+					let path = args
+						.next()
+						.ok_or(ConfigError::MissingArgumentValue("--config"))?;
+					result.config_path = Some(PathBuf::from(path));
+				}
+				Some(("--services", list)) => result.services = Some(parse_services(list)?),
+				None if arg == "--services" => {
+					let list = args
+						.next()
+						.ok_or(ConfigError::MissingArgumentValue("--services"))?;
+					result.services = Some(parse_services(&unicode(list)?)?);
+				}
+				Some(_) | None => return Err(ConfigError::UnknownArgument(arg)),
+			}
+		}
+
+		Ok(result)
+	}
+}
+
+/// The given argument as a string, or an error if it isn't valid Unicode, rather
+/// than quietly changing it into another argument.
+fn unicode(arg: OsString) -> Result<String, ConfigError> {
+	arg.into_string()
+		.map_err(|arg| ConfigError::NonUnicodeArgument(arg.to_string_lossy().into_owned()))
+}
+
+/// Parses a comma-separated list of service names.
+fn parse_services(list: &str) -> Result<Vec<Service>, ConfigError> {
+	list.split(',')
+		.map(str::trim)
+		.filter(|name| !name.is_empty())
+		.map(|name| {
+			name.parse().map_err(|reason| ConfigError::InvalidValue {
+				key: "--services".to_owned(),
+				value: list.to_owned(),
+				reason,
+			})
+		})
+		.collect()
+}
+
+// MARK: - Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn parse(args: &[&str]) -> Result<Args, ConfigError> {
+		Args::parse(args.iter().map(OsString::from))
+	}
+
+	#[test]
+	fn parses_nothing() {
+		let args = parse(&[]).expect("Empty args should parse");
+		assert_eq!(args.config_path, None);
+		assert_eq!(args.services, None);
+	}
+
+	#[test]
+	fn parses_config_path() {
+		for args in [
+			&["--config", "site.toml"][..],
+			&["-c", "site.toml"],
+			&["--config=site.toml"],
+		] {
+			let args = parse(args).expect("Args should parse");
+			assert_eq!(args.config_path, Some(PathBuf::from("site.toml")));
+		}
+	}
+
+	#[test]
+	fn parses_services() {
+		let all = Service::all();
+		let names = all
+			.iter()
+			.map(ToString::to_string)
+			.collect::<Vec<_>>()
+			.join(",");
+		for args in [
+			&["--services", &names][..],
+			&[&format!("--services={names}")],
+		] {
+			let args = parse(args).expect("Args should parse");
+			assert_eq!(args.services.as_ref(), Some(&all));
+		}
+	}
+
+	#[test]
+	fn rejects_unknown_services() {
+		let Err(ConfigError::InvalidValue { key, .. }) = parse(&["--services", "gopher"]) else {
+			panic!("Expected an invalid value error");
+		};
+		assert_eq!(key, "--services");
+	}
+
+	#[test]
+	fn rejects_config_flag_without_value() {
+		let Err(ConfigError::MissingArgumentValue(flag)) = parse(&["--config"]) else {
+			panic!("Expected a missing value error");
+		};
+		assert_eq!(flag, "--config");
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn keeps_non_unicode_config_paths() {
+		use std::os::unix::ffi::OsStringExt;
+
+		let path = || OsString::from_vec(b"site\xff.toml".to_vec());
+		let args = Args::parse([OsString::from("--config"), path()]).expect("Args should parse");
+		assert_eq!(args.config_path, Some(PathBuf::from(path())));
+
+		let mut config_flag = OsString::from("--config=");
+		config_flag.push(path());
+		let Err(ConfigError::NonUnicodeArgument(arg)) = Args::parse([config_flag]) else {
+			panic!("Expected a non-Unicode argument error");
+		};
+		assert_eq!(arg, "--config=site\u{fffd}.toml");
+	}
+
+	#[test]
+	fn rejects_unknown_arguments() {
+		let Err(ConfigError::UnknownArgument(arg)) = parse(&["--verbose"]) else {
+			panic!("Expected an unknown argument error");
+		};
+		assert_eq!(arg, "--verbose");
+	}
+}
@@ -0,0 +1,92 @@
+use std::{io, path::PathBuf};
+
+/// A problem with the server's configuration, found at startup.
+#[derive(Debug)]
+pub enum ConfigError {
+	/// A command-line argument was not recognized.
+	UnknownArgument(String),
+
+	/// A command-line flag was given without its value.
+	MissingArgumentValue(&'static str),
+
+	/// A command-line argument was not valid Unicode.
+	NonUnicodeArgument(String),
+
+	/// The config file could not be read.
+	ReadFile { path: PathBuf, source: io::Error },
+
+	/// The config file is not valid TOML, or contains unknown or mistyped keys.
+	ParseFile {
+		path: PathBuf,
+		source: toml::de::Error,
+	},
+
+	/// Both an environment variable and its `_FILE` variant were set.
+	ConflictingVars { name: String },
+
+	/// An environment variable was not valid Unicode.
+	NonUnicodeVar { name: String },
+
+	/// The file named by a `_FILE` environment variable could not be read.
+	ReadVarFile {
+		name: String,
+		path: PathBuf,
+		source: io::Error,
+	},
+
+	/// A config value could not be parsed or is out of range.
+	InvalidValue {
+		/// The config key or environment variable that held the value.
+		key: String,
+		value: String,
+		reason: String,
+	},
+
+	/// A redirect rule is malformed, repeated, or part of a loop.
+	InvalidRedirect { from: String, reason: String },
+}
+
+impl core::fmt::Display for ConfigError {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		match self {
+			Self::UnknownArgument(arg) => write!(f, "Unknown argument '{arg}'"),
+			Self::MissingArgumentValue(flag) => write!(f, "Missing value for {flag}"),
+			Self::NonUnicodeArgument(arg) => write!(f, "Argument '{arg}' is not valid Unicode"),
+			Self::ReadFile { path, source } => {
+				write!(f, "Failed to read config file {}: {source}", path.display())
+			}
+			Self::ParseFile { path, source } => {
+				write!(f, "Invalid config file {}: {source}", path.display())
+			}
+			Self::ConflictingVars { name } => {
+				write!(f, "Only one of {name} and {name}_FILE may be set")
+			}
+			Self::NonUnicodeVar { name } => write!(f, "{name} is not valid Unicode"),
+			Self::ReadVarFile { name, path, source } => {
+				write!(f, "Failed to read {name}_FILE {}: {source}", path.display())
+			}
+			Self::InvalidValue { key, value, reason } => {
+				write!(f, "Invalid value '{value}' for {key}: {reason}")
+			}
+			Self::InvalidRedirect { from, reason } => {
+				write!(f, "Invalid redirect from '{from}': {reason}")
+			}
+		}
+	}
+}
+
+impl core::error::Error for ConfigError {
+	fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+		match self {
+			Self::ReadFile { source, .. } | Self::ReadVarFile { source, .. } => Some(source),
+			Self::ParseFile { source, .. } => Some(source),
+			Self::UnknownArgument(_)
+			| Self::MissingArgumentValue(_)
+			| Self::NonUnicodeArgument(_)
+			| Self::ConflictingVars { .. }
+			| Self::NonUnicodeVar { .. }
+			| Self::InvalidValue { .. }
+			| Self::InvalidRedirect { .. } => None,
+		}
+	}
+}
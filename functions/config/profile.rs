@@ -0,0 +1,163 @@
+use super::ConfigError;
+use serde::Deserialize;
+use url::Url;
+
+/// The `[profile]` section of the config file: whose site this is.
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+	/// The person's name, which the Ways feed credits as its author.
+	/// Overridden by `PROFILE_NAME`.
+	pub name: String,
+
+	/// The site's canonical origin, like `https://average.name`. Only this origin gets
+	/// CORS access to our pages, and Webfinger answers for its domain and subdomains.
+	/// Overridden by `PROFILE_ORIGIN`.
+	pub origin: String,
+
+	/// Other domains whose `acct:` resources Webfinger answers for, like a former
+	/// instance's. Overridden by `PROFILE_WEBFINGER_DOMAINS`, a comma-separated list.
+	pub webfinger_domains: Vec<String>,
+
+	/// Handles that redirect from `/@{handle}` on this site to the fediverse profile,
+	/// and that Webfinger lists as aliases.
+	/// Overridden by `PROFILE_ALIASES`, a comma-separated list.
+	pub aliases: Vec<String>,
+
+	/// The pronouns served at `/.well-known/pronouns` and in `X-Pronouns-Acceptable`.
+	/// Overridden by `PROFILE_PRONOUNS`.
+	pub pronouns: String,
+
+	/// Names of people to memorialize in `X-Clacks-Overhead`.
+	/// Overridden by `PROFILE_MEMORIALS`, a comma-separated list.
+	pub memorials: Vec<String>,
+
+	/// The fediverse account that Webfinger points to.
+	pub fediverse: FediverseAccount,
+}
+
+impl Default for Profile {
+	fn default() -> Self {
+		Self {
+			name: "Average Helper".to_owned(),
+			origin: "https://average.name".to_owned(),
+			webfinger_domains: vec!["fosstodon.org".to_owned()],
+			aliases: vec![
+				"average".to_owned(),
+				"avg".to_owned(),
+				"avghelper".to_owned(),
+			],
+			pronouns: "she/her".to_owned(),
+			memorials: vec![
+				"Terry Pratchett".to_owned(), // 28 April 1948 - 12 March 2015
+				"Nex Benedict".to_owned(),    // 11 January 2008 - February 8, 2024
+			],
+			fediverse: FediverseAccount::default(),
+		}
+	}
+}
+
+/// The `[profile.fediverse]` section of the config file.
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FediverseAccount {
+	/// The account's address, like `avghelper@gts.average.name`.
+	/// Overridden by `PROFILE_FEDIVERSE_ACCOUNT`.
+	pub account: String,
+
+	/// The URL of the account's profile page.
+	/// Overridden by `PROFILE_FEDIVERSE_PROFILE_PAGE`.
+	pub profile_page: String,
+
+	/// The URL of the account's actor, which Webfinger links as `self`.
+	/// Overridden by `PROFILE_FEDIVERSE_ACTOR`.
+	pub actor: String,
+
+	/// The URL of the instance's nodeinfo document, which we point GitHub to.
+	/// Overridden by `PROFILE_FEDIVERSE_NODEINFO`.
+	pub nodeinfo: String,
+}
+
+impl Default for FediverseAccount {
+	fn default() -> Self {
+		Self {
+			account: "avghelper@gts.average.name".to_owned(),
+			profile_page: "https://gts.average.name/@avghelper".to_owned(),
+			actor: "https://gts.average.name/users/avghelper".to_owned(),
+			nodeinfo: "https://gts.average.name/.well-known/nodeinfo".to_owned(),
+		}
+	}
+}
+
+impl Profile {
+	/// The domain name of the site's canonical origin.
+	pub fn domain(&self) -> String {
+		Url::parse(&self.origin)
+			.ok()
+			.and_then(|url| url.domain().map(ToOwned::to_owned))
+			.unwrap_or_default()
+	}
+
+	/// Whether Webfinger should answer for `acct:` resources at the given host.
+	#[cfg(feature = "http")]
+	pub fn knows_host(&self, host: &str) -> bool {
+		let domain = self.domain();
+		let is_subdomain = host
+			.strip_suffix(domain.as_str())
+			.is_some_and(|prefix| prefix.is_empty() || prefix.ends_with('.'));
+		is_subdomain || self.webfinger_domains.iter().any(|known| known == host)
+	}
+
+	/// The URLs on this site that redirect to the fediverse profile.
+	#[cfg(feature = "http")]
+	pub fn alias_urls(&self) -> impl Iterator<Item = String> {
+		self.aliases
+			.iter()
+			.map(|handle| format!("{}/@{handle}", self.origin))
+	}
+
+	/// Checks that the origin and fediverse URLs are usable.
+	pub(super) fn validate(&self) -> Result<(), ConfigError> {
+		let invalid = |key: &str, value: &str, reason: &str| ConfigError::InvalidValue {
+			key: key.to_owned(),
+			value: value.to_owned(),
+			reason: reason.to_owned(),
+		};
+
+		let is_origin = Url::parse(&self.origin).is_ok_and(|url| {
+			url.domain().is_some() && url.origin().ascii_serialization() == self.origin
+		});
+		if !is_origin {
+			return Err(invalid(
+				"profile.origin",
+				&self.origin,
+				"expected an origin like https://example.com, without a path",
+			));
+		}
+
+		let fediverse = &self.fediverse;
+		if fediverse
+			.account
+			.split_once('@')
+			.is_none_or(|(user, host)| {
+				user.is_empty() || !matches!(url::Host::parse(host), Ok(url::Host::Domain(_)))
+			}) {
+			return Err(invalid(
+				"profile.fediverse.account",
+				&fediverse.account,
+				"expected an address like user@example.com",
+			));
+		}
+		for (key, value) in [
+			("profile.fediverse.profile_page", &fediverse.profile_page),
+			("profile.fediverse.actor", &fediverse.actor),
+			("profile.fediverse.nodeinfo", &fediverse.nodeinfo),
+		] {
+			if !Url::parse(value).is_ok_and(|url| matches!(url.scheme(), "http" | "https")) {
+				return Err(invalid(key, value, "expected an http or https URL"));
+			}
+		}
+
+		Ok(())
+	}
+}
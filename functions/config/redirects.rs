@@ -0,0 +1,450 @@
+mod pattern;
+
+use super::ConfigError;
+use serde::Deserialize;
+use std::{collections::HashSet, fs, path::Path};
+use url::Url;
+
+/// The redirect table that ships with the server.
+static BUILTIN: &str = include_str!("../redirects.toml");
+
+/// Paths that send visitors somewhere else, as read from a TOML file of
+/// `[[redirect]]` entries.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RedirectTable {
+	#[serde(default, rename = "redirect")]
+	pub rules: Vec<RedirectRule>,
+}
+
+impl Default for RedirectTable {
+	fn default() -> Self {
+		toml::from_str(BUILTIN).expect("Built-in redirect table should parse")
+	}
+}
+
+/// One `[[redirect]]` entry.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RedirectRule {
+	/// The path on this site to redirect from, like `/how`.
+	///
+	/// A `<name>` placeholder matches the part of one path segment between its
+	/// literal prefix and suffix, like `/@<name>`. A `<name..>` placeholder must be
+	/// the last segment, and matches the rest of the path, like `/blog/<rest..>`.
+	pub from: String,
+
+	/// The path or absolute URL to redirect to, where each `<name>` is replaced
+	/// with what that placeholder matched in `from`.
+	pub to: String,
+
+	/// The status to redirect with. Defaults to 302 (Found).
+	#[serde(default)]
+	pub status: RedirectStatus,
+
+	/// Whether a request for `{from}.html` redirects to `{to}.html`. Defaults to
+	/// `true`. Turn it off for targets that aren't pages, like `/.well-known/` files.
+	#[serde(default = "html_by_default")]
+	pub html: bool,
+}
+
+const fn html_by_default() -> bool {
+	true
+}
+
+impl RedirectRule {
+	/// A path that this rule matches, with `x` for each placeholder.
+	#[cfg(all(test, feature = "http"))]
+	pub fn example(&self) -> String {
+		pattern::example(&self.from)
+	}
+}
+
+/// An HTTP status that a redirect may use.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "u16")]
+pub enum RedirectStatus {
+	/// 301 Moved Permanently
+	MovedPermanently,
+
+	/// 302 Found
+	#[default]
+	Found,
+
+	/// 303 See Other
+	SeeOther,
+
+	/// 307 Temporary Redirect
+	TemporaryRedirect,
+
+	/// 308 Permanent Redirect
+	PermanentRedirect,
+}
+
+impl RedirectStatus {
+	/// The status's numeric code.
+	#[cfg(any(feature = "http", test))]
+	pub const fn code(self) -> u16 {
+		match self {
+			Self::MovedPermanently => 301,
+			Self::Found => 302,
+			Self::SeeOther => 303,
+			Self::TemporaryRedirect => 307,
+			Self::PermanentRedirect => 308,
+		}
+	}
+
+	/// Whether clients should remember the new location.
+	#[cfg(feature = "gemini")]
+	pub const fn is_permanent(self) -> bool {
+		matches!(self, Self::MovedPermanently | Self::PermanentRedirect)
+	}
+}
+
+impl TryFrom<u16> for RedirectStatus {
+	type Error = String;
+
+	fn try_from(value: u16) -> Result<Self, Self::Error> {
+		match value {
+			301 => Ok(Self::MovedPermanently),
+			302 => Ok(Self::Found),
+			303 => Ok(Self::SeeOther),
+			307 => Ok(Self::TemporaryRedirect),
+			308 => Ok(Self::PermanentRedirect),
+			_ => Err(format!(
+				"unsupported redirect status {value} (expected 301, 302, 303, 307, or 308)"
+			)),
+		}
+	}
+}
+
+/// Where to send a request that matched a redirect rule.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Redirection {
+	/// The path or absolute URL to send the client to.
+	pub location: String,
+
+	/// The status to redirect with.
+	pub status: RedirectStatus,
+}
+
+impl Redirection {
+	/// The path part of the location, if it points to this site.
+	#[cfg(feature = "gemini")]
+	pub fn local_path(&self) -> Option<&str> {
+		local_path(&self.location)
+	}
+}
+
+impl RedirectTable {
+	/// Returns where to redirect a request for the given path and query, if anywhere.
+	///
+	/// A path ending in `.html` that matches no rule is tried without the suffix,
+	/// and then redirects to the `.html` variant of a local target, unless the rule
+	/// turns that off. The query, if any, is passed along to the target.
+	pub fn resolve(&self, path: &str, query: Option<&str>) -> Option<Redirection> {
+		let (location, status) = self
+			.find(path)
+			.map(|(rule, location)| (location, rule.status))
+			.or_else(|| {
+				let stem = path.strip_suffix(".html")?;
+				let (rule, location) = self.find(stem)?;
+				let location = if rule.html {
+					with_html_suffix(&location)
+				} else {
+					location
+				};
+				Some((location, rule.status))
+			})?;
+		Some(Redirection {
+			location: with_query(&location, query),
+			status,
+		})
+	}
+
+	/// Returns the first rule that matches the given path, and its target for that path.
+	fn find(&self, path: &str) -> Option<(&RedirectRule, String)> {
+		self.rules.iter().find_map(|rule| {
+			let captures = pattern::captures(&rule.from, path)?;
+			Some((rule, pattern::substitute(&rule.to, &captures)))
+		})
+	}
+
+	/// Reads the redirect table at the given path.
+	pub(super) fn from_file(path: &Path) -> Result<Self, ConfigError> {
+		let text = fs::read_to_string(path).map_err(|source| ConfigError::ReadFile {
+			path: path.to_owned(),
+			source,
+		})?;
+		toml::from_str(&text).map_err(|source| ConfigError::ParseFile {
+			path: path.to_owned(),
+			source,
+		})
+	}
+
+	/// Checks that every rule redirects from a distinct, well-formed pattern to
+	/// somewhere that doesn't lead back to it.
+	pub(super) fn validate(&self) -> Result<(), ConfigError> {
+		for (index, rule) in self.rules.iter().enumerate() {
+			let invalid = |reason: &str| ConfigError::InvalidRedirect {
+				from: rule.from.clone(),
+				reason: reason.to_owned(),
+			};
+
+			let names = pattern::placeholders(&rule.from).map_err(|reason| invalid(&reason))?;
+			if !rule.to.starts_with('/') && Url::parse(&rule.to).is_err() {
+				return Err(invalid(
+					"expected a target path starting with '/', or an absolute URL",
+				));
+			}
+			if let Some(name) = pattern::template_names(&rule.to)
+				.into_iter()
+				.find(|name| !names.contains(name))
+			{
+				return Err(invalid(&format!(
+					"the target uses <{name}>, which isn't in the path"
+				)));
+			}
+			if self
+				.rules
+				.iter()
+				.skip(index.saturating_add(1))
+				.any(|other| other.from == rule.from)
+			{
+				return Err(invalid("redirects more than once"));
+			}
+
+			// Follow the chains of local redirects from an example path and its `.html`
+			// variant, and make sure they end. A chain longer than each rule reached with
+			// and without `.html` counts as a loop too, like a pattern that keeps
+			// growing the path
+			let example = pattern::example(&rule.from);
+			let max_steps = self.rules.len().saturating_mul(2);
+			for start in [format!("{example}.html"), example] {
+				let mut visited = HashSet::new();
+				let mut next = Some(start);
+				while let Some(path) = next {
+					if visited.len() > max_steps || !visited.insert(path.clone()) {
+						return Err(invalid("redirects in a loop"));
+					}
+					next = self
+						.resolve(&path, None)
+						.and_then(|next| local_path(&next.location).map(str::to_owned));
+				}
+			}
+		}
+
+		Ok(())
+	}
+}
+
+/// The path part of the given redirect target, if it points to this site.
+fn local_path(target: &str) -> Option<&str> {
+	if !target.starts_with('/') {
+		return None;
+	}
+	target.split(['?', '#']).next()
+}
+
+/// Adds `.html` to the path of the given local target, unless it names a
+/// directory or already has an extension.
+fn with_html_suffix(target: &str) -> String {
+	let Some(path) = local_path(target) else {
+		return target.to_owned();
+	};
+	let name = path.rsplit('/').next().unwrap_or_default();
+	if name.is_empty() || name.contains('.') {
+		return target.to_owned();
+	}
+	let rest = target.get(path.len()..).unwrap_or_default();
+	format!("{path}.html{rest}")
+}
+
+/// Adds the given query to the given target, before any fragment.
+fn with_query(target: &str, query: Option<&str>) -> String {
+	let Some(query) = query.filter(|query| !query.is_empty()) else {
+		return target.to_owned();
+	};
+	let (base, fragment) = match target.split_once('#') {
+		Some((base, fragment)) => (base, format!("#{fragment}")),
+		None => (target, String::new()),
+	};
+	let separator = if base.contains('?') { '&' } else { '?' };
+	format!("{base}{separator}{query}{fragment}")
+}
+
+// MARK: - Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn table(toml: &str) -> RedirectTable {
+		toml::from_str(toml).expect("Table should parse")
+	}
+
+	fn rejected_from(toml: &str) -> String {
+		match table(toml).validate() {
+			Err(ConfigError::InvalidRedirect { from, .. }) => from,
+			_ => panic!("Expected an invalid redirect error"),
+		}
+	}
+
+	#[test]
+	fn builtin_table_is_valid() {
+		let builtin = RedirectTable::default();
+		assert!(
+			!builtin.rules.is_empty(),
+			"Built-in table should have rules"
+		);
+		builtin.validate().expect("Built-in table should be valid");
+	}
+
+	#[test]
+	fn defaults_to_found() {
+		let table = table("[[redirect]]\nfrom = \"/a\"\nto = \"/b\"\n");
+		let [ref rule] = table.rules[..] else {
+			panic!("Expected exactly 1 rule");
+		};
+		assert_eq!(rule.status, RedirectStatus::Found);
+		assert_eq!(rule.status.code(), 302);
+	}
+
+	#[test]
+	fn rejects_unsupported_status() {
+		for status in [200, 300, 304, 404] {
+			let result = toml::from_str::<RedirectTable>(&format!(
+				"[[redirect]]\nfrom = \"/a\"\nto = \"/b\"\nstatus = {status}\n"
+			));
+			assert!(result.is_err(), "Status {status} should fail");
+		}
+	}
+
+	#[test]
+	fn rejects_duplicate_sources() {
+		let from = rejected_from(
+			"[[redirect]]\nfrom = \"/a\"\nto = \"/b\"\n\n[[redirect]]\nfrom = \"/a\"\nto = \"/c\"\n",
+		);
+		assert_eq!(from, "/a");
+	}
+
+	#[test]
+	fn rejects_loops() {
+		let from = rejected_from("[[redirect]]\nfrom = \"/a\"\nto = \"/a?again\"\n");
+		assert_eq!(from, "/a");
+
+		let from = rejected_from(
+			"[[redirect]]\nfrom = \"/a\"\nto = \"/b\"\n\n[[redirect]]\nfrom = \"/b\"\nto = \"/c\"\n\n[[redirect]]\nfrom = \"/c\"\nto = \"/a\"\n",
+		);
+		assert_eq!(from, "/a");
+	}
+
+	#[test]
+	fn rejects_loops_through_html_variants() {
+		let from = rejected_from(
+			"[[redirect]]\nfrom = \"/a\"\nto = \"/b.html\"\n\n[[redirect]]\nfrom = \"/b\"\nto = \"/a\"\n",
+		);
+		assert_eq!(from, "/a");
+	}
+
+	#[test]
+	fn rejects_chains_that_never_end() {
+		let from = rejected_from("[[redirect]]\nfrom = \"/a/<rest..>\"\nto = \"/a/a/<rest>\"\n");
+		assert_eq!(from, "/a/<rest..>");
+	}
+
+	#[test]
+	fn allows_chains_that_end() {
+		table(
+			"[[redirect]]\nfrom = \"/a\"\nto = \"/b\"\n\n[[redirect]]\nfrom = \"/b\"\nto = \"https://example.com/a\"\n",
+		)
+		.validate()
+		.expect("Chain should be valid");
+	}
+
+	#[test]
+	fn rejects_malformed_paths() {
+		let from = rejected_from("[[redirect]]\nfrom = \"a\"\nto = \"/b\"\n");
+		assert_eq!(from, "a");
+
+		let from = rejected_from("[[redirect]]\nfrom = \"/a b\"\nto = \"/b\"\n");
+		assert_eq!(from, "/a b");
+
+		let from = rejected_from("[[redirect]]\nfrom = \"/a\"\nto = \"b\"\n");
+		assert_eq!(from, "/a");
+	}
+
+	fn location(table: &RedirectTable, path: &str, query: Option<&str>) -> Option<String> {
+		table
+			.resolve(path, query)
+			.map(|redirection| redirection.location)
+	}
+
+	#[test]
+	fn resolves_html_variants() {
+		let table = table(
+			"[[redirect]]\nfrom = \"/how\"\nto = \"/ways\"\n\n[[redirect]]\nfrom = \"/work\"\nto = \"/\"\n\n[[redirect]]\nfrom = \"/now\"\nto = \"https://example.com/now\"\n",
+		);
+		assert_eq!(location(&table, "/how", None).as_deref(), Some("/ways"));
+		assert_eq!(
+			location(&table, "/how.html", None).as_deref(),
+			Some("/ways.html")
+		);
+		assert_eq!(location(&table, "/work.html", None).as_deref(), Some("/"));
+		assert_eq!(
+			location(&table, "/now.html", None).as_deref(),
+			Some("https://example.com/now")
+		);
+		assert_eq!(location(&table, "/ways", None), None);
+	}
+
+	#[test]
+	fn keeps_targets_that_arent_pages() {
+		let table = table(
+			"[[redirect]]\nfrom = \"/pronouns\"\nto = \"/.well-known/pronouns\"\nhtml = false\n",
+		);
+		assert_eq!(
+			location(&table, "/pronouns.html", None).as_deref(),
+			Some("/.well-known/pronouns")
+		);
+	}
+
+	#[test]
+	fn resolves_patterns_with_query() {
+		let table = table(
+			"[[redirect]]\nfrom = \"/blog/<rest..>\"\nto = \"/ways/<rest>\"\nstatus = 301\n\n[[redirect]]\nfrom = \"/@<name>\"\nto = \"https://social.example.com/@<name>?from=site#top\"\n",
+		);
+		assert_eq!(
+			table.resolve("/blog/a/b", Some("page=2")),
+			Some(Redirection {
+				location: "/ways/a/b?page=2".to_owned(),
+				status: RedirectStatus::MovedPermanently,
+			})
+		);
+		assert_eq!(
+			location(&table, "/blog/post.html", None).as_deref(),
+			Some("/ways/post.html")
+		);
+		assert_eq!(
+			location(&table, "/@me", Some("a=b")).as_deref(),
+			Some("https://social.example.com/@me?from=site&a=b#top")
+		);
+	}
+
+	#[test]
+	fn rejects_unknown_target_placeholders() {
+		let from = rejected_from("[[redirect]]\nfrom = \"/<a>\"\nto = \"/<b>\"\n");
+		assert_eq!(from, "/<a>");
+	}
+
+	#[test]
+	fn rejects_pattern_loops() {
+		let from = rejected_from("[[redirect]]\nfrom = \"/a/<rest..>\"\nto = \"/a/<rest>\"\n");
+		assert_eq!(from, "/a/<rest..>");
+
+		let from = rejected_from(
+			"[[redirect]]\nfrom = \"/a/<rest..>\"\nto = \"/b/<rest>\"\n\n[[redirect]]\nfrom = \"/b/<rest..>\"\nto = \"/a/<rest>\"\n",
+		);
+		assert_eq!(from, "/a/<rest..>");
+	}
+}
@@ -0,0 +1,215 @@
+/// Checks that the given pattern is a path whose placeholders are well-formed,
+/// and returns the names of its placeholders.
+pub fn placeholders(pattern: &str) -> Result<Vec<&str>, String> {
+	let Some(path) = pattern.strip_prefix('/') else {
+		return Err("expected a path starting with '/'".to_owned());
+	};
+	if !path
+		.chars()
+		.all(|c| c.is_ascii_graphic() && !matches!(c, '?' | '#'))
+	{
+		return Err("expected a path without spaces, a query, or a fragment".to_owned());
+	}
+
+	let mut names = Vec::new();
+	let mut segments = path.split('/').peekable();
+	while let Some(segment) = segments.next() {
+		let name = if let Some(name) = wildcard(segment) {
+			if segments.peek().is_some() {
+				return Err(format!("<{name}..> must be the last segment"));
+			}
+			name
+		} else if let Some((prefix, name, suffix)) = placeholder(segment) {
+			if prefix.contains(['<', '>']) || suffix.contains(['<', '>']) {
+				return Err(format!(
+					"'{segment}' may have at most one placeholder per segment"
+				));
+			}
+			name
+		} else if segment.contains(['<', '>']) {
+			return Err(format!("'{segment}' has a malformed placeholder"));
+		} else {
+			continue;
+		};
+
+		if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+			return Err(format!(
+				"'<{name}>' should be named with letters, digits, or '_'"
+			));
+		}
+		if names.contains(&name) {
+			return Err(format!("'<{name}>' appears more than once"));
+		}
+		names.push(name);
+	}
+	Ok(names)
+}
+
+/// Matches the given path against the given pattern, and returns the value
+/// of each placeholder, or `None` if the path doesn't match.
+pub fn captures<'a>(pattern: &'a str, path: &'a str) -> Option<Vec<(&'a str, &'a str)>> {
+	let mut captures = Vec::new();
+	let mut rest = Some(path.strip_prefix('/')?);
+
+	for part in pattern.strip_prefix('/')?.split('/') {
+		if let Some(name) = wildcard(part) {
+			captures.push((name, rest.unwrap_or("")));
+			return Some(captures);
+		}
+
+		let (segment, remaining) = match rest?.split_once('/') {
+			Some((segment, remaining)) => (segment, Some(remaining)),
+			None => (rest?, None),
+		};
+		rest = remaining;
+
+		match placeholder(part) {
+			Some((prefix, name, suffix)) => {
+				let value = segment.strip_prefix(prefix)?.strip_suffix(suffix)?;
+				if value.is_empty() {
+					return None;
+				}
+				captures.push((name, value));
+			}
+			None if part == segment => {}
+			None => return None,
+		}
+	}
+
+	rest.is_none().then_some(captures)
+}
+
+/// Replaces each `<name>` in the given template with its captured value.
+/// Placeholders without a value are left as they are.
+pub fn substitute(template: &str, captures: &[(&str, &str)]) -> String {
+	let mut result = String::with_capacity(template.len());
+	let mut rest = template;
+
+	while let Some((before, after)) = rest.split_once('<') {
+		result.push_str(before);
+		let value = after.split_once('>').and_then(|(name, after)| {
+			captures
+				.iter()
+				.find(|(known, _)| *known == name)
+				.map(|(_, value)| (*value, after))
+		});
+		if let Some((value, after)) = value {
+			result.push_str(value);
+			rest = after;
+		} else {
+			result.push('<');
+			rest = after;
+		}
+	}
+
+	result.push_str(rest);
+	result
+}
+
+/// The names of the `<name>` placeholders in the given template.
+pub fn template_names(template: &str) -> Vec<&str> {
+	template
+		.split('<')
+		.skip(1)
+		.filter_map(|part| part.split_once('>').map(|(name, _)| name))
+		.collect()
+}
+
+/// A path that the given pattern matches, with `x` for each placeholder.
+pub fn example(pattern: &str) -> String {
+	let names = placeholders(pattern).unwrap_or_default();
+	let captures: Vec<(&str, &str)> = names.into_iter().map(|name| (name, "x")).collect();
+	substitute(&pattern.replace("..>", ">"), &captures)
+}
+
+/// The name of the given segment's `<name..>` placeholder, if it is one.
+fn wildcard(segment: &str) -> Option<&str> {
+	segment.strip_prefix('<')?.strip_suffix("..>")
+}
+
+/// The literal prefix, name, and literal suffix of the given segment's `<name>` placeholder.
+fn placeholder(segment: &str) -> Option<(&str, &str, &str)> {
+	let (prefix, after) = segment.split_once('<')?;
+	let (name, suffix) = after.split_once('>')?;
+	Some((prefix, name, suffix))
+}
+
+// MARK: - Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn matches_literal_paths() {
+		assert_eq!(captures("/", "/"), Some(vec![]));
+		assert_eq!(captures("/how", "/how"), Some(vec![]));
+		assert_eq!(captures("/how", "/how/"), None);
+		assert_eq!(captures("/how", "/ways"), None);
+		assert_eq!(captures("/a/b", "/a"), None);
+	}
+
+	#[test]
+	fn captures_placeholders() {
+		assert_eq!(captures("/@<name>", "/@avg"), Some(vec![("name", "avg")]));
+		assert_eq!(
+			captures("/@<name>", "/@"),
+			None,
+			"Placeholders aren't empty"
+		);
+		assert_eq!(captures("/@<name>", "/avg"), None);
+		assert_eq!(
+			captures("/<year>/<slug>.html", "/2024/hello.html"),
+			Some(vec![("year", "2024"), ("slug", "hello")])
+		);
+		assert_eq!(captures("/<year>", "/2024/hello"), None);
+	}
+
+	#[test]
+	fn captures_rest_of_path() {
+		assert_eq!(
+			captures("/blog/<rest..>", "/blog/a/b.html"),
+			Some(vec![("rest", "a/b.html")])
+		);
+		assert_eq!(
+			captures("/blog/<rest..>", "/blog"),
+			Some(vec![("rest", "")])
+		);
+		assert_eq!(captures("/blog/<rest..>", "/blogs/a"), None);
+	}
+
+	#[test]
+	fn substitutes_captures() {
+		assert_eq!(substitute("/ways/<rest>", &[("rest", "a/b")]), "/ways/a/b");
+		assert_eq!(
+			substitute("https://example.com/@<name>?<name>", &[("name", "me")]),
+			"https://example.com/@me?me"
+		);
+		assert_eq!(substitute("/<unknown>", &[]), "/<unknown>");
+	}
+
+	#[test]
+	fn builds_examples() {
+		assert_eq!(example("/how"), "/how");
+		assert_eq!(example("/@<name>"), "/@x");
+		assert_eq!(example("/blog/<rest..>"), "/blog/x");
+	}
+
+	#[test]
+	fn rejects_malformed_patterns() {
+		for pattern in [
+			"how",
+			"/how?now",
+			"/a b",
+			"/<rest..>/more",
+			"/<a><b>",
+			"/<a/b>",
+			"/<>",
+			"/<a-b>",
+			"/<a>/<a>",
+		] {
+			assert!(placeholders(pattern).is_err(), "'{pattern}' should fail");
+		}
+		assert_eq!(placeholders("/<a>/<b..>"), Ok(vec!["a", "b"]));
+	}
+}
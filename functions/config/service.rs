@@ -0,0 +1,68 @@
+use serde::Deserialize;
+
+/// One of the webservers that this program can run.
+///
+/// Only the services enabled by cargo features exist in a given build.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Service {
+	/// The Gemini capsule. Requires the `gemini` feature.
+	#[cfg(feature = "gemini")]
+	Gemini,
+
+	/// The HTTP website. Requires the `http` feature.
+	#[cfg(feature = "http")]
+	Http,
+}
+
+impl Service {
+	/// The names of every service this program knows, whether or not it is in this build.
+	const NAMES: &[&str] = &["gemini", "http"];
+
+	/// Returns every service included in this build.
+	pub fn all() -> Vec<Self> {
+		Self::NAMES
+			.iter()
+			.filter_map(|name| name.parse().ok())
+			.collect()
+	}
+}
+
+impl core::fmt::Display for Service {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		match self {
+			#[cfg(feature = "gemini")]
+			Self::Gemini => write!(f, "gemini"),
+			#[cfg(feature = "http")]
+			Self::Http => write!(f, "http"),
+		}
+	}
+}
+
+impl core::str::FromStr for Service {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			#[cfg(feature = "gemini")]
+			"gemini" => Ok(Self::Gemini),
+			#[cfg(feature = "http")]
+			"http" => Ok(Self::Http),
+			name if Self::NAMES.contains(&name) => {
+				Err(format!("the {name} service was not included in this build"))
+			}
+			_ => Err(format!(
+				"unknown service (expected one of {})",
+				Self::NAMES.join(", ")
+			)),
+		}
+	}
+}
+
+impl TryFrom<String> for Service {
+	type Error = String;
+
+	fn try_from(value: String) -> Result<Self, Self::Error> {
+		value.parse()
+	}
+}
@@ -1,2 +1,11 @@
+mod gemtext_client;
+pub use gemtext_client::*;
+
+mod redirects;
+pub use redirects::*;
+
+mod terminal;
+pub use terminal::*;
+
 mod webfinger;
 pub use webfinger::*;
@@ -0,0 +1,46 @@
+use crate::middleware::{accepted_media_types, media_type_quality};
+use rocket::Request;
+use rocket::http::Status;
+use rocket::request::{self, FromRequest};
+
+/// A request guard that forwards unless the client's `Accept` header prefers
+/// `text/gemini` to `text/html`.
+pub struct GemtextClient;
+
+impl GemtextClient {
+	/// Whether the given `Accept` value prefers gemtext to HTML.
+	fn is_preferred(accepted: &str) -> bool {
+		media_type_quality(accepted, "text/gemini") > media_type_quality(accepted, "text/html")
+	}
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for GemtextClient {
+	type Error = ();
+
+	async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+		if Self::is_preferred(&accepted_media_types(req)) {
+			request::Outcome::Success(Self)
+		} else {
+			request::Outcome::Forward(Status::NotFound)
+		}
+	}
+}
+
+// MARK: - Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use test_case::test_case;
+
+	#[test_case("text/gemini", true ; "gemtext")]
+	#[test_case("text/gemini, text/html;q=0.5", true ; "gemtext first")]
+	#[test_case("text/html, text/gemini;q=0.9", false ; "html first")]
+	#[test_case("text/*", false ; "any text")]
+	#[test_case("*/*", false ; "anything")]
+	#[test_case("text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8", false ; "browser")]
+	fn prefers_gemtext(accepted: &str, expected: bool) {
+		assert_eq!(GemtextClient::is_preferred(accepted), expected);
+	}
+}
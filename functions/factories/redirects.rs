@@ -0,0 +1,38 @@
+use crate::config::RedirectTable;
+use http::header;
+use rocket::http::{Header, Method, Status};
+use rocket::route::{Handler, Outcome, Route};
+use rocket::{Data, Request, Response};
+use std::sync::Arc;
+
+/// Ranks the redirect route before every route with a default rank, so that the
+/// table can redirect any path.
+const REDIRECT_RANK: isize = -13;
+
+/// A Rocket route handler that answers requests that match the given table with
+/// a redirect, and forwards the rest.
+#[derive(Clone)]
+struct RedirectHandler(Arc<RedirectTable>);
+
+#[rocket::async_trait]
+impl Handler for RedirectHandler {
+	async fn handle<'r>(&self, req: &'r Request<'_>, data: Data<'r>) -> Outcome<'r> {
+		let uri = req.uri();
+		let query = uri.query().map(|query| query.as_str());
+		let Some(redirection) = self.0.resolve(uri.path().as_str(), query) else {
+			return Outcome::forward(data, Status::NotFound);
+		};
+
+		let response = Response::build()
+			.status(Status::new(redirection.status.code()))
+			.header(Header::new(header::LOCATION.as_str(), redirection.location))
+			.finalize();
+		Outcome::Success(response)
+	}
+}
+
+/// Returns a `GET` route that serves redirects from the given table.
+pub fn redirect_route(table: &RedirectTable) -> Route {
+	let handler = RedirectHandler(Arc::new(table.clone()));
+	Route::ranked(REDIRECT_RANK, Method::Get, "/<path..>", handler)
+}
@@ -0,0 +1,76 @@
+use super::UserAgent;
+use crate::middleware::{accepted_media_types, media_type_quality};
+use core::convert::Infallible;
+use rocket::Request;
+use rocket::request::{self, FromRequest};
+
+/// How to render a page as text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextStyle {
+	/// Plain text, for programs that save or render what they get, like `wget` and `lynx`.
+	Plain,
+
+	/// Text with ANSI escape codes for headings and links, for programs that usually
+	/// print what they get to a terminal, like `curl`.
+	Ansi,
+}
+
+/// A request guard that says how the client would like to read pages as text, if
+/// it's a terminal program or it asks for `text/plain` over `text/html`.
+pub struct TerminalClient(pub Option<TextStyle>);
+
+impl TerminalClient {
+	/// Decides from the given client program name and `Accept` value. The `Accept`
+	/// value wins if it prefers one of `text/plain` and `text/html` to the other.
+	fn new(product: Option<&str>, accepted: &str) -> Self {
+		let plain = media_type_quality(accepted, "text/plain");
+		let html = media_type_quality(accepted, "text/html");
+		if plain != html {
+			return Self((plain > html).then_some(TextStyle::Plain));
+		}
+
+		let style = product.and_then(|product| {
+			if product.eq_ignore_ascii_case("curl") {
+				Some(TextStyle::Ansi)
+			} else if product.eq_ignore_ascii_case("wget") || product.eq_ignore_ascii_case("lynx") {
+				Some(TextStyle::Plain)
+			} else {
+				None
+			}
+		});
+		Self(style)
+	}
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for TerminalClient {
+	type Error = Infallible;
+
+	async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+		let user_agent = req.guard::<UserAgent<'r>>().await.succeeded();
+		let product = user_agent.and_then(|user_agent| user_agent.product());
+		request::Outcome::Success(Self::new(product, &accepted_media_types(req)))
+	}
+}
+
+// MARK: - Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use test_case::test_case;
+
+	const BROWSER_ACCEPT: &str = "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8";
+
+	#[test_case(Some("curl"), "*/*", Some(TextStyle::Ansi) ; "curl")]
+	#[test_case(Some("Wget"), "*/*", Some(TextStyle::Plain) ; "wget")]
+	#[test_case(Some("Lynx"), "text/html, text/plain, text/sgml, */*;q=0.01", Some(TextStyle::Plain) ; "lynx")]
+	#[test_case(Some("Mozilla"), BROWSER_ACCEPT, None ; "browser")]
+	#[test_case(None, "*/*", None ; "unknown client")]
+	#[test_case(None, "text/plain", Some(TextStyle::Plain) ; "asks for text")]
+	#[test_case(Some("curl"), "text/plain", Some(TextStyle::Plain) ; "curl asks for text")]
+	#[test_case(Some("curl"), "text/html", None ; "curl asks for html")]
+	fn picks_text_style(product: Option<&str>, accepted: &str, expected: Option<TextStyle>) {
+		assert_eq!(TerminalClient::new(product, accepted).0, expected);
+	}
+}
@@ -1,9 +1,10 @@
+use crate::config::Profile;
 use crate::middleware::CorsAllowAll;
 use core::{borrow::Borrow, convert::Infallible};
 use rocket::http::{ContentType, Status};
 use rocket::request::{self, FromRequest};
 use rocket::response::{Redirect, Responder};
-use rocket::{Request, Response, uri};
+use rocket::{Request, Response};
 use serde::{Deserialize, Serialize};
 use std::io::Cursor;
 use url::Url;
@@ -49,8 +50,9 @@ impl<'r> AvailableLink<'r> {
 
 #[derive(Serialize, Deserialize)]
 pub struct WebFinger<'r> {
-	pub subject: &'r str,
-	pub aliases: Vec<&'r str>,
+	pub subject: String,
+	pub aliases: Vec<String>,
+	#[serde(borrow)]
 	pub links: Vec<AvailableLink<'r>>,
 }
 impl<'r> Responder<'r, 'static> for WebFinger<'r> {
@@ -69,8 +71,9 @@ impl<'r> Responder<'r, 'static> for WebFinger<'r> {
 	}
 }
 
-/// Answers Webfinger requests. See <https://www.rfc-editor.org/rfc/rfc7033.html>.
+/// Answers Webfinger requests for the given profile. See <https://www.rfc-editor.org/rfc/rfc7033.html>.
 pub fn webfinger<'r>(
+	profile: &'r Profile,
 	resource: &'r str,
 	rel: Option<Vec<&'r str>>,
 ) -> Result<WebFinger<'r>, Status> {
@@ -101,7 +104,7 @@ pub fn webfinger<'r>(
 
 	// "If the "resource" parameter is a value for which the server has no information, the server MUST indicate [not found]"
 	let host = resource.split('@').next_back().unwrap_or("");
-	if !host.ends_with("average.name") && host != "fosstodon.org" {
+	if !profile.knows_host(host) {
 		return Err(Status::NotFound);
 	}
 
@@ -109,8 +112,8 @@ pub fn webfinger<'r>(
 
 	let links: Vec<AvailableLink> = {
 		let mut available_links = vec![
-			AvailableLink::with_profile_page("https://gts.average.name/@avghelper"),
-			AvailableLink::with_self("https://gts.average.name/users/avghelper"),
+			AvailableLink::with_profile_page(profile.fediverse.profile_page.as_str()),
+			AvailableLink::with_self(profile.fediverse.actor.as_str()),
 		];
 
 		// "When the "rel" parameter is used and accepted, only the link relation types that match the link relation type provided via the "rel" parameter are included."
@@ -121,15 +124,13 @@ pub fn webfinger<'r>(
 		available_links
 	};
 
+	let fediverse = &profile.fediverse;
 	Ok(WebFinger {
-		subject: "acct:avghelper@gts.average.name",
-		aliases: vec![
-			"https://average.name/@average",
-			"https://average.name/@avg",
-			"https://average.name/@avghelper",
-			"https://gts.average.name/@avghelper",
-			"https://gts.average.name/users/avghelper",
-		],
+		subject: format!("acct:{}", fediverse.account),
+		aliases: profile
+			.alias_urls()
+			.chain([fediverse.profile_page.clone(), fediverse.actor.clone()])
+			.collect(),
 		links,
 	})
 }
@@ -137,6 +138,15 @@ pub fn webfinger<'r>(
 /// A request guard that provides the request's User-Agent string, if any.
 pub struct UserAgent<'r>(Option<&'r str>);
 
+impl<'r> UserAgent<'r> {
+	/// The name of the client program, like `curl` for `curl/8.4.0`.
+	pub fn product(&self) -> Option<&'r str> {
+		self.0
+			.and_then(|ua| ua.split(['/', ' ']).next())
+			.filter(|product| !product.is_empty())
+	}
+}
+
 #[rocket::async_trait]
 impl<'r> FromRequest<'r> for UserAgent<'r> {
 	type Error = Infallible;
@@ -147,22 +157,23 @@ impl<'r> FromRequest<'r> for UserAgent<'r> {
 	}
 }
 
-/// Returns my Fedi's nodeinfo if the requester is GitHub's noneinfo query bot.
-pub fn nodeinfo<'r, U: Borrow<UserAgent<'r>>>(user_agent: U) -> Result<Redirect, Status> {
+/// Returns the profile's Fedi nodeinfo if the requester is GitHub's noneinfo query bot.
+pub fn nodeinfo<'r, U: Borrow<UserAgent<'r>>>(
+	profile: &Profile,
+	user_agent: U,
+) -> Result<Redirect, Status> {
 	// Who's asking?
 	let user_agent = user_agent.borrow();
-	if user_agent.0.is_none()
-		|| user_agent
-			.0
-			.is_some_and(|ua| !ua.starts_with("GitHub-NodeinfoQuery"))
+	if user_agent
+		.0
+		.is_none_or(|ua| !ua.starts_with("GitHub-NodeinfoQuery"))
 	{
 		// Non-GitHub User-Agent provided, hide:
 		return Err(Status::NotFound);
 	}
 
 	// GitHub is asking. Point to Fedi:
-	let fedi = uri!("https://gts.average.name/.well-known/nodeinfo");
-	Ok(Redirect::found(fedi))
+	Ok(Redirect::found(profile.fediverse.nodeinfo.clone()))
 }
 
 // MARK: - Tests
@@ -170,11 +181,15 @@ pub fn nodeinfo<'r, U: Borrow<UserAgent<'r>>>(user_agent: U) -> Result<Redirect,
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use crate::config::FediverseAccount;
+	use std::sync::LazyLock;
+
+	static PROFILE: LazyLock<Profile> = LazyLock::new(Profile::default);
 
 	#[test]
 	fn nodeinfo_returns_not_found_without_user_agent() {
 		let no_user_agent = UserAgent(None);
-		match nodeinfo(no_user_agent) {
+		match nodeinfo(&PROFILE, no_user_agent) {
 			Ok(_) => panic!("Expected 404"),
 			Err(status) => assert_eq!(status, Status::NotFound),
 		}
@@ -183,7 +198,7 @@ mod tests {
 	#[test]
 	fn nodeinfo_returns_not_found_with_regular_user_agent() {
 		let user_agent = UserAgent(Some("foo"));
-		match nodeinfo(user_agent) {
+		match nodeinfo(&PROFILE, user_agent) {
 			Ok(_) => panic!("Expected 404"),
 			Err(status) => assert_eq!(status, Status::NotFound),
 		}
@@ -192,12 +207,23 @@ mod tests {
 	#[test]
 	fn nodeinfo_returns_redirect_with_github_user_agent() {
 		let user_agent = UserAgent(Some("GitHub-NodeinfoQuery"));
-		nodeinfo(user_agent).expect("Expected 302");
+		nodeinfo(&PROFILE, user_agent).expect("Expected 302");
+	}
+
+	#[test]
+	fn user_agent_names_product() {
+		assert_eq!(UserAgent(Some("curl/8.4.0")).product(), Some("curl"));
+		assert_eq!(
+			UserAgent(Some("Lynx/2.9.0dev.12 libwww-FM/2.14")).product(),
+			Some("Lynx")
+		);
+		assert_eq!(UserAgent(Some("")).product(), None);
+		assert_eq!(UserAgent(None).product(), None);
 	}
 
 	#[test]
 	fn webfinger_fails_if_resource_param_is_empty() {
-		match webfinger("", None) {
+		match webfinger(&PROFILE, "", None) {
 			Ok(_) => panic!("Expected 400"),
 			Err(status) => assert_eq!(status, Status::BadRequest),
 		}
@@ -205,7 +231,7 @@ mod tests {
 
 	#[test]
 	fn webfinger_fails_if_resource_param_is_not_a_url() {
-		match webfinger("foo", None) {
+		match webfinger(&PROFILE, "foo", None) {
 			Ok(_) => panic!("Expected 400"),
 			Err(status) => assert_eq!(status, Status::BadRequest),
 		}
@@ -213,7 +239,7 @@ mod tests {
 
 	#[test]
 	fn webfinger_fails_if_resource_param_is_only_protocol() {
-		match webfinger("acct:", None) {
+		match webfinger(&PROFILE, "acct:", None) {
 			Ok(_) => panic!("Expected 400"),
 			Err(status) => assert_eq!(status, Status::BadRequest),
 		}
@@ -221,7 +247,7 @@ mod tests {
 
 	#[test]
 	fn webfinger_fails_if_resource_param_is_not_acct() {
-		match webfinger("https:foo.bar", None) {
+		match webfinger(&PROFILE, "https:foo.bar", None) {
 			Ok(_) => panic!("Expected 404"),
 			Err(status) => assert_eq!(status, Status::NotFound),
 		}
@@ -229,7 +255,7 @@ mod tests {
 
 	#[test]
 	fn webfinger_fails_if_resource_url_param_host_is_not_known() {
-		match webfinger("acct:foo.bar", None) {
+		match webfinger(&PROFILE, "acct:foo.bar", None) {
 			Ok(_) => panic!("Expected 404"),
 			Err(status) => assert_eq!(status, Status::NotFound),
 		}
@@ -237,7 +263,7 @@ mod tests {
 
 	#[test]
 	fn webfinger_fails_if_resource_account_param_host_is_not_known() {
-		match webfinger("acct:foo@foo.bar", None) {
+		match webfinger(&PROFILE, "acct:foo@foo.bar", None) {
 			Ok(_) => panic!("Expected 404"),
 			Err(status) => assert_eq!(status, Status::NotFound),
 		}
@@ -299,49 +325,54 @@ mod tests {
 
 	#[test]
 	fn webfinger_succeeds_with_resource_acct_average_name() {
-		let res = webfinger("acct:average.name", None);
+		let res = webfinger(&PROFILE, "acct:average.name", None);
 		assert_base_finger(res);
 	}
 
 	#[test]
 	fn webfinger_succeeds_with_resource_acct_average_average_name() {
-		let res = webfinger("acct:average@average.name", None);
+		let res = webfinger(&PROFILE, "acct:average@average.name", None);
 		assert_base_finger(res);
 	}
 
 	#[test]
 	fn webfinger_succeeds_with_resource_acct_gts_average_average_name() {
-		let res = webfinger("acct:average@gts.average.name", None);
+		let res = webfinger(&PROFILE, "acct:average@gts.average.name", None);
 		assert_base_finger(res);
 	}
 
 	#[test]
 	fn webfinger_succeeds_with_resource_acct_social_average_average_name() {
-		let res = webfinger("acct:average@social.average.name", None);
+		let res = webfinger(&PROFILE, "acct:average@social.average.name", None);
 		assert_base_finger(res);
 	}
 
 	#[test]
 	fn webfinger_succeeds_with_resource_acct_any_subdomain_average_average_name() {
-		let res = webfinger("acct:average@thissubdomaindoesnotexist.average.name", None);
+		let res = webfinger(
+			&PROFILE,
+			"acct:average@thissubdomaindoesnotexist.average.name",
+			None,
+		);
 		assert_base_finger(res);
 	}
 
 	#[test]
 	fn webfinger_succeeds_with_resource_acct_fosstodon_org() {
-		let res = webfinger("acct:fosstodon.org", None);
+		let res = webfinger(&PROFILE, "acct:fosstodon.org", None);
 		assert_base_finger(res);
 	}
 
 	#[test]
 	fn webfinger_succeeds_with_resource_acct_avghelper_fosstodon_org() {
-		let res = webfinger("acct:avghelper@fosstodon.org", None);
+		let res = webfinger(&PROFILE, "acct:avghelper@fosstodon.org", None);
 		assert_base_finger(res);
 	}
 
 	#[test]
 	fn webfinger_responds_with_rel_self() {
-		let Ok(data) = webfinger("acct:avghelper@fosstodon.org", Some(vec!["self"])) else {
+		let Ok(data) = webfinger(&PROFILE, "acct:avghelper@fosstodon.org", Some(vec!["self"]))
+		else {
 			panic!("Expected 200");
 		};
 		assert_eq!(data.subject, "acct:avghelper@gts.average.name");
@@ -361,4 +392,51 @@ mod tests {
 			"https://gts.average.name/users/avghelper"
 		);
 	}
+
+	#[test]
+	fn webfinger_answers_for_given_profile() {
+		let profile = Profile {
+			origin: "https://example.com".to_owned(),
+			webfinger_domains: Vec::new(),
+			aliases: vec!["me".to_owned()],
+			fediverse: FediverseAccount {
+				account: "me@social.example.com".to_owned(),
+				profile_page: "https://social.example.com/@me".to_owned(),
+				actor: "https://social.example.com/users/me".to_owned(),
+				nodeinfo: "https://social.example.com/.well-known/nodeinfo".to_owned(),
+			},
+			..Profile::default()
+		};
+
+		let Ok(data) = webfinger(&profile, "acct:me@example.com", None) else {
+			panic!("Expected 200");
+		};
+		assert_eq!(data.subject, "acct:me@social.example.com");
+		assert_eq!(
+			data.aliases,
+			vec![
+				"https://example.com/@me",
+				"https://social.example.com/@me",
+				"https://social.example.com/users/me",
+			]
+		);
+		assert_eq!(
+			data.links.iter().map(|link| link.href).collect::<Vec<_>>(),
+			vec![
+				Some("https://social.example.com/@me"),
+				Some("https://social.example.com/users/me"),
+			]
+		);
+
+		for resource in [
+			"acct:avghelper@average.name",
+			"acct:avghelper@fosstodon.org",
+			"acct:me@notexample.com",
+		] {
+			match webfinger(&profile, resource, None) {
+				Ok(_) => panic!("Expected 404 for {resource}"),
+				Err(status) => assert_eq!(status, Status::NotFound),
+			}
+		}
+	}
 }
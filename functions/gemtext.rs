@@ -0,0 +1,95 @@
+#[cfg(feature = "http")]
+mod capsule_routes;
+#[cfg(feature = "http")]
+pub use capsule_routes::*;
+
+#[cfg(feature = "http")]
+mod html;
+#[cfg(feature = "http")]
+pub use html::*;
+
+#[cfg(feature = "http")]
+mod parser;
+#[cfg(feature = "http")]
+pub use parser::*;
+
+use rust_embed::Embed;
+use std::borrow::Cow;
+use std::path::Path;
+use tokio::fs;
+
+// Generated in build.rs:
+include!(concat!(env!("OUT_DIR"), "/ways.rs"));
+
+/// The Ways index document, generated in build.rs.
+const WAYS_INDEX: &str = include_str!(concat!(env!("OUT_DIR"), "/ways.gmi"));
+
+/// Where gemtext is read from when serving from disk, relative to the working directory.
+const GEMTEXT_DIR: &str = "src/content/gemtext";
+
+#[derive(Embed)]
+#[folder = "src/content/gemtext/"]
+#[prefix = "/"]
+#[include = "*.gmi"]
+struct GemtextAsset;
+
+/// The name of the file in `src/content/gemtext` that holds the document at the
+/// given capsule path, like `/contact.gmi` for `/contact`.
+fn file_path(path: &str) -> Option<String> {
+	match path {
+		"/" => Some("/index.gmi".to_owned()),
+		"/index" => None, // Only reachable at the root
+		_ => Some(format!("{path}.gmi")),
+	}
+}
+
+/// Whether the capsule has a document at the given path.
+#[cfg(feature = "http")]
+pub fn is_document(path: &str) -> bool {
+	path == "/ways"
+		|| ways_from_slug(path).is_some()
+		|| file_path(path).is_some_and(|file_path| GemtextAsset::get(&file_path).is_some())
+}
+
+/// Returns the bytes of the capsule document at the given path, like `/`, `/contact`,
+/// or `/ways/ways`, or `None` if there is no such document.
+///
+/// Documents from `src/content/gemtext` are read from disk if `from_disk` is set.
+pub async fn document(path: &str, from_disk: bool) -> Option<Cow<'static, [u8]>> {
+	if path == "/ways" {
+		return Some(Cow::Borrowed(WAYS_INDEX.as_bytes()));
+	}
+	if let Some(doc) = ways_from_slug(path) {
+		return Some(Cow::Borrowed(doc.as_bytes()));
+	}
+
+	let file_path = file_path(path)?;
+	if from_disk {
+		let file_path = Path::new(GEMTEXT_DIR).join(file_path.trim_start_matches('/'));
+		fs::read(file_path).await.ok().map(Cow::Owned)
+	} else {
+		GemtextAsset::get(&file_path).map(|asset| asset.data)
+	}
+}
+
+// MARK: - Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use test_case::test_case;
+
+	#[test_case("/", true ; "root")]
+	#[test_case("/contact", true ; "static document")]
+	#[test_case("/ways", true ; "ways index")]
+	#[test_case("/ways/ways", true ; "ways document")]
+	#[test_case("/index", false ; "index by name")]
+	#[test_case("/humans.txt", false ; "other file")]
+	#[test_case("/nothing", false ; "missing document")]
+	#[tokio::test]
+	async fn finds_document(path: &str, expected: bool) {
+		#[cfg(feature = "http")]
+		assert_eq!(is_document(path), expected);
+		assert_eq!(document(path, false).await.is_some(), expected);
+	}
+}
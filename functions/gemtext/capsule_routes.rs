@@ -0,0 +1,8 @@
+/// Paths that the capsule serves without a document, so the website has nothing
+/// at them. Links to these should go to the capsule itself.
+pub const CAPSULE_ROUTES: &[&str] = &["/guestbook", "/guestbook/sign", "/search", "/ways/atom.xml"];
+
+/// Whether the capsule serves the given path without a document.
+pub fn is_capsule_route(path: &str) -> bool {
+	CAPSULE_ROUTES.contains(&path)
+}
@@ -0,0 +1,169 @@
+use super::{Line, is_capsule_route, is_document};
+use crate::utils::{escape_html, with_element_contents};
+use url::Url;
+
+/// Where the website serves the capsule's documents.
+pub const PROXY_PREFIX: &str = "/gemini";
+
+/// Renders the given gemtext lines as HTML, pointing each link at the `href` that
+/// `link_href` gives for its URL.
+pub fn to_html(lines: &[Line<'_>], link_href: impl Fn(&str) -> String) -> String {
+	let mut elements = Vec::new();
+	let mut open_group: Option<&str> = None; // the list or quote we're in, if any
+	for line in lines {
+		let group = match line {
+			Line::ListItem(_) => Some("ul"),
+			Line::Quote(_) => Some("blockquote"),
+			_ => None,
+		};
+		if open_group != group {
+			elements.extend(open_group.map(|tag| format!("</{tag}>\n")));
+			elements.extend(group.map(|tag| format!("<{tag}>\n")));
+			open_group = group;
+		}
+
+		let element = match line {
+			Line::Text("") => continue,
+			Line::Text(text) | Line::Quote(text) => format!("<p>{}</p>\n", escape_html(text)),
+			Line::Link { url, label } => format!(
+				"<p><a href=\"{}\">{}</a></p>\n",
+				escape_html(&link_href(url)),
+				escape_html(label.unwrap_or(url))
+			),
+			Line::Heading { level, text } => {
+				format!("<h{level}>{}</h{level}>\n", escape_html(text))
+			}
+			Line::ListItem(text) => format!("<li>{}</li>\n", escape_html(text)),
+			Line::Preformatted { alt, lines } => {
+				let label = alt
+					.map(|alt| format!(" role=\"img\" aria-label=\"{}\"", escape_html(alt)))
+					.unwrap_or_default();
+				let text = escape_html(&lines.join("\n"));
+				format!("<pre{label}><code>{text}</code></pre>\n")
+			}
+		};
+		elements.push(element);
+	}
+	elements.extend(open_group.map(|tag| format!("</{tag}>\n")));
+	elements.concat()
+}
+
+/// The document's title, which is its first top-level heading.
+pub fn title<'a>(lines: &[Line<'a>]) -> Option<&'a str> {
+	lines.iter().find_map(|line| match line {
+		Line::Heading { level: 1, text } => Some(*text),
+		_ => None,
+	})
+}
+
+/// Where the given link, found in the capsule document at `doc_path`, should point
+/// on the website. Links to the capsule's documents stay inside the proxy, links to
+/// its other routes go to the capsule, links to its other files go to the same path
+/// on the website, and links elsewhere are kept.
+pub fn proxied_link(link: &str, doc_path: &str, hostname: &str) -> String {
+	let Ok(base) = Url::parse(&format!("gemini://{hostname}{doc_path}")) else {
+		return link.to_owned();
+	};
+	let Ok(target) = base.join(link) else {
+		return link.to_owned();
+	};
+	if target.scheme() != "gemini" || target.host_str() != Some(hostname) {
+		return link.to_owned();
+	}
+
+	let path = target.path();
+	if is_capsule_route(path) {
+		return target.into();
+	}
+	let mut href = match path {
+		"/" => PROXY_PREFIX.to_owned(),
+		_ if is_document(path) => format!("{PROXY_PREFIX}{path}"),
+		_ => path.to_owned(),
+	};
+	if let Some(query) = target.query() {
+		href.push('?');
+		href.push_str(query);
+	}
+	if let Some(fragment) = target.fragment() {
+		href.push('#');
+		href.push_str(fragment);
+	}
+	href
+}
+
+/// An HTML page with the given title and content. The page is based on the given
+/// template, like the site's 404 page, with its `<title>` and its `<main>` (or else
+/// `<body>`) contents replaced, so that it keeps the site's style.
+pub fn html_page(template: Option<&str>, title: &str, content: &str) -> String {
+	let title = escape_html(title);
+	let page = template.and_then(|template| {
+		with_element_contents(template, "main", content)
+			.or_else(|| with_element_contents(template, "body", content))
+	});
+	match page {
+		Some(page) => with_element_contents(&page, "title", &title).unwrap_or(page),
+		None => format!(
+			"<!doctype html>\n<html lang=\"en\">\n<head><meta charset=\"utf-8\" /><title>{title}</title></head>\n<body><main>\n{content}</main></body>\n</html>\n"
+		),
+	}
+}
+
+// MARK: - Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::gemtext::parse;
+	use test_case::test_case;
+
+	#[test]
+	fn renders_html() {
+		let gemtext = "# Hello <world>\n\nSome text & more\n=> /ways Ways\n=> https://example.com\n* One\n* Two\n> Quoted\n> Again\n```ascii art\n<o>\n```\n### End";
+		let html = to_html(&parse(gemtext), |url| format!("{url}#x"));
+		assert_eq!(
+			html,
+			"<h1>Hello &lt;world&gt;</h1>\n\
+			<p>Some text &amp; more</p>\n\
+			<p><a href=\"/ways#x\">Ways</a></p>\n\
+			<p><a href=\"https://example.com#x\">https://example.com</a></p>\n\
+			<ul>\n<li>One</li>\n<li>Two</li>\n</ul>\n\
+			<blockquote>\n<p>Quoted</p>\n<p>Again</p>\n</blockquote>\n\
+			<pre role=\"img\" aria-label=\"ascii art\"><code>&lt;o&gt;</code></pre>\n\
+			<h3>End</h3>\n"
+		);
+	}
+
+	#[test]
+	fn finds_title() {
+		assert_eq!(title(&parse("## Sub\n# Main\n# Other")), Some("Main"));
+		assert_eq!(title(&parse("Just text")), None);
+	}
+
+	#[test_case("/ways", "/", "/gemini/ways" ; "document")]
+	#[test_case("/", "/contact", "/gemini" ; "root")]
+	#[test_case("ways", "/ways/ways", "/gemini/ways/ways" ; "relative document")]
+	#[test_case("gemini://average.name/contact", "/", "/gemini/contact" ; "absolute document")]
+	#[test_case("/humans.txt", "/", "/humans.txt" ; "other file")]
+	#[test_case("/search", "/ways", "gemini://average.name/search" ; "capsule route")]
+	#[test_case("atom.xml", "/ways/ways", "gemini://average.name/ways/atom.xml" ; "relative capsule route")]
+	#[test_case("/ways?q=1#top", "/", "/gemini/ways?q=1#top" ; "query and fragment")]
+	#[test_case("gemini://git.average.name", "/", "gemini://git.average.name" ; "other capsule")]
+	#[test_case("https://average.name", "/", "https://average.name" ; "other scheme")]
+	fn rewrites_link(link: &str, doc_path: &str, expected: &str) {
+		assert_eq!(proxied_link(link, doc_path, "average.name"), expected);
+	}
+
+	#[test]
+	fn fills_template() {
+		let template = "<html><head><title>Not Found</title></head><body><nav>Nav</nav><main class=\"page\"><h1>Not Found</h1></main></body></html>";
+		assert_eq!(
+			html_page(Some(template), "A & B", "<p>Hi</p>"),
+			"<html><head><title>A &amp; B</title></head><body><nav>Nav</nav><main class=\"page\"><p>Hi</p></main></body></html>"
+		);
+		assert_eq!(
+			html_page(Some("<body><h1>Not Found</h1></body>"), "Hi", "<p>Hi</p>"),
+			"<body><p>Hi</p></body>"
+		);
+		assert!(html_page(None, "Hi", "<p>Hi</p>").contains("<title>Hi</title>"));
+	}
+}
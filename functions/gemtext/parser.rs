@@ -0,0 +1,138 @@
+/// A line (or, for preformatted text, a block of lines) of a gemtext document, as
+/// described in <gemini://geminiprotocol.net/docs/gemtext-specification.gmi>
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Line<'a> {
+	/// A line of plain text, which may be empty.
+	Text(&'a str),
+
+	/// A link to the given URL, which may be relative to the document.
+	Link {
+		url: &'a str,
+		label: Option<&'a str>,
+	},
+
+	/// A heading of level 1, 2, or 3.
+	Heading { level: u8, text: &'a str },
+
+	/// An item of an unordered list.
+	ListItem(&'a str),
+
+	/// A line of quoted text.
+	Quote(&'a str),
+
+	/// Lines of text between toggle lines, to be shown as they are.
+	Preformatted {
+		alt: Option<&'a str>,
+		lines: Vec<&'a str>,
+	},
+}
+
+/// Parses the given gemtext document into its lines.
+pub fn parse(gemtext: &str) -> Vec<Line<'_>> {
+	let mut parsed = Vec::new();
+	let mut lines = gemtext.lines();
+	while let Some(line) = lines.next() {
+		if let Some(alt) = line.strip_prefix("```") {
+			// Everything up to the next toggle line (or the end) is preformatted
+			let block = lines.by_ref().take_while(|line| !line.starts_with("```"));
+			parsed.push(Line::Preformatted {
+				alt: non_empty(alt),
+				lines: block.collect(),
+			});
+		} else {
+			parsed.push(parse_line(line));
+		}
+	}
+	parsed
+}
+
+/// Parses a line that isn't preformatted.
+fn parse_line(line: &str) -> Line<'_> {
+	if let Some(rest) = line.strip_prefix("=>") {
+		let rest = rest.trim_start();
+		let (url, label) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+		if url.is_empty() {
+			return Line::Text(line);
+		}
+		return Line::Link {
+			url,
+			label: non_empty(label),
+		};
+	}
+	if let Some(text) = line.strip_prefix("###") {
+		return Line::Heading {
+			level: 3,
+			text: text.trim(),
+		};
+	}
+	if let Some(text) = line.strip_prefix("##") {
+		return Line::Heading {
+			level: 2,
+			text: text.trim(),
+		};
+	}
+	if let Some(text) = line.strip_prefix('#') {
+		return Line::Heading {
+			level: 1,
+			text: text.trim(),
+		};
+	}
+	if let Some(text) = line.strip_prefix("* ") {
+		return Line::ListItem(text.trim());
+	}
+	if let Some(text) = line.strip_prefix('>') {
+		return Line::Quote(text.trim());
+	}
+	Line::Text(line)
+}
+
+/// The given text without surrounding whitespace, or `None` if that leaves nothing.
+fn non_empty(text: &str) -> Option<&str> {
+	let text = text.trim();
+	(!text.is_empty()).then_some(text)
+}
+
+// MARK: - Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use test_case::test_case;
+
+	#[test_case("Hello", &Line::Text("Hello") ; "text")]
+	#[test_case("", &Line::Text("") ; "empty line")]
+	#[test_case("=> /ways Ways folder", &Line::Link { url: "/ways", label: Some("Ways folder") } ; "link")]
+	#[test_case("=>\thttps://average.name", &Line::Link { url: "https://average.name", label: None } ; "link without label")]
+	#[test_case("=>", &Line::Text("=>") ; "link without url")]
+	#[test_case("# Hello, world!", &Line::Heading { level: 1, text: "Hello, world!" } ; "heading")]
+	#[test_case("##Stuff", &Line::Heading { level: 2, text: "Stuff" } ; "subheading")]
+	#[test_case("### More ", &Line::Heading { level: 3, text: "More" } ; "sub-subheading")]
+	#[test_case("#### Deeper", &Line::Heading { level: 3, text: "# Deeper" } ; "too deep")]
+	#[test_case("* Item", &Line::ListItem("Item") ; "list item")]
+	#[test_case("*Emphasis*", &Line::Text("*Emphasis*") ; "not a list item")]
+	#[test_case("> Quoted", &Line::Quote("Quoted") ; "quote")]
+	fn parses_line(line: &str, expected: &Line<'_>) {
+		assert_eq!(&parse_line(line), expected);
+	}
+
+	#[test]
+	fn parses_preformatted_blocks() {
+		let gemtext =
+			"Before\n```rust code\nfn main() {\n# not a heading\n```\nAfter\n```\n=> unterminated";
+		assert_eq!(
+			parse(gemtext),
+			vec![
+				Line::Text("Before"),
+				Line::Preformatted {
+					alt: Some("rust code"),
+					lines: vec!["fn main() {", "# not a heading"],
+				},
+				Line::Text("After"),
+				Line::Preformatted {
+					alt: None,
+					lines: vec!["=> unterminated"],
+				},
+			]
+		);
+	}
+}
@@ -1,723 +1,186 @@
+#[cfg(feature = "gemini")]
 mod capsule;
 mod config;
+#[cfg(feature = "http")]
 mod factories;
+mod gemtext;
+#[cfg(feature = "http")]
 mod middleware;
+mod shutdown;
+mod supervisor;
 mod utils;
-
-use capsule::gemini_service;
-use config::Config;
-use factories::{UserAgent, WebFinger};
-use include_dir::{Dir, include_dir};
-use middleware::{
-	Clacks, CorsAllowAllResponse, CorsOnlyProdResponse, ExtraSecurityHeaders, PRONOUNS_EN,
-	PronounsAcceptable, TrimSlash, shield,
-};
-use rocket::{Build, Rocket, catch, catchers, get, routes, uri};
-use rocket::{
-	http::{ContentType, Status},
-	response::{
-		Redirect, content::RawHtml, content::RawJson, status::BadRequest, status::NotFound,
-	},
-};
-use rocket_async_compression::CachedCompression;
-use std::process::ExitCode;
-use std::{ffi::OsStr, path::PathBuf};
-
-// MARK: - Routes
-
-/// Favicon is always not found
-#[get("/favicon.ico")]
-fn favicon() -> NotFound<&'static str> {
-	NotFound("")
-}
-
-#[get("/ip")]
-fn ip() -> Redirect {
-	Redirect::found(uri!("https://ip.average.name"))
-}
-
-#[get("/work")]
-fn work() -> Redirect {
-	Redirect::to(uri!("/"))
-}
-
-#[get("/work.html")]
-fn work_html() -> Redirect {
-	Redirect::to(uri!("/"))
-}
-
-// MARK: Ways
-
-#[get("/how")]
-fn how() -> Redirect {
-	Redirect::found(uri!("/ways"))
-}
-
-#[get("/how.html")]
-fn how_html() -> Redirect {
-	Redirect::found(uri!("/ways.html"))
-}
-
-// MARK: Links
-
-#[get("/bookmarks")]
-fn bookmarks() -> Redirect {
-	Redirect::found(uri!("/links"))
-}
-
-#[get("/bookmarks.html")]
-fn bookmarks_html() -> Redirect {
-	Redirect::found(uri!("/links.html"))
-}
-
-#[get("/now")]
-fn now() -> Redirect {
-	Redirect::found(uri!(
-		"https://git.average.name/AverageHelper?tab=repositories"
-	))
-}
-
-// MARK: Pronouns
-
-#[get("/pronouns")]
-fn pronouns() -> Redirect {
-	Redirect::found(uri!(well_known_pronouns()))
-}
-
-#[get("/.well-known/pronouns")]
-fn well_known_pronouns() -> CorsAllowAllResponse<&'static str> {
-	CorsAllowAllResponse(PRONOUNS_EN)
-}
-
-// MARK: Fursona
-
-#[get("/fursona.json")]
-fn root_fursona_json() -> Redirect {
-	Redirect::found(uri!(fursona()))
-}
-
-#[get("/.well-known/fursona")]
-fn well_known_fursona() -> Redirect {
-	Redirect::found(uri!(fursona()))
-}
-
-static AVATAR_IMAGE: &[u8] = include_bytes!("../dist/images/refs/AverageHelper-avatar.png");
-static FURSONA_JSON: &str = include_str!("../dist/.well-known/fursona.json");
-
-/// Serves my fursona avatar image, without CORS so external readers can access the file.
-#[get("/images/refs/AverageHelper-avatar.png")]
-fn avatar() -> CorsAllowAllResponse<(ContentType, &'static [u8])> {
-	CorsAllowAllResponse((ContentType::PNG, AVATAR_IMAGE))
-}
-
-/// Serves fursona.json, without CORS so external readers can access the file.
-#[get("/.well-known/fursona.json")]
-fn fursona() -> CorsAllowAllResponse<RawJson<&'static str>> {
-	CorsAllowAllResponse(RawJson(FURSONA_JSON))
-}
-
-// MARK: Fediverse aliases
-
-#[get("/@avg")]
-fn at_avg() -> Redirect {
-	Redirect::found(uri!("https://gts.average.name/@avghelper"))
-}
-
-#[get("/@avghelper")]
-fn at_avghelper() -> Redirect {
-	Redirect::found(uri!("https://gts.average.name/@avghelper"))
-}
-
-#[get("/@average")]
-fn at_average() -> Redirect {
-	Redirect::found(uri!("https://gts.average.name/@avghelper"))
-}
-
-#[get("/.well-known/webfinger?<resource>&<rel>")]
-fn webfinger<'r>(
-	resource: Option<&'r str>,
-	rel: Option<Vec<&'r str>>,
-) -> Result<WebFinger<'r>, Status> {
-	match resource {
-		None => Err(Status::BadRequest),
-		Some(resource) => factories::webfinger(resource, rel),
-	}
-}
-
-#[get("/.well-known/nodeinfo")]
-fn nodeinfo(user_agent: UserAgent<'_>) -> Result<Redirect, Status> {
-	factories::nodeinfo(user_agent)
-}
-
-// MARK: /dist
-
-static DIST: Dir = include_dir!("dist");
-static ROOT: &str = include_str!("../dist/index.html");
-
-#[get("/")]
-fn root() -> CorsOnlyProdResponse<RawHtml<&'static str>> {
-	CorsOnlyProdResponse(RawHtml(ROOT))
+#[cfg(feature = "http")]
+mod website;
+
+#[cfg(not(any(feature = "http", feature = "gemini")))]
+compile_error!("At least one of the `http` or `gemini` features must be enabled");
+
+#[cfg(feature = "gemini")]
+use capsule::{CertError, gemini_service};
+use config::{Config, ConfigError, Service};
+#[cfg(feature = "http")]
+use core::pin::pin;
+#[cfg(feature = "http")]
+use futures::future::join_all;
+use shutdown::Shutdown;
+use std::{io, process::ExitCode, sync::Arc};
+use supervisor::{RestartPolicy, supervise};
+use tokio::task::JoinError;
+#[cfg(feature = "http")]
+use website::{http_service, launch};
+
+#[cfg(feature = "gemini")]
+async fn start_gemini_service(config: &Config, shutdown: &Shutdown) -> Result<(), AppError> {
+	gemini_service(config, shutdown).await?;
+	Ok(())
 }
 
-#[get("/<path..>")]
-fn dist(path: PathBuf) -> CorsOnlyProdResponse<Option<(ContentType, &'static [u8])>> {
-	let mut path = path;
-
-	// If a directory, try adding .html and see if that exists.
-	if DIST.get_dir(&path).is_some() {
-		let adjacent_html = path.with_extension("html");
-		let inner_html = path.join("index.html");
-		if DIST.contains(&adjacent_html) {
-			path = adjacent_html;
-		} else if DIST.contains(&inner_html) {
-			path = inner_html;
-		}
-	} else if !DIST.contains(&path) {
-		let as_html = path.with_extension("html");
-		if DIST.contains(&as_html) {
-			path = as_html;
-		}
-	}
-
-	let res = match DIST.get_file(&path) {
-		None => None,
-		Some(asset) => {
-			let content_type = path
-				.extension()
-				.and_then(OsStr::to_str)
-				.and_then(ContentType::from_extension)
-				.unwrap_or(ContentType::Bytes);
-			Some((content_type, asset.contents()))
+#[cfg(feature = "http")]
+async fn start_http_service(config: &Config, shutdown: &Shutdown) -> Result<(), AppError> {
+	// Rocket listens at only one address, so launch an instance for each. If any
+	// of them fails, the rest stop gracefully, without stopping other services
+	let stop = Shutdown::default();
+	let launches = config.http.addresses.iter().map(|&address| {
+		let stop = &stop;
+		async move {
+			let result = launch(http_service(config, address), stop).await;
+			if result.is_err() {
+				stop.trigger();
+			}
+			result
+		}
+	});
+	let mut launches = pin!(join_all(launches));
+
+	let results = tokio::select! {
+		results = &mut launches => results,
+		() = shutdown.triggered() => {
+			stop.trigger();
+			launches.await
 		}
 	};
-	CorsOnlyProdResponse(res)
-}
-
-// MARK: - Service
-
-#[catch(400)]
-fn bad_request() -> BadRequest<&'static str> {
-	BadRequest("Bad Request")
-}
-
-#[catch(404)]
-fn not_found() -> NotFound<RawHtml<&'static str>> {
-	let not_found = include_str!("../dist/404.html");
-	NotFound(RawHtml(not_found))
-}
-
-fn http_service(config: &Config) -> Rocket<Build> {
-	let config = config.rocket_config();
-
-	let suffixes = [".css", ".html", ".svg", ".xml", ".txt"]
-		.iter()
-		.map(ToString::to_string)
-		.collect();
-
-	rocket::build()
-		.configure(config)
-		.attach(CachedCompression::path_suffix_fairing(suffixes))
-		.attach(TrimSlash)
-		.attach(shield())
-		.attach(ExtraSecurityHeaders)
-		.attach(Clacks)
-		.attach(PronounsAcceptable)
-		.mount(
-			"/",
-			routes![
-				favicon,
-				ip,
-				work,
-				work_html,
-				how,
-				how_html,
-				bookmarks,
-				bookmarks_html,
-				now,
-				pronouns,
-				well_known_pronouns,
-				root_fursona_json,
-				well_known_fursona,
-				avatar,
-				fursona,
-				at_avg,
-				at_avghelper,
-				at_average,
-				webfinger,
-				nodeinfo,
-				root,
-				dist,
-			],
-		)
-		.register("/", catchers![bad_request, not_found])
-}
-
-async fn start_gemini_service(config: &Config) -> Result<(), AppError> {
-	gemini_service(config).await?;
+	results.into_iter().collect::<Result<Vec<()>, _>>()?;
 	Ok(())
 }
 
-async fn start_http_service(config: &Config) -> Result<(), AppError> {
-	http_service(config).ignite().await?.launch().await?;
-	Ok(())
+/// Starts the given service, and returns when it stops.
+async fn start_service(
+	service: Service,
+	config: Arc<Config>,
+	shutdown: Shutdown,
+) -> Result<(), AppError> {
+	match service {
+		#[cfg(feature = "gemini")]
+		Service::Gemini => start_gemini_service(&config, &shutdown).await,
+		#[cfg(feature = "http")]
+		Service::Http => start_http_service(&config, &shutdown).await,
+	}
 }
 
 #[derive(Debug)]
 enum AppError {
+	Config(ConfigError),
+	#[cfg(feature = "gemini")]
+	Certs(CertError),
+	#[cfg(feature = "gemini")]
 	Gemini(fluffer::AppErr),
+	#[cfg(feature = "http")]
 	Http(rocket::Error),
+	Panic(JoinError),
+	Signal(io::Error),
+}
+
+impl AppError {
+	/// The name of this error's variant, for logging.
+	const fn variant(&self) -> &'static str {
+		match self {
+			Self::Config(_) => "Config",
+			#[cfg(feature = "gemini")]
+			Self::Certs(_) => "Certs",
+			#[cfg(feature = "gemini")]
+			Self::Gemini(_) => "Gemini",
+			#[cfg(feature = "http")]
+			Self::Http(_) => "Http",
+			Self::Panic(_) => "Panic",
+			Self::Signal(_) => "Signal",
+		}
+	}
 }
 
 impl core::fmt::Display for AppError {
 	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
 		match self {
+			Self::Config(err) => write!(f, "{err}"),
+			#[cfg(feature = "gemini")]
+			Self::Certs(err) => write!(f, "{err}"),
+			#[cfg(feature = "gemini")]
 			Self::Gemini(err) => write!(f, "{err}"),
+			#[cfg(feature = "http")]
 			Self::Http(err) => write!(f, "{err}"),
+			Self::Panic(err) => write!(f, "{err}"),
+			Self::Signal(err) => write!(f, "Failed to listen for signals: {err}"),
 		}
 	}
 }
 
 impl core::error::Error for AppError {}
 
+impl From<ConfigError> for AppError {
+	fn from(value: ConfigError) -> Self {
+		Self::Config(value)
+	}
+}
+
+impl From<JoinError> for AppError {
+	fn from(value: JoinError) -> Self {
+		Self::Panic(value)
+	}
+}
+
+#[cfg(feature = "gemini")]
+impl From<CertError> for AppError {
+	fn from(value: CertError) -> Self {
+		Self::Certs(value)
+	}
+}
+
+#[cfg(feature = "gemini")]
 impl From<fluffer::AppErr> for AppError {
 	fn from(value: fluffer::AppErr) -> Self {
 		Self::Gemini(value)
 	}
 }
 
+#[cfg(feature = "http")]
 impl From<rocket::Error> for AppError {
 	fn from(value: rocket::Error) -> Self {
 		Self::Http(value)
 	}
 }
 
-#[rocket::main]
-async fn main() -> ExitCode {
-	let config = Config::default();
+async fn run() -> Result<(), AppError> {
+	let config = Arc::new(Config::load()?);
+	let services = config.services.clone();
+	let policy = RestartPolicy::from(&config.supervisor);
 
-	// Run both webservers at once, and kill the other when one dies:
-	let res = tokio::select! {
-		r = start_gemini_service(&config) => r,
-		r = start_http_service(&config) => r,
-	};
+	// Stop every service gracefully on SIGTERM or SIGINT:
+	let shutdown = Shutdown::default();
+	shutdown.trigger_on_signal().map_err(AppError::Signal)?;
+
+	// Run the selected webservers at once, restarting any that fail:
+	let services_shutdown = shutdown.clone();
+	supervise(&services, policy, &shutdown, move |service| {
+		start_service(service, Arc::clone(&config), services_shutdown.clone())
+	})
+	.await
+}
 
-	if let Err(err) = res {
+#[tokio::main]
+async fn main() -> ExitCode {
+	if let Err(err) = run().await {
 		eprintln!("{err}");
 		ExitCode::FAILURE
 	} else {
 		ExitCode::SUCCESS
 	}
 }
-
-// MARK: - Tests
-
-#[cfg(test)]
-mod tests {
-	use super::*;
-	use http::header;
-	use middleware::{X_CLACKS_OVERHEAD, X_PRONOUNS_ACCEPTABLE};
-	use rocket::{
-		http::{ContentType, Header, MediaType},
-		local::blocking::{Client, LocalResponse},
-	};
-
-	fn assert_header(res: &LocalResponse, key: &str, value: &str) {
-		let expectation = format!("{key} value should exist");
-		assert_eq!(res.headers().get_one(key).expect(&expectation), value);
-	}
-
-	fn assert_headers(res: &LocalResponse) {
-		assert!(res.headers().contains(header::CONTENT_SECURITY_POLICY));
-		assert!(
-			res.headers()
-				.get_one(header::CONTENT_SECURITY_POLICY.as_str())
-				.expect("Content-Security-Policy value should exist")
-				.contains("upgrade-insecure-requests")
-		);
-		assert_header(res, header::REFERRER_POLICY.as_str(), "no-referrer");
-		assert!(res.headers().contains(header::STRICT_TRANSPORT_SECURITY));
-		assert!(res.headers().contains(header::X_CONTENT_TYPE_OPTIONS));
-		assert!(res.headers().contains(header::X_FRAME_OPTIONS));
-		assert!(res.headers().contains(X_CLACKS_OVERHEAD));
-		assert!(res.headers().contains(X_PRONOUNS_ACCEPTABLE));
-	}
-
-	fn assert_cors(res: &LocalResponse, allowed_origin: &'static str) {
-		let actual_allowed_origin = res
-			.headers()
-			.get_one(header::ACCESS_CONTROL_ALLOW_ORIGIN.as_str());
-		assert_eq!(actual_allowed_origin, Some(allowed_origin));
-	}
-
-	fn assert_redir(res: &LocalResponse, location: &str) {
-		assert_eq!(
-			res.headers()
-				.get_one(header::LOCATION.as_str())
-				.expect("Location header should be present"),
-			location
-		);
-	}
-
-	fn assert_status(res: &LocalResponse, status_code: Status) {
-		assert_eq!(res.status(), status_code, "Status should be {status_code}");
-	}
-
-	fn assert_content_type(res: &LocalResponse, content_type: ContentType) {
-		assert_eq!(res.content_type(), Some(content_type));
-	}
-
-	fn assert_file_contents_match(response_contents: &str, expected_file_path: &str) {
-		let file_contents =
-			std::fs::read_to_string(expected_file_path).expect("File should exist and be readable");
-		assert_eq!(response_contents, file_contents);
-	}
-
-	fn assert_file_bytes_match(response_contents: &[u8], expected_file_path: &str) {
-		let file_contents =
-			std::fs::read(expected_file_path).expect("File should exist and be readable");
-		assert_eq!(response_contents, file_contents);
-	}
-
-	fn build_client() -> Client {
-		let config = Config::default();
-		Client::tracked(http_service(&config)).expect("Test client should launch")
-	}
-
-	struct Origin(&'static str);
-
-	impl From<Origin> for Header<'static> {
-		fn from(value: Origin) -> Self {
-			Self::new(header::ORIGIN.as_str(), value.0)
-		}
-	}
-
-	struct UserAgent(String);
-
-	impl From<UserAgent> for Header<'static> {
-		fn from(value: UserAgent) -> Self {
-			Self::new(header::USER_AGENT.as_str(), value.0)
-		}
-	}
-
-	fn get<'r>(client: &'r Client, path: &'static str) -> LocalResponse<'r> {
-		let req = client.get(path).header(Origin("https://average.name"));
-		req.dispatch()
-	}
-	fn get_with_origin<'r>(
-		client: &'r Client,
-		path: &'static str,
-		origin: Option<&'static str>,
-	) -> LocalResponse<'r> {
-		let mut req = client.get(path);
-		if let Some(origin) = origin {
-			req = req.header(Origin(origin));
-		}
-		req.dispatch()
-	}
-	fn get_with_user_agent<'r>(
-		client: &'r Client,
-		path: &'r str,
-		user_agent: &'r str,
-	) -> LocalResponse<'r> {
-		let req = client.get(path).header(UserAgent(user_agent.to_owned()));
-		req.dispatch()
-	}
-	fn head<'r>(client: &'r Client, path: &'r str) -> LocalResponse<'r> {
-		let req = client.head(path);
-		req.dispatch()
-	}
-
-	fn response_bytes(res: LocalResponse<'_>) -> Vec<u8> {
-		let bytes = res.into_bytes().expect("Body should download safely");
-		let hundred_mb = 100_000_000;
-		assert!(bytes.len() < hundred_mb, "Body should be under 100 MB");
-		bytes
-	}
-
-	fn response_body(res: LocalResponse) -> String {
-		let body_bytes = response_bytes(res);
-		String::from_utf8(body_bytes).expect("Body should be valid UTF-8")
-	}
-
-	#[test]
-	fn answers_favicon() {
-		let client = build_client();
-		let res = get(&client, "/favicon.ico");
-		assert_status(&res, Status::NotFound);
-		assert_headers(&res);
-		let body = response_body(res);
-		assert!(body.is_empty());
-		client.terminate();
-	}
-
-	#[test]
-	fn answers_pronouns() {
-		let client = build_client();
-		let res = get(&client, "/.well-known/pronouns");
-		assert_status(&res, Status::Ok);
-		assert_headers(&res);
-		assert_cors(&res, "*");
-		let text = response_body(res);
-		assert_eq!(text, "she/her");
-		client.terminate();
-	}
-
-	#[test]
-	fn serves_webfinger() {
-		let client = build_client();
-		{
-			let res = get(
-				&client,
-				"/.well-known/webfinger?resource=acct:average@average.name",
-			);
-			assert_status(&res, Status::Ok);
-			assert_headers(&res);
-			assert_cors(&res, "*");
-			let content_type = res.content_type().expect("Content-Type should be sent");
-			let mime = "application/jrd+json; charset=UTF-8"
-				.parse::<MediaType>()
-				.expect("MIME string should be valid");
-			assert_eq!(content_type.0, mime);
-
-			let body = res.into_string().expect("Result should be a string");
-			let result: WebFinger =
-				serde_json::from_str(&body).expect("Result should be a JSON string");
-			assert_eq!(result.subject, "acct:avghelper@gts.average.name");
-			assert_eq!(result.links.len(), 2);
-		}
-		client.terminate();
-	}
-
-	#[test]
-	fn serves_webfinger_with_rel() {
-		let client = build_client();
-		{
-			let res = get(
-				&client,
-				"/.well-known/webfinger?resource=acct:average@average.name&rel=self",
-			);
-			assert_status(&res, Status::Ok);
-			assert_headers(&res);
-			assert_cors(&res, "*");
-			let content_type = res.content_type().expect("Content-Type should be sent");
-			let mime = "application/jrd+json; charset=UTF-8"
-				.parse::<MediaType>()
-				.expect("MIME string should be valid");
-			assert_eq!(content_type.0, mime);
-
-			let body = res.into_string().expect("Result should be a string");
-			let result: WebFinger =
-				serde_json::from_str(&body).expect("Result should be a JSON string");
-			assert_eq!(result.subject, "acct:avghelper@gts.average.name");
-			assert_eq!(result.links.len(), 1);
-		}
-		client.terminate();
-	}
-
-	#[test]
-	fn webfinger_fails_without_resource_param() {
-		let client = build_client();
-		{
-			let res = get(&client, "/.well-known/webfinger");
-			assert_status(&res, Status::BadRequest);
-			assert_headers(&res);
-		}
-		client.terminate();
-	}
-
-	#[test]
-	fn serves_nodeinfo() {
-		let client = build_client();
-		{
-			let res = get(&client, "/.well-known/nodeinfo");
-			assert_status(&res, Status::NotFound);
-			assert_headers(&res);
-
-			let res2 =
-				get_with_user_agent(&client, "/.well-known/nodeinfo", "GitHub-NodeinfoQuery");
-			assert_status(&res2, Status::Found);
-			assert_headers(&res2);
-		}
-		client.terminate();
-	}
-
-	#[test]
-	fn serves_static_files() {
-		let file_paths = vec![
-			("/robots.txt", ContentType::Plain, "https://average.name"),
-			("/sitemap.html", ContentType::HTML, "https://average.name"),
-			(
-				"/sitemap-index.xml",
-				ContentType::XML,
-				"https://average.name",
-			),
-			("/sitemap-0.xml", ContentType::XML, "https://average.name"),
-			("/.well-known/fursona.json", ContentType::JSON, "*"),
-			("/index.html", ContentType::HTML, "https://average.name"),
-			("/contact.html", ContentType::HTML, "https://average.name"),
-		];
-
-		let client = build_client();
-		for (path, mime, origin) in file_paths {
-			let res = get(&client, path);
-			assert_status(&res, Status::Ok);
-			assert_headers(&res);
-			assert_cors(&res, origin);
-			assert_content_type(&res, mime.clone());
-			let response_contents = response_body(res);
-			let expected_file_path = format!("./dist{path}");
-			assert_file_contents_match(&response_contents, &expected_file_path);
-		}
-
-		client.terminate();
-	}
-
-	#[test]
-	fn omits_cors_header_for_unknown_origin() {
-		let file_paths = vec![
-			("/robots.txt", ContentType::Plain),
-			("/sitemap.html", ContentType::HTML),
-			("/sitemap-index.xml", ContentType::XML),
-			("/sitemap-0.xml", ContentType::XML),
-			("/index.html", ContentType::HTML),
-			("/contact.html", ContentType::HTML),
-		];
-
-		let client = build_client();
-		for (path, mime) in file_paths {
-			let res = get_with_origin(&client, path, None);
-			assert_status(&res, Status::Ok);
-			assert_headers(&res);
-			assert_content_type(&res, mime.clone());
-			let has_allowed_origin = res
-				.headers()
-				.contains(header::ACCESS_CONTROL_ALLOW_ORIGIN.as_str());
-			assert!(!has_allowed_origin);
-			let response_contents = response_body(res);
-			let expected_file_path = format!("./dist{path}");
-			assert_file_contents_match(&response_contents, &expected_file_path);
-		}
-		client.terminate();
-	}
-
-	#[test]
-	fn serves_static_files_without_extension() {
-		let file_paths = vec![
-			("/sitemap", "sitemap.html"),
-			("/", "index.html"),
-			("/contact", "contact.html"),
-		];
-
-		let client = build_client();
-		for (path, file_name) in file_paths {
-			let res = get(&client, path);
-			assert_status(&res, Status::Ok);
-			assert_headers(&res);
-			assert_content_type(&res, ContentType::HTML);
-			let response_contents = response_body(res);
-			let expected_file_path = format!("./dist/{file_name}");
-			assert_file_contents_match(&response_contents, &expected_file_path);
-		}
-
-		client.terminate();
-	}
-
-	#[test]
-	fn serves_404_for_unknown_page() {
-		let file_paths = vec![
-			"/foo_bar_nothing_to_see_here", //
-			"/contact/no-thanks",
-		];
-
-		let client = build_client();
-		for path in file_paths {
-			let res = get(&client, path);
-			assert_status(&res, Status::NotFound);
-			assert_headers(&res);
-			assert_content_type(&res, ContentType::HTML);
-			let response_contents = response_body(res);
-			assert_file_contents_match(&response_contents, "./dist/404.html");
-		}
-
-		client.terminate();
-	}
-
-	fn assert_would_serve_file(path: &'static str, content_type: ContentType) {
-		let client = build_client();
-		let res = head(&client, path);
-		assert_status(&res, Status::Ok);
-		assert_headers(&res);
-		assert_content_type(&res, content_type);
-		// TODO: Should we send Content-Length here?
-
-		// CORS should permit any origin
-		assert_cors(&res, "*");
-
-		let response_contents = response_bytes(res);
-		assert_eq!(response_contents.len(), 0);
-		client.terminate();
-	}
-
-	fn assert_serves_file(path: &'static str, content_type: ContentType) {
-		let client = build_client();
-		let res = get(&client, path);
-		assert_status(&res, Status::Ok);
-		assert_headers(&res);
-		assert_content_type(&res, content_type);
-		// TODO: Should we send Content-Length here?
-
-		// CORS should permit any origin
-		assert_cors(&res, "*");
-
-		let response_contents = response_bytes(res);
-		assert_file_bytes_match(&response_contents, &format!("./dist{path}"));
-
-		client.terminate();
-	}
-
-	#[test]
-	fn serves_fursona_ref() {
-		let path = "/images/refs/AverageHelper-avatar.png";
-		assert_would_serve_file(path, ContentType::PNG);
-		assert_serves_file("/images/refs/AverageHelper-avatar.png", ContentType::PNG);
-	}
-
-	#[test]
-	fn serves_fursona_json() {
-		assert_would_serve_file("/.well-known/fursona.json", ContentType::JSON);
-		assert_serves_file("/.well-known/fursona.json", ContentType::JSON);
-	}
-
-	// TODO: Test that all internal links go where they're supposed to go
-
-	#[test]
-	fn redirects() {
-		let redirects = vec![
-			("/ip", "https://ip.average.name"),
-			("/how", "/ways"),
-			("/how.html", "/ways.html"),
-			("/bookmarks", "/links"),
-			("/bookmarks.html", "/links.html"),
-			(
-				"/now",
-				"https://git.average.name/AverageHelper?tab=repositories",
-			),
-			("/pronouns", "/.well-known/pronouns"),
-			("/fursona.json", "/.well-known/fursona.json"),
-			("/.well-known/fursona", "/.well-known/fursona.json"),
-			("/@avg", "https://gts.average.name/@avghelper"),
-			("/@avghelper", "https://gts.average.name/@avghelper"),
-			("/@average", "https://gts.average.name/@avghelper"),
-		];
-
-		let client = build_client();
-		for (from, to) in redirects {
-			let res = get(&client, from);
-			assert_status(&res, Status::Found);
-			assert_headers(&res);
-			assert_redir(&res, to);
-		}
-
-		client.terminate();
-	}
-}
@@ -1,14 +1,62 @@
+mod cache_control;
+pub use cache_control::*;
+
 mod clacks;
 pub use clacks::*;
 
+mod conditional;
+pub use conditional::*;
+
 mod cors;
 pub use cors::*;
 
+mod negotiated_error;
+pub use negotiated_error::*;
+
+mod precompressed;
+pub use precompressed::*;
+
 mod pronouns_acceptable;
 pub use pronouns_acceptable::*;
 
+mod quality;
+pub use quality::*;
+
+mod ranges;
+pub use ranges::*;
+
 mod security_headers;
 pub use security_headers::*;
 
 mod trim_slash;
 pub use trim_slash::*;
+
+mod vary;
+pub use vary::*;
+
+// MARK: - Test helpers
+
+#[cfg(test)]
+mod testing {
+	use rocket::http::Header;
+	use rocket::local::blocking::{Client, LocalResponse};
+	use rocket::{Catcher, Route};
+
+	/// A client for a Rocket that serves the given routes and catchers at the root.
+	pub fn client(routes: Vec<Route>, catchers: Vec<Catcher>) -> Client {
+		let rocket = rocket::build().mount("/", routes).register("/", catchers);
+		Client::tracked(rocket).expect("Test client should launch")
+	}
+
+	/// Requests the root with the given headers.
+	pub fn get_with<'c>(
+		client: &'c Client,
+		headers: &[(&'static str, &'static str)],
+	) -> LocalResponse<'c> {
+		let mut req = client.get("/");
+		for &(key, value) in headers {
+			req = req.header(Header::new(key, value));
+		}
+		req.dispatch()
+	}
+}
@@ -0,0 +1,192 @@
+use http::header;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{ContentType, Status};
+use rocket::{Request, Response};
+
+/// How long clients may cache a response.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CachePolicy {
+	/// Content that never changes at its path, like fingerprinted assets.
+	Immutable,
+
+	/// Content that changes now and then, like well-known documents.
+	Moderate,
+
+	/// Pages that should be revalidated soon, so that updates show up quickly.
+	Short,
+
+	/// Responses that should not be cached at all, like errors.
+	NoStore,
+}
+
+impl CachePolicy {
+	/// The `Cache-Control` value for this policy.
+	pub const fn header_value(self) -> &'static str {
+		match self {
+			Self::Immutable => "public, max-age=31536000, immutable",
+			Self::Moderate => "public, max-age=86400",
+			Self::Short => "public, max-age=300, must-revalidate",
+			Self::NoStore => "no-store",
+		}
+	}
+}
+
+/// A class of successful responses.
+enum ResponseClass {
+	/// Responses at paths that start with the given prefix.
+	PathPrefix(&'static str),
+
+	/// HTML pages.
+	Html,
+
+	/// Any response.
+	Any,
+}
+
+impl ResponseClass {
+	fn contains(&self, path: &str, content_type: Option<&ContentType>) -> bool {
+		match self {
+			Self::PathPrefix(prefix) => path.starts_with(prefix),
+			Self::Html => content_type.is_some_and(|content_type| content_type.is_html()),
+			Self::Any => true,
+		}
+	}
+}
+
+/// The policy for each class of successful responses. The first matching class wins.
+static POLICIES: &[(ResponseClass, CachePolicy)] = &[
+	// Astro puts a hash of each asset's content in its name
+	(
+		ResponseClass::PathPrefix("/_astro/"),
+		CachePolicy::Immutable,
+	),
+	(
+		ResponseClass::PathPrefix("/.well-known/"),
+		CachePolicy::Moderate,
+	),
+	(ResponseClass::Html, CachePolicy::Short),
+	(ResponseClass::Any, CachePolicy::Moderate),
+];
+
+/// Returns the cache policy for a response with the given status and content type
+/// at the given path, if the response should have one.
+fn policy_for(
+	status: Status,
+	path: &str,
+	content_type: Option<&ContentType>,
+) -> Option<CachePolicy> {
+	// A 304 has no body, so it may not say what it's for. Go by its path instead,
+	// so that it doesn't replace the cached response's policy with another
+	let path_content_type;
+	let content_type = match content_type {
+		None if status == Status::NotModified => {
+			path_content_type = content_type_of_path(path);
+			path_content_type.as_ref()
+		}
+		content_type => content_type,
+	};
+
+	match status.code {
+		200..=299 | 304 => POLICIES
+			.iter()
+			.find(|(class, _)| class.contains(path, content_type))
+			.map(|(_, policy)| *policy),
+		400..=599 => Some(CachePolicy::NoStore),
+		_ => None, // Redirects and such keep the default
+	}
+}
+
+/// The type of content at the given path, going by its extension. Paths without
+/// one are pages.
+fn content_type_of_path(path: &str) -> Option<ContentType> {
+	let file_name = path.rsplit('/').next().unwrap_or_default();
+	match file_name.rsplit_once('.') {
+		Some((_, extension)) => ContentType::from_extension(extension),
+		None => Some(ContentType::HTML),
+	}
+}
+
+/// A Rocket [Fairing](https://rocket.rs/guide/v0.5/fairings/#fairings) that sets
+/// `Cache-Control` on each response that doesn't already have one, according
+/// to the class of the response.
+pub struct CacheControl;
+
+#[rocket::async_trait]
+impl Fairing for CacheControl {
+	fn info(&self) -> Info {
+		Info {
+			name: "Cache-Control",
+			kind: Kind::Response,
+		}
+	}
+
+	async fn on_response<'r>(&self, req: &'r Request<'_>, response: &mut Response<'r>) {
+		if response.headers().contains(header::CACHE_CONTROL.as_str()) {
+			return;
+		}
+
+		let content_type = response.content_type();
+		let path = req.uri().path();
+		if let Some(policy) = policy_for(response.status(), path.as_str(), content_type.as_ref()) {
+			response.set_raw_header(header::CACHE_CONTROL.as_str(), policy.header_value());
+		}
+	}
+}
+
+// MARK: - Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use test_case::test_case;
+
+	#[test_case("/_astro/index.B2xY7.css", Some(&ContentType::CSS) ; "fingerprinted stylesheet")]
+	#[test_case("/_astro/font.woff2", None ; "fingerprinted font")]
+	fn caches_fingerprinted_assets_forever(path: &str, content_type: Option<&ContentType>) {
+		let policy = policy_for(Status::Ok, path, content_type);
+		assert_eq!(policy, Some(CachePolicy::Immutable));
+	}
+
+	#[test_case("/" ; "root")]
+	#[test_case("/contact" ; "page without extension")]
+	#[test_case("/contact.html" ; "page with extension")]
+	fn revalidates_pages_soon(path: &str) {
+		let policy = policy_for(Status::Ok, path, Some(&ContentType::HTML));
+		assert_eq!(policy, Some(CachePolicy::Short));
+	}
+
+	#[test_case("/.well-known/fursona.json", Some(&ContentType::JSON) ; "fursona")]
+	#[test_case("/.well-known/pronouns", Some(&ContentType::Plain) ; "pronouns")]
+	#[test_case("/robots.txt", Some(&ContentType::Plain) ; "other file")]
+	#[test_case("/images/refs/AverageHelper-avatar.png", Some(&ContentType::PNG) ; "image")]
+	fn caches_other_files_for_a_while(path: &str, content_type: Option<&ContentType>) {
+		let policy = policy_for(Status::Ok, path, content_type);
+		assert_eq!(policy, Some(CachePolicy::Moderate));
+	}
+
+	#[test_case("/_astro/index.css", CachePolicy::Immutable ; "fingerprinted asset")]
+	#[test_case("/", CachePolicy::Short ; "root")]
+	#[test_case("/contact", CachePolicy::Short ; "page without extension")]
+	#[test_case("/contact.html", CachePolicy::Short ; "page with extension")]
+	#[test_case("/.well-known/pronouns", CachePolicy::Moderate ; "well-known document")]
+	#[test_case("/robots.txt", CachePolicy::Moderate ; "other file")]
+	fn keeps_policy_when_not_modified(path: &str, expected: CachePolicy) {
+		let policy = policy_for(Status::NotModified, path, None);
+		assert_eq!(policy, Some(expected));
+	}
+
+	#[test_case(Status::NotFound, "/_astro/missing.css" ; "missing asset")]
+	#[test_case(Status::NotFound, "/nothing" ; "missing page")]
+	#[test_case(Status::BadRequest, "/.well-known/webfinger" ; "bad request")]
+	#[test_case(Status::InternalServerError, "/" ; "server error")]
+	fn never_caches_errors(status: Status, path: &str) {
+		let policy = policy_for(status, path, Some(&ContentType::HTML));
+		assert_eq!(policy, Some(CachePolicy::NoStore));
+	}
+
+	#[test_case(Status::Found ; "temporary redirect")]
+	#[test_case(Status::MovedPermanently ; "permanent redirect")]
+	fn leaves_redirects_alone(status: Status) {
+		assert_eq!(policy_for(status, "/how", None), None);
+	}
+}
@@ -1,3 +1,4 @@
+use crate::config::Profile;
 use crate::utils::random_name;
 use rocket::fairing::{Fairing, Info, Kind};
 use rocket::{Request, Response};
@@ -5,7 +6,8 @@ use rocket::{Request, Response};
 pub static X_CLACKS_OVERHEAD: &str = "X-Clacks-Overhead";
 
 /// A Rocket [Fairing](https://rocket.rs/guide/v0.5/fairings/#fairings) that sets
-/// [`X-Clacks-Overhead`](https://xclacksoverhead.org/home/about) on every response.
+/// [`X-Clacks-Overhead`](https://xclacksoverhead.org/home/about) on every response,
+/// naming someone from the managed [`Profile`]'s memorials.
 pub struct Clacks;
 
 #[rocket::async_trait]
@@ -17,9 +19,14 @@ impl Fairing for Clacks {
 		}
 	}
 
-	async fn on_response<'r>(&self, _: &'r Request<'_>, response: &mut Response<'r>) {
+	async fn on_response<'r>(&self, req: &'r Request<'_>, response: &mut Response<'r>) {
+		let Some(profile) = req.rocket().state::<Profile>() else {
+			return;
+		};
 		let mut rng = rand::rng();
-		let name = random_name(&mut rng);
+		let Some(name) = random_name(&profile.memorials, &mut rng) else {
+			return;
+		};
 
 		response.set_raw_header(X_CLACKS_OVERHEAD, format!("GNU {name}"));
 	}
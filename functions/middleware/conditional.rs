@@ -0,0 +1,140 @@
+use http::header;
+use rocket::Request;
+use rocket::http::{Header, Status};
+use rocket::response::{Responder, Response};
+
+/// What a client needs to revalidate a cached response: a strong entity tag,
+/// and when the content last changed.
+#[derive(Clone, Copy)]
+pub struct Validators {
+	/// The content's hash, in quotes.
+	etag: &'static str,
+
+	/// When the content last changed, as an HTTP date.
+	last_modified: &'static str,
+}
+
+impl Validators {
+	pub const fn new(etag: &'static str, last_modified: &'static str) -> Self {
+		Self {
+			etag,
+			last_modified,
+		}
+	}
+
+	/// Whether the given `If-Range` condition names this exact content, by its
+	/// entity tag (compared strongly) or its last-modified date.
+	pub(super) fn matches_exactly(self, condition: &str) -> bool {
+		if condition.starts_with('"') || condition.starts_with("W/") {
+			return condition == self.etag;
+		}
+		let date = httpdate::parse_http_date(condition);
+		date.is_ok() && date.ok() == httpdate::parse_http_date(self.last_modified).ok()
+	}
+
+	/// Whether the client's cached copy, as described by the request's
+	/// `If-None-Match` or `If-Modified-Since` header, is still fresh.
+	fn is_fresh(self, request: &Request<'_>) -> bool {
+		let headers = request.headers();
+
+		// If-None-Match takes precedence, and compares weakly (RFC 9110 § 13.1.2)
+		if headers.contains(header::IF_NONE_MATCH.as_str()) {
+			return headers
+				.get(header::IF_NONE_MATCH.as_str())
+				.flat_map(|value| value.split(','))
+				.map(str::trim)
+				.any(|tag| tag == "*" || tag.trim_start_matches("W/") == self.etag);
+		}
+
+		let Ok(last_modified) = httpdate::parse_http_date(self.last_modified) else {
+			return false;
+		};
+		headers
+			.get_one(header::IF_MODIFIED_SINCE.as_str())
+			.and_then(|since| httpdate::parse_http_date(since).ok())
+			.is_some_and(|since| last_modified <= since)
+	}
+}
+
+/// A responder that wraps the given responder and answers with `304 Not Modified`
+/// if the client's cached copy matches the given validators. Otherwise, the response
+/// gets `ETag` and `Last-Modified` headers so the client can revalidate it later.
+pub struct ConditionalResponse<T: for<'r> Responder<'r, 'static>>(pub T, pub Option<Validators>);
+impl<'r, T: for<'s> Responder<'s, 'static>> Responder<'r, 'static> for ConditionalResponse<T> {
+	fn respond_to(self, request: &'r Request<'_>) -> rocket::response::Result<'static> {
+		let Some(validators) = self.1 else {
+			return self.0.respond_to(request);
+		};
+
+		let mut res = if validators.is_fresh(request) {
+			Response::build().status(Status::NotModified).finalize()
+		} else {
+			self.0.respond_to(request)?
+		};
+		res.set_header(Header::new(header::ETAG.as_str(), validators.etag));
+		res.set_header(Header::new(
+			header::LAST_MODIFIED.as_str(),
+			validators.last_modified,
+		));
+		Ok(res)
+	}
+}
+
+// MARK: - Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::middleware::testing::{client, get_with};
+	use rocket::get;
+	use rocket::{catchers, routes};
+	use test_case::test_case;
+
+	const VALIDATORS: Validators = Validators::new("\"abc\"", "Tue, 14 Nov 2023 22:13:20 GMT");
+
+	#[get("/")]
+	fn page() -> ConditionalResponse<&'static str> {
+		ConditionalResponse("Page", Some(VALIDATORS))
+	}
+
+	#[test]
+	fn sends_validators() {
+		let client = client(routes![page], catchers![]);
+		let res = get_with(&client, &[]);
+		assert_eq!(res.status(), Status::Ok);
+		assert_eq!(res.headers().get_one("ETag"), Some("\"abc\""));
+		assert_eq!(
+			res.headers().get_one("Last-Modified"),
+			Some("Tue, 14 Nov 2023 22:13:20 GMT")
+		);
+		assert_eq!(res.into_string().as_deref(), Some("Page"));
+	}
+
+	#[test_case("If-None-Match", "\"abc\"" ; "matching tag")]
+	#[test_case("If-None-Match", "\"xyz\", W/\"abc\"" ; "weak tag in list")]
+	#[test_case("If-None-Match", "*" ; "any tag")]
+	#[test_case("If-Modified-Since", "Tue, 14 Nov 2023 22:13:20 GMT" ; "same date")]
+	#[test_case("If-Modified-Since", "Wed, 15 Nov 2023 00:00:00 GMT" ; "later date")]
+	fn answers_not_modified(key: &'static str, value: &'static str) {
+		let client = client(routes![page], catchers![]);
+		let res = get_with(&client, &[(key, value)]);
+		assert_eq!(res.status(), Status::NotModified);
+		assert_eq!(res.headers().get_one("ETag"), Some("\"abc\""));
+		let body = res.into_bytes().unwrap_or_default();
+		assert!(body.is_empty(), "Body should be empty");
+	}
+
+	#[test_case(&[("If-None-Match", "\"xyz\"")] ; "other tag")]
+	#[test_case(&[("If-Modified-Since", "Mon, 13 Nov 2023 00:00:00 GMT")] ; "earlier date")]
+	#[test_case(&[("If-Modified-Since", "yesterday")] ; "malformed date")]
+	#[test_case(&[
+		("If-None-Match", "\"xyz\""),
+		("If-Modified-Since", "Wed, 15 Nov 2023 00:00:00 GMT"),
+	] ; "tag takes precedence")]
+	fn answers_modified(headers: &[(&'static str, &'static str)]) {
+		let client = client(routes![page], catchers![]);
+		let res = get_with(&client, headers);
+		assert_eq!(res.status(), Status::Ok);
+		assert_eq!(res.into_string().as_deref(), Some("Page"));
+	}
+}
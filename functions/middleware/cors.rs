@@ -1,9 +1,6 @@
+use crate::config::Profile;
 use http::header;
-use rocket::{
-	http::{Header, uri::Absolute},
-	response::Responder,
-	uri,
-};
+use rocket::{http::Header, response::Responder};
 
 /// A response header that allows all origins.
 pub struct CorsAllowAll;
@@ -27,35 +24,31 @@ impl<'r, T: for<'s> Responder<'s, 'static>> Responder<'r, 'static> for CorsAllow
 	}
 }
 
-static PROD_URI: Absolute<'static> = uri!("https://average.name");
-
 /// A response header that allows only our deployment origin.
-struct CorsOnlyProd;
+struct CorsOnlyProd(String);
 
 impl From<CorsOnlyProd> for Header<'static> {
-	fn from(_: CorsOnlyProd) -> Self {
-		Self::new(
-			header::ACCESS_CONTROL_ALLOW_ORIGIN.as_str(),
-			PROD_URI.to_string(),
-		)
+	fn from(value: CorsOnlyProd) -> Self {
+		Self::new(header::ACCESS_CONTROL_ALLOW_ORIGIN.as_str(), value.0)
 	}
 }
 
 /// A responder that wraps the given responder and applies CORS headers
-/// that allow only our deployment origin. If the request's `Origin` header
-/// is not present or does not match our expected origin, then no new CORS
-/// header is set.
+/// that allow only our deployment origin, as given by the managed [`Profile`].
+/// If the request's `Origin` header is not present or does not match our
+/// expected origin, then no new CORS header is set.
 ///
-/// Effectively sets `access-control-allow-origin: https://average.name` on applicable responses.
+/// Effectively sets `access-control-allow-origin` to [`Profile::origin`] on applicable responses.
 pub struct CorsOnlyProdResponse<T: for<'r> Responder<'r, 'static>>(pub T);
 impl<'r, T: for<'s> Responder<'s, 'static>> Responder<'r, 'static> for CorsOnlyProdResponse<T> {
 	fn respond_to(self, request: &'r rocket::Request<'_>) -> rocket::response::Result<'static> {
 		let mut res = self.0.respond_to(request)?;
 
 		if let Some(user_provided_origin) = request.headers().get_one(header::ORIGIN.as_str())
-			&& PROD_URI == user_provided_origin
+			&& let Some(profile) = request.rocket().state::<Profile>()
+			&& profile.origin == user_provided_origin
 		{
-			res.set_header(CorsOnlyProd);
+			res.set_header(CorsOnlyProd(profile.origin.clone()));
 		}
 
 		Ok(res)
@@ -0,0 +1,227 @@
+use super::{accepted_media_types, media_type_quality};
+use crate::utils::with_element_contents;
+use http::header;
+use rocket::Request;
+use rocket::http::{ContentType, Status};
+use rocket::response::{Responder, Response};
+use std::borrow::Cow;
+use std::io::Cursor;
+
+/// A format that an error response can take.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ErrorFormat {
+	/// The status line, like `404 Not Found`.
+	Plain,
+
+	/// A page in the style of the rest of the site.
+	Html,
+
+	/// An `application/problem+json` document, as described in RFC 9457.
+	Problem,
+}
+
+impl ErrorFormat {
+	/// Every format, in the order that we prefer them when the client has no preference.
+	const ALL: [Self; 3] = [Self::Plain, Self::Html, Self::Problem];
+
+	/// The media types that the client may ask for to get this format.
+	const fn media_types(self) -> &'static [&'static str] {
+		match self {
+			Self::Plain => &["text/plain"],
+			Self::Html => &["text/html"],
+			Self::Problem => &["application/problem+json", "application/json"],
+		}
+	}
+}
+
+/// A responder that describes the given error status in whichever of plain text,
+/// HTML, or `application/problem+json` the request's `Accept` header prefers.
+/// Clients that accept anything, like `curl`, get plain text.
+///
+/// The HTML page is the given 404 page, with its title and first heading changed to
+/// name the status, so that it keeps the site's style.
+///
+/// Sets `vary: accept` on the response.
+pub struct NegotiatedError {
+	pub status: Status,
+	pub not_found_page: Option<Cow<'static, [u8]>>,
+}
+
+impl<'r> Responder<'r, 'static> for NegotiatedError {
+	fn respond_to(self, request: &'r Request<'_>) -> rocket::response::Result<'static> {
+		let accepted = accepted_media_types(request);
+		let status = self.status;
+		let (content_type, body) = match preferred_format(&accepted) {
+			ErrorFormat::Plain => (
+				ContentType::Plain,
+				Cow::Owned(status.to_string().into_bytes()),
+			),
+			ErrorFormat::Html => (ContentType::HTML, self.html_page()),
+			ErrorFormat::Problem => {
+				let problem = serde_json::json!({
+					"type": "about:blank",
+					"title": status.reason_lossy(),
+					"status": status.code,
+				});
+				let body = serde_json::to_vec(&problem).unwrap_or_default();
+				(
+					ContentType::new("application", "problem+json"),
+					Cow::Owned(body),
+				)
+			}
+		};
+
+		Response::build()
+			.status(status)
+			.header(content_type)
+			.raw_header(header::VARY.as_str(), "Accept")
+			.sized_body(body.len(), Cursor::new(body))
+			.ok()
+	}
+}
+
+impl NegotiatedError {
+	/// The HTML page for this error.
+	fn html_page(self) -> Cow<'static, [u8]> {
+		let status = self.status;
+		match self.not_found_page {
+			Some(page) if status == Status::NotFound => page,
+			Some(page) => {
+				let heading = format!("Error {}: {}", status.code, status.reason_lossy());
+				let page = with_element_contents(&String::from_utf8_lossy(&page), "h1", &heading)
+					.map_or_else(
+						|| plain_html_page(status),
+						|page| with_element_contents(&page, "title", &heading).unwrap_or(page),
+					);
+				Cow::Owned(page.into_bytes())
+			}
+			None => Cow::Owned(plain_html_page(status).into_bytes()),
+		}
+	}
+}
+
+/// A bare HTML page for the given status, for when there's no page to base it on.
+fn plain_html_page(status: Status) -> String {
+	format!(
+		"<!doctype html>\n<html lang=\"en\">\n<head><meta charset=\"utf-8\" /><title>{status}</title></head>\n<body><h1>{status}</h1><p><a href=\"/\">Return home</a></p></body>\n</html>\n"
+	)
+}
+
+/// The format that the given `Accept` value rates highest. Ties go to the format
+/// we prefer.
+fn preferred_format(accepted: &str) -> ErrorFormat {
+	let mut preferred = (0, ErrorFormat::Plain);
+	for format in ErrorFormat::ALL {
+		let quality = format
+			.media_types()
+			.iter()
+			.map(|media_type| media_type_quality(accepted, media_type))
+			.max()
+			.unwrap_or_default();
+		if quality > preferred.0 {
+			preferred = (quality, format);
+		}
+	}
+	preferred.1
+}
+
+// MARK: - Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::middleware::testing::client;
+	use rocket::http::Header;
+	use rocket::{catch, catchers, get, routes};
+	use test_case::test_case;
+
+	const PAGE: &str = "<html><head><title>Not Found</title></head><body><h1 id=\"error\">Error 404: Page Not Found</h1><a href=\"/\">Return home</a></body></html>";
+
+	#[get("/<code>")]
+	fn fail(code: u16) -> Status {
+		Status::new(code)
+	}
+
+	#[catch(default)]
+	fn error(status: Status, _request: &Request<'_>) -> NegotiatedError {
+		NegotiatedError {
+			status,
+			not_found_page: Some(Cow::Borrowed(PAGE.as_bytes())),
+		}
+	}
+
+	#[test_case("*/*", ErrorFormat::Plain ; "anything")]
+	#[test_case("text/*", ErrorFormat::Plain ; "any text")]
+	#[test_case("text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8", ErrorFormat::Html ; "browser")]
+	#[test_case("application/json", ErrorFormat::Problem ; "json")]
+	#[test_case("application/problem+json, text/plain;q=0.5", ErrorFormat::Problem ; "problem")]
+	#[test_case("text/*;q=0.2, TEXT/HTML", ErrorFormat::Html ; "most specific range wins")]
+	#[test_case("*/*, text/plain;q=0", ErrorFormat::Html ; "refused format")]
+	#[test_case("image/png", ErrorFormat::Plain ; "nothing acceptable")]
+	fn prefers_format(accepted: &str, expected: ErrorFormat) {
+		assert_eq!(preferred_format(accepted), expected);
+	}
+
+	#[test_case(405 ; "method not allowed")]
+	#[test_case(413 ; "content too large")]
+	#[test_case(422 ; "unprocessable content")]
+	#[test_case(429 ; "too many requests")]
+	#[test_case(500 ; "internal server error")]
+	#[test_case(503 ; "service unavailable")]
+	fn describes_error(code: u16) {
+		let client = client(routes![fail], catchers![error]);
+		let status = Status::new(code);
+		let uri = format!("/{code}");
+
+		let res = client.get(uri.clone()).dispatch();
+		assert_eq!(res.status(), status);
+		assert_eq!(res.content_type(), Some(ContentType::Plain));
+		assert_eq!(res.headers().get_one("Vary"), Some("Accept"));
+		assert_eq!(res.into_string(), Some(status.to_string()));
+
+		let res = client
+			.get(uri.clone())
+			.header(Header::new("Accept", "text/html"))
+			.dispatch();
+		assert_eq!(res.status(), status);
+		assert_eq!(res.content_type(), Some(ContentType::HTML));
+		let body = res.into_string().expect("Body should be text");
+		let heading = format!("Error {code}: {}", status.reason_lossy());
+		assert!(body.contains(&format!(">{heading}</h1>")));
+		assert!(body.contains(&format!("<title>{heading}</title>")));
+		assert!(!body.contains("Not Found"));
+		assert!(body.contains("Return home"));
+
+		let res = client
+			.get(uri)
+			.header(Header::new("Accept", "application/json"))
+			.dispatch();
+		assert_eq!(res.status(), status);
+		assert_eq!(
+			res.content_type(),
+			Some(ContentType::new("application", "problem+json"))
+		);
+		let problem: serde_json::Value =
+			serde_json::from_str(&res.into_string().expect("Body should be text"))
+				.expect("Body should be JSON");
+		assert_eq!(
+			problem,
+			serde_json::json!({
+				"type": "about:blank",
+				"title": status.reason_lossy(),
+				"status": code,
+			})
+		);
+	}
+
+	#[test]
+	fn keeps_not_found_page() {
+		let client = client(routes![fail], catchers![error]);
+		let res = client
+			.get("/404")
+			.header(Header::new("Accept", "text/html"))
+			.dispatch();
+		assert_eq!(res.status(), Status::NotFound);
+		assert_eq!(res.into_string().as_deref(), Some(PAGE));
+	}
+}
@@ -0,0 +1,247 @@
+use super::{ByteRanges, ConditionalResponse, Validators, add_vary, item_quality};
+use http::header;
+use rocket::Request;
+use rocket::http::{ContentType, Header};
+use rocket::response::Responder;
+use std::borrow::Cow;
+
+/// A content coding that files are precompressed with at build time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContentCoding {
+	Brotli,
+	Gzip,
+	Zstd,
+}
+
+impl ContentCoding {
+	/// The coding's name in `Accept-Encoding` and `Content-Encoding`.
+	const fn token(self) -> &'static str {
+		match self {
+			Self::Brotli => "br",
+			Self::Gzip => "gzip",
+			Self::Zstd => "zstd",
+		}
+	}
+
+	/// Whether the given `Accept-Encoding` token names this coding.
+	fn is_named(self, token: &str) -> bool {
+		token.eq_ignore_ascii_case(self.token())
+			|| (self == Self::Gzip && token.eq_ignore_ascii_case("x-gzip"))
+	}
+}
+
+/// A precompressed copy of a file, which is always smaller than the original.
+#[derive(Clone, Copy)]
+pub struct Variant {
+	coding: ContentCoding,
+	validators: Validators,
+	bytes: &'static [u8],
+}
+
+impl Variant {
+	pub const fn new(coding: ContentCoding, validators: Validators, bytes: &'static [u8]) -> Self {
+		Self {
+			coding,
+			validators,
+			bytes,
+		}
+	}
+}
+
+/// A responder that serves the given file, or the smallest of its precompressed
+/// variants that the request's `Accept-Encoding` header prefers. Each variant has
+/// its own validators, and gets byte ranges and conditional requests of its own.
+///
+/// Sets `vary: accept-encoding` on the response if the file has any variants.
+pub struct Precompressed {
+	pub content_type: ContentType,
+	pub bytes: Cow<'static, [u8]>,
+	pub validators: Option<Validators>,
+	pub variants: &'static [Variant],
+}
+
+impl<'r> Responder<'r, 'static> for Precompressed {
+	fn respond_to(self, request: &'r Request<'_>) -> rocket::response::Result<'static> {
+		let accepted = request
+			.headers()
+			.get(header::ACCEPT_ENCODING.as_str())
+			.collect::<Vec<_>>()
+			.join(",");
+		let variant = preferred_variant(&accepted, self.variants);
+
+		let (bytes, validators) = match variant {
+			Some(variant) => (Cow::Borrowed(variant.bytes), Some(variant.validators)),
+			None => (self.bytes, self.validators),
+		};
+		let file = ByteRanges(self.content_type, bytes, validators);
+		let mut res = ConditionalResponse(file, validators).respond_to(request)?;
+
+		if let Some(variant) = variant {
+			res.set_header(Header::new(
+				header::CONTENT_ENCODING.as_str(),
+				variant.coding.token(),
+			));
+		}
+		if !self.variants.is_empty() {
+			add_vary(&mut res, &["Accept-Encoding"]);
+		}
+		Ok(res)
+	}
+}
+
+/// Returns the smallest of the given variants whose coding the given `Accept-Encoding`
+/// value rates highest, or `None` if the original file should be sent because the
+/// value rates it higher. Variants win ties, since they're smaller.
+fn preferred_variant(accepted: &str, variants: &[Variant]) -> Option<Variant> {
+	// The original is acceptable unless refused, but ranks below any coding that
+	// the client names. See RFC 9110, section 12.5.3
+	let identity_quality =
+		quality_of(accepted, |token| token.eq_ignore_ascii_case("identity")).unwrap_or(1);
+
+	let mut preferred: Option<(u16, Variant)> = None;
+	for &variant in variants {
+		let quality = quality_of(accepted, |token| variant.coding.is_named(token)).unwrap_or(0);
+		if quality == 0 || quality < identity_quality {
+			continue;
+		}
+		let is_better = preferred.is_none_or(|(best_quality, best)| {
+			quality > best_quality
+				|| (quality == best_quality && variant.bytes.len() < best.bytes.len())
+		});
+		if is_better {
+			preferred = Some((quality, variant));
+		}
+	}
+	preferred.map(|(_, variant)| variant)
+}
+
+/// The quality, in thousandths, that the given `Accept-Encoding` value gives the
+/// coding with the given name, either by name or with `*`, or `None` if the value
+/// doesn't mention it.
+fn quality_of(accepted: &str, is_named: impl Fn(&str) -> bool) -> Option<u16> {
+	let mut wildcard = None;
+	for item in accepted.split(',') {
+		let mut params = item.split(';').map(str::trim);
+		let token = params.next().unwrap_or_default();
+		let quality = item_quality(params);
+
+		if is_named(token) {
+			return Some(quality);
+		}
+		if token == "*" {
+			wildcard = Some(quality);
+		}
+	}
+	wildcard
+}
+
+// MARK: - Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::middleware::testing::{client, get_with};
+	use rocket::get;
+	use rocket::http::Status;
+	use rocket::{catchers, routes};
+	use test_case::test_case;
+
+	const DATE: &str = "Tue, 14 Nov 2023 22:13:20 GMT";
+	static VARIANTS: &[Variant] = &[
+		Variant::new(
+			ContentCoding::Gzip,
+			Validators::new("\"abc-gzip\"", DATE),
+			b"gzip",
+		),
+		Variant::new(
+			ContentCoding::Brotli,
+			Validators::new("\"abc-br\"", DATE),
+			b"br",
+		),
+		Variant::new(
+			ContentCoding::Zstd,
+			Validators::new("\"abc-zstd\"", DATE),
+			b"zstd",
+		),
+	];
+
+	#[get("/")]
+	fn file() -> Precompressed {
+		Precompressed {
+			content_type: ContentType::Plain,
+			bytes: Cow::Borrowed(b"original"),
+			validators: Some(Validators::new("\"abc\"", DATE)),
+			variants: VARIANTS,
+		}
+	}
+
+	#[test_case("", None ; "nothing")]
+	#[test_case("identity", None ; "identity")]
+	#[test_case("gzip", Some(ContentCoding::Gzip) ; "gzip")]
+	#[test_case("x-gzip", Some(ContentCoding::Gzip) ; "gzip alias")]
+	#[test_case("gzip, deflate, br", Some(ContentCoding::Brotli) ; "smallest of equals")]
+	#[test_case("gzip, deflate, br, zstd", Some(ContentCoding::Brotli) ; "smallest of all")]
+	#[test_case("br;q=0.5, zstd", Some(ContentCoding::Zstd) ; "highest quality")]
+	#[test_case("GZIP;Q=0.9, br;q=0", Some(ContentCoding::Gzip) ; "refused coding")]
+	#[test_case("*", Some(ContentCoding::Brotli) ; "any coding")]
+	#[test_case("*;q=0.1, br;q=0", Some(ContentCoding::Gzip) ; "any other coding")]
+	#[test_case("br;q=2", None ; "malformed quality")]
+	#[test_case("identity, gzip;q=0.1", None ; "identity preferred")]
+	#[test_case("identity;q=0.5, gzip;q=0.5", Some(ContentCoding::Gzip) ; "identity tied")]
+	#[test_case("*;q=0.5, identity;q=0", Some(ContentCoding::Brotli) ; "identity refused")]
+	fn prefers_variant(accepted: &str, expected: Option<ContentCoding>) {
+		let variant = preferred_variant(accepted, VARIANTS);
+		assert_eq!(variant.map(|variant| variant.coding), expected);
+	}
+
+	#[test]
+	fn serves_original_by_default() {
+		let client = client(routes![file], catchers![]);
+		let res = get_with(&client, &[]);
+		assert_eq!(res.status(), Status::Ok);
+		assert_eq!(res.headers().get_one("Content-Encoding"), None);
+		assert_eq!(res.headers().get_one("Vary"), Some("Accept-Encoding"));
+		assert_eq!(res.headers().get_one("ETag"), Some("\"abc\""));
+		assert_eq!(res.into_string().as_deref(), Some("original"));
+	}
+
+	#[test]
+	fn serves_preferred_variant() {
+		let client = client(routes![file], catchers![]);
+		let res = get_with(&client, &[("Accept-Encoding", "gzip, zstd;q=0.5")]);
+		assert_eq!(res.status(), Status::Ok);
+		assert_eq!(res.headers().get_one("Content-Encoding"), Some("gzip"));
+		assert_eq!(res.headers().get_one("Vary"), Some("Accept-Encoding"));
+		assert_eq!(res.headers().get_one("ETag"), Some("\"abc-gzip\""));
+		assert_eq!(res.into_string().as_deref(), Some("gzip"));
+	}
+
+	#[test]
+	fn revalidates_each_variant() {
+		let client = client(routes![file], catchers![]);
+		let res = get_with(
+			&client,
+			&[("Accept-Encoding", "br"), ("If-None-Match", "\"abc-br\"")],
+		);
+		assert_eq!(res.status(), Status::NotModified);
+
+		let res = get_with(
+			&client,
+			&[("Accept-Encoding", "br"), ("If-None-Match", "\"abc\"")],
+		);
+		assert_eq!(res.status(), Status::Ok);
+	}
+
+	#[test]
+	fn serves_ranges_of_variant() {
+		let client = client(routes![file], catchers![]);
+		let res = get_with(
+			&client,
+			&[("Accept-Encoding", "zstd"), ("Range", "bytes=1-2")],
+		);
+		assert_eq!(res.status(), Status::PartialContent);
+		assert_eq!(res.headers().get_one("Content-Encoding"), Some("zstd"));
+		assert_eq!(res.headers().get_one("Content-Range"), Some("bytes 1-2/4"));
+		assert_eq!(res.into_string().as_deref(), Some("st"));
+	}
+}
@@ -1,11 +1,12 @@
+use crate::config::Profile;
 use rocket::fairing::{Fairing, Info, Kind};
 use rocket::{Request, Response};
 
 pub static X_PRONOUNS_ACCEPTABLE: &str = "X-Pronouns-Acceptable";
-pub static PRONOUNS_EN: &str = "she/her";
 
 /// A Rocket [Fairing](https://rocket.rs/guide/v0.5/fairings/#fairings) that sets
-/// [`X-Pronouns-Acceptable`](https://www.andrewyu.org/article/x-pronouns.html) on every response.
+/// [`X-Pronouns-Acceptable`](https://www.andrewyu.org/article/x-pronouns.html) on every response,
+/// using the managed [`Profile`]'s pronouns.
 pub struct PronounsAcceptable;
 
 #[rocket::async_trait]
@@ -17,7 +18,10 @@ impl Fairing for PronounsAcceptable {
 		}
 	}
 
-	async fn on_response<'r>(&self, _: &'r Request<'_>, response: &mut Response<'r>) {
-		response.set_raw_header(X_PRONOUNS_ACCEPTABLE, format!("en:{PRONOUNS_EN}"));
+	async fn on_response<'r>(&self, req: &'r Request<'_>, response: &mut Response<'r>) {
+		if let Some(profile) = req.rocket().state::<Profile>() {
+			let pronouns = &profile.pronouns;
+			response.set_raw_header(X_PRONOUNS_ACCEPTABLE, format!("en:{pronouns}"));
+		}
 	}
 }
@@ -0,0 +1,104 @@
+use http::header;
+use rocket::Request;
+
+/// The request's `Accept` values as one list, or `*/*` if it has none.
+pub fn accepted_media_types(request: &Request<'_>) -> String {
+	let accepted = request
+		.headers()
+		.get(header::ACCEPT.as_str())
+		.collect::<Vec<_>>()
+		.join(",");
+	if accepted.trim().is_empty() {
+		"*/*".to_owned()
+	} else {
+		accepted
+	}
+}
+
+/// The quality, in thousandths, that the given `Accept` value gives the given media
+/// type, according to the most specific media range that matches it.
+pub fn media_type_quality(accepted: &str, media_type: &str) -> u16 {
+	let (top, sub) = media_type.split_once('/').unwrap_or((media_type, ""));
+	let mut best: Option<(u8, u16)> = None; // (specificity, quality)
+	for item in accepted.split(',') {
+		let mut params = item.split(';').map(str::trim);
+		let Some((range_top, range_sub)) = params.next().and_then(|range| range.split_once('/'))
+		else {
+			continue;
+		};
+		let specificity = match (range_top, range_sub) {
+			("*", "*") => 1,
+			(range_top, "*") if range_top.eq_ignore_ascii_case(top) => 2,
+			(range_top, range_sub)
+				if range_top.eq_ignore_ascii_case(top) && range_sub.eq_ignore_ascii_case(sub) =>
+			{
+				3
+			}
+			_ => continue,
+		};
+		let quality = item_quality(params);
+
+		if best.is_none_or(|(best_specificity, _)| specificity > best_specificity) {
+			best = Some((specificity, quality));
+		}
+	}
+	best.map_or(0, |(_, quality)| quality)
+}
+
+/// The quality, in thousandths, given by the `q` parameter among the given parameters
+/// of an item in a header like `Accept`. Items without one have full quality, and
+/// items with a malformed one have none.
+pub fn item_quality<'a>(mut params: impl Iterator<Item = &'a str>) -> u16 {
+	params
+		.find_map(|param| {
+			param
+				.strip_prefix("q=")
+				.or_else(|| param.strip_prefix("Q="))
+		})
+		.map_or(Some(1000), parse_quality)
+		.unwrap_or(0)
+}
+
+/// Parses a quality value like `0.8` into thousandths.
+fn parse_quality(value: &str) -> Option<u16> {
+	let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+	if fraction.len() > 3 || !fraction.bytes().all(|byte| byte.is_ascii_digit()) {
+		return None;
+	}
+	let thousandths = format!("{fraction:0<3}").parse::<u16>().ok()?;
+	match whole {
+		"0" => Some(thousandths),
+		"1" if thousandths == 0 => Some(1000),
+		_ => None,
+	}
+}
+
+// MARK: - Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use test_case::test_case;
+
+	#[test_case("1", Some(1000))]
+	#[test_case("1.000", Some(1000))]
+	#[test_case("0.8", Some(800))]
+	#[test_case("0.125", Some(125))]
+	#[test_case("0", Some(0))]
+	#[test_case("1.5", None)]
+	#[test_case("0.1234", None)]
+	#[test_case("-1", None)]
+	fn parses_quality(value: &str, expected: Option<u16>) {
+		assert_eq!(parse_quality(value), expected);
+	}
+
+	#[test_case("text/plain", 1000 ; "exact")]
+	#[test_case("text/*;q=0.5", 500 ; "any subtype")]
+	#[test_case("*/*;q=0.1", 100 ; "anything")]
+	#[test_case("*/*;q=0.1, text/*;q=0.2, Text/Plain;q=0.3", 300 ; "most specific wins")]
+	#[test_case("text/plain;q=0, */*", 0 ; "refused")]
+	#[test_case("text/html", 0 ; "other type")]
+	fn rates_media_type(accepted: &str, expected: u16) {
+		assert_eq!(media_type_quality(accepted, "text/plain"), expected);
+	}
+}
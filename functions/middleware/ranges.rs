@@ -0,0 +1,334 @@
+use super::Validators;
+use core::ops::RangeInclusive;
+use http::header;
+use rocket::Request;
+use rocket::http::{ContentType, Header, Status};
+use rocket::response::{Responder, Response};
+use std::borrow::Cow;
+use std::io::Cursor;
+
+/// The most ranges we'll answer in one response. Requests for more get the whole file.
+const MAX_RANGES: usize = 16;
+
+/// A responder that serves the given file with the given content type, or only the
+/// parts of it that the request's `Range` header asks for. If the request has an
+/// `If-Range` header, ranges are served only if it matches the given validators.
+///
+/// Sets `accept-ranges: bytes` on the response.
+pub struct ByteRanges(
+	pub ContentType,
+	pub Cow<'static, [u8]>,
+	pub Option<Validators>,
+);
+impl<'r> Responder<'r, 'static> for ByteRanges {
+	fn respond_to(self, request: &'r Request<'_>) -> rocket::response::Result<'static> {
+		let Self(content_type, bytes, validators) = self;
+		let len = bytes.len();
+		let requested = request
+			.headers()
+			.get_one(header::RANGE.as_str())
+			.filter(|_| if_range_matches(request, validators))
+			.and_then(|value| parse_ranges(value, len));
+
+		let mut res = match requested {
+			None => Response::build()
+				.header(content_type)
+				.sized_body(len, Cursor::new(bytes))
+				.finalize(),
+			Some(RangeRequest::Unsatisfiable) => Response::build()
+				.status(Status::RangeNotSatisfiable)
+				.raw_header(header::CONTENT_RANGE.as_str(), format!("bytes */{len}"))
+				.finalize(),
+			Some(RangeRequest::Satisfiable(ranges)) => {
+				if let [range] = ranges.as_slice() {
+					let part = part_of(bytes, range.clone());
+					Response::build()
+						.status(Status::PartialContent)
+						.header(content_type)
+						.raw_header(header::CONTENT_RANGE.as_str(), content_range(range, len))
+						.sized_body(part.len(), Cursor::new(part))
+						.finalize()
+				} else {
+					let boundary = format!("{:016x}", rand::random::<u64>());
+					let body = multipart_body(&bytes, &ranges, &content_type, &boundary);
+					Response::build()
+						.status(Status::PartialContent)
+						.header(
+							ContentType::new("multipart", "byteranges")
+								.with_params(("boundary", boundary)),
+						)
+						.sized_body(body.len(), Cursor::new(body))
+						.finalize()
+				}
+			}
+		};
+		res.set_header(Header::new(header::ACCEPT_RANGES.as_str(), "bytes"));
+		Ok(res)
+	}
+}
+
+/// What a `Range` header asks for.
+#[derive(Debug, PartialEq, Eq)]
+enum RangeRequest {
+	/// The ranges of the file to send, sorted and without overlaps.
+	Satisfiable(Vec<RangeInclusive<usize>>),
+
+	/// None of the requested ranges are in the file.
+	Unsatisfiable,
+}
+
+/// Parses the given `Range` header value for a file of the given length. Returns
+/// `None` if the header should be ignored, as when it's malformed or asks for too
+/// many ranges.
+fn parse_ranges(value: &str, len: usize) -> Option<RangeRequest> {
+	let (unit, specs) = value.split_once('=')?;
+	if !unit.trim().eq_ignore_ascii_case("bytes") {
+		return None;
+	}
+
+	let specs: Vec<&str> = specs
+		.split(',')
+		.map(str::trim)
+		.filter(|spec| !spec.is_empty())
+		.collect();
+	if specs.is_empty() || specs.len() > MAX_RANGES {
+		return None;
+	}
+
+	let mut ranges = Vec::with_capacity(specs.len());
+	for spec in specs {
+		let (first, last) = spec.split_once('-')?;
+		let range = if first.is_empty() {
+			// The last `suffix` bytes
+			let suffix = position(last)?;
+			if suffix == 0 || len == 0 {
+				continue;
+			}
+			len.saturating_sub(suffix)..=len.saturating_sub(1)
+		} else {
+			let first = position(first)?;
+			let last = if last.is_empty() {
+				None
+			} else {
+				Some(position(last)?)
+			};
+			if last.is_some_and(|last| last < first) {
+				return None;
+			}
+			if first >= len {
+				continue;
+			}
+			let end = len.saturating_sub(1);
+			first..=last.map_or(end, |last| last.min(end))
+		};
+		ranges.push(range);
+	}
+
+	if ranges.is_empty() {
+		return Some(RangeRequest::Unsatisfiable);
+	}
+	Some(RangeRequest::Satisfiable(coalesce(ranges)))
+}
+
+/// Parses a byte position, which is only digits.
+fn position(value: &str) -> Option<usize> {
+	if value.is_empty() || !value.bytes().all(|byte| byte.is_ascii_digit()) {
+		return None;
+	}
+	value.parse().ok()
+}
+
+/// Sorts the given ranges, and merges those that overlap or touch.
+fn coalesce(mut ranges: Vec<RangeInclusive<usize>>) -> Vec<RangeInclusive<usize>> {
+	ranges.sort_by_key(|range| *range.start());
+	let mut merged: Vec<RangeInclusive<usize>> = Vec::with_capacity(ranges.len());
+	for range in ranges {
+		match merged.last_mut() {
+			Some(last) if *range.start() <= last.end().saturating_add(1) => {
+				*last = *last.start()..=*last.end().max(range.end());
+			}
+			_ => merged.push(range),
+		}
+	}
+	merged
+}
+
+/// Whether ranges may be served, according to the request's `If-Range` header.
+fn if_range_matches(request: &Request<'_>, validators: Option<Validators>) -> bool {
+	let Some(condition) = request.headers().get_one(header::IF_RANGE.as_str()) else {
+		return true;
+	};
+	validators.is_some_and(|validators| validators.matches_exactly(condition.trim()))
+}
+
+/// The given range of the given file, borrowed if the file is.
+fn part_of(bytes: Cow<'static, [u8]>, range: RangeInclusive<usize>) -> Cow<'static, [u8]> {
+	match bytes {
+		Cow::Borrowed(bytes) => Cow::Borrowed(bytes.get(range).unwrap_or_default()),
+		Cow::Owned(bytes) => Cow::Owned(bytes.get(range).unwrap_or_default().to_vec()),
+	}
+}
+
+/// The `Content-Range` value for the given range of a file of the given length.
+fn content_range(range: &RangeInclusive<usize>, len: usize) -> String {
+	format!("bytes {}-{}/{len}", range.start(), range.end())
+}
+
+/// Builds a `multipart/byteranges` body with a part for each of the given ranges.
+fn multipart_body(
+	bytes: &[u8],
+	ranges: &[RangeInclusive<usize>],
+	content_type: &ContentType,
+	boundary: &str,
+) -> Vec<u8> {
+	let mut body = Vec::new();
+	for range in ranges {
+		let range_header = content_range(range, bytes.len());
+		let part_headers = format!(
+			"--{boundary}\r\nContent-Type: {content_type}\r\nContent-Range: {range_header}\r\n\r\n"
+		);
+		body.extend_from_slice(part_headers.as_bytes());
+		body.extend_from_slice(bytes.get(range.clone()).unwrap_or_default());
+		body.extend_from_slice(b"\r\n");
+	}
+	body.extend_from_slice(format!("--{boundary}--\r\n").as_bytes());
+	body
+}
+
+// MARK: - Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::middleware::testing::{client, get_with};
+	use rocket::get;
+	use rocket::{catchers, routes};
+	use test_case::test_case;
+
+	static FILE: &[u8] = b"0123456789";
+	const VALIDATORS: Validators = Validators::new("\"abc\"", "Tue, 14 Nov 2023 22:13:20 GMT");
+
+	#[get("/")]
+	fn file() -> ByteRanges {
+		ByteRanges(ContentType::Plain, Cow::Borrowed(FILE), Some(VALIDATORS))
+	}
+
+	#[get("/owned")]
+	fn owned_file() -> ByteRanges {
+		ByteRanges(ContentType::Plain, Cow::Owned(FILE.to_vec()), None)
+	}
+
+	#[test_case("bytes=0-4", &[0..=4] ; "first bytes")]
+	#[test_case("bytes=5-", &[5..=9] ; "open end")]
+	#[test_case("bytes=-3", &[7..=9] ; "suffix")]
+	#[test_case("bytes=-30", &[0..=9] ; "suffix longer than file")]
+	#[test_case("bytes=8-20", &[8..=9] ; "end past file")]
+	#[test_case("Bytes = 1-1", &[1..=1] ; "loose syntax")]
+	#[test_case("bytes=0-1, 6-7", &[0..=1, 6..=7] ; "two ranges")]
+	#[test_case("bytes=6-7, 0-1, 20-30", &[0..=1, 6..=7] ; "sorted without unsatisfiable")]
+	#[test_case("bytes=0-3, 2-5, 6-6", &[0..=6] ; "overlapping and adjacent")]
+	fn parses_satisfiable_ranges(value: &str, expected: &[RangeInclusive<usize>]) {
+		assert_eq!(
+			parse_ranges(value, FILE.len()),
+			Some(RangeRequest::Satisfiable(expected.to_vec()))
+		);
+	}
+
+	#[test_case("bytes=10-" ; "start past file")]
+	#[test_case("bytes=-0" ; "empty suffix")]
+	#[test_case("bytes=20-30, 40-" ; "all past file")]
+	fn parses_unsatisfiable_ranges(value: &str) {
+		assert_eq!(
+			parse_ranges(value, FILE.len()),
+			Some(RangeRequest::Unsatisfiable)
+		);
+	}
+
+	#[test_case("items=0-4" ; "other unit")]
+	#[test_case("bytes=" ; "no ranges")]
+	#[test_case("bytes=5-4" ; "backwards")]
+	#[test_case("bytes=a-b" ; "not numbers")]
+	#[test_case("bytes=+1-2" ; "signed number")]
+	#[test_case("bytes=4" ; "no dash")]
+	#[test_case("bytes=0-0,1-1,2-2,3-3,4-4,5-5,6-6,7-7,8-8,9-9,0-0,1-1,2-2,3-3,4-4,5-5,6-6" ; "too many ranges")]
+	fn ignores_malformed_ranges(value: &str) {
+		assert_eq!(parse_ranges(value, FILE.len()), None);
+	}
+
+	#[test]
+	fn serves_whole_file() {
+		let client = client(routes![file, owned_file], catchers![]);
+		let res = get_with(&client, &[]);
+		assert_eq!(res.status(), Status::Ok);
+		assert_eq!(res.headers().get_one("Accept-Ranges"), Some("bytes"));
+		assert_eq!(res.body().preset_size(), Some(FILE.len()));
+		assert_eq!(res.into_bytes().as_deref(), Some(FILE));
+	}
+
+	#[test]
+	fn serves_one_range() {
+		let client = client(routes![file, owned_file], catchers![]);
+		let res = get_with(&client, &[("Range", "bytes=2-4")]);
+		assert_eq!(res.status(), Status::PartialContent);
+		assert_eq!(res.headers().get_one("Accept-Ranges"), Some("bytes"));
+		assert_eq!(res.headers().get_one("Content-Range"), Some("bytes 2-4/10"));
+		assert_eq!(res.content_type(), Some(ContentType::Plain));
+		assert_eq!(res.body().preset_size(), Some(3));
+		assert_eq!(res.into_bytes().as_deref(), Some(&b"234"[..]));
+	}
+
+	#[test]
+	fn serves_one_range_of_owned_file() {
+		let client = client(routes![file, owned_file], catchers![]);
+		let res = client
+			.get("/owned")
+			.header(Header::new("Range", "bytes=-2"))
+			.dispatch();
+		assert_eq!(res.status(), Status::PartialContent);
+		assert_eq!(res.headers().get_one("Content-Range"), Some("bytes 8-9/10"));
+		assert_eq!(res.into_bytes().as_deref(), Some(&b"89"[..]));
+	}
+
+	#[test]
+	fn serves_several_ranges() {
+		let client = client(routes![file, owned_file], catchers![]);
+		let res = get_with(&client, &[("Range", "bytes=0-1,-2")]);
+		assert_eq!(res.status(), Status::PartialContent);
+		let content_type = res.content_type().expect("Content-Type should be sent");
+		assert_eq!(content_type.top(), "multipart");
+		assert_eq!(content_type.sub(), "byteranges");
+		let boundary = content_type
+			.param("boundary")
+			.expect("Boundary should be given")
+			.to_owned();
+
+		let body = res.into_string().expect("Body should be text");
+		assert_eq!(
+			body,
+			format!(
+				"--{boundary}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Range: bytes 0-1/10\r\n\r\n01\r\n\
+				--{boundary}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Range: bytes 8-9/10\r\n\r\n89\r\n\
+				--{boundary}--\r\n"
+			)
+		);
+	}
+
+	#[test]
+	fn rejects_unsatisfiable_range() {
+		let client = client(routes![file, owned_file], catchers![]);
+		let res = get_with(&client, &[("Range", "bytes=10-")]);
+		assert_eq!(res.status(), Status::RangeNotSatisfiable);
+		assert_eq!(res.headers().get_one("Content-Range"), Some("bytes */10"));
+	}
+
+	#[test_case("\"abc\"", Status::PartialContent ; "matching tag")]
+	#[test_case("\"xyz\"", Status::Ok ; "other tag")]
+	#[test_case("W/\"abc\"", Status::Ok ; "weak tag")]
+	#[test_case("Tue, 14 Nov 2023 22:13:20 GMT", Status::PartialContent ; "matching date")]
+	#[test_case("Wed, 15 Nov 2023 00:00:00 GMT", Status::Ok ; "other date")]
+	fn checks_if_range(condition: &'static str, status: Status) {
+		let client = client(routes![file, owned_file], catchers![]);
+		let res = get_with(&client, &[("Range", "bytes=0-1"), ("If-Range", condition)]);
+		assert_eq!(res.status(), status);
+	}
+}
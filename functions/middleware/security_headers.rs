@@ -1,11 +1,11 @@
+use crate::config::Profile;
 use http::header;
 use rocket::fairing::{Fairing, Info, Kind};
-use rocket::http::uri::{Absolute, Host};
+use rocket::http::uri::Host;
 use rocket::shield::{
 	Allow, Feature, Frame, Hsts, NoSniff, Permission, Prefetch, Referrer, Shield,
 };
 use rocket::time::Duration;
-use rocket::uri;
 use rocket::{Request, Response};
 
 // Standard headers that aren't in the http crate yet:
@@ -97,14 +97,18 @@ impl Fairing for ExtraSecurityHeaders {
 
 	async fn on_response<'r>(&self, req: &'r Request<'_>, response: &mut Response<'r>) {
 		let user_provided_host = req.host();
+		let prod_origin = req
+			.rocket()
+			.state::<Profile>()
+			.map_or("", |profile| profile.origin.as_str());
+		let origin = resource_origin(user_provided_host, prod_origin);
 
 		// We need to set script-src-elem dynamically, because
 		// (1) XML styles somehow read as a script,
 		// (2) we don't know which origin the request comes from until this spot, and
 		// (3) I don't want to permit localhost resources in production.
-		let rss_styles_src = format!("{}/rss/styles.xsl", resource_origin(user_provided_host));
-		let sitemap_styles_src =
-			format!("{}/sitemap/styles.xsl", resource_origin(user_provided_host));
+		let rss_styles_src = format!("{origin}/rss/styles.xsl");
+		let sitemap_styles_src = format!("{origin}/sitemap/styles.xsl");
 
 		response.set_raw_header(header::CONTENT_SECURITY_POLICY.to_string(), format!("base-uri 'none'; default-src 'none'; font-src 'self'; form-action 'self'; frame-ancestors 'none'; img-src 'self' https://* data:; sandbox allow-same-origin allow-downloads allow-forms allow-scripts; style-src 'self' 'unsafe-inline'; media-src 'none'; script-src-elem {rss_styles_src} {sitemap_styles_src}; upgrade-insecure-requests"));
 		response.set_raw_header(CROSS_ORIGIN_EMBEDDER_POLICY, "require-corp");
@@ -117,13 +121,13 @@ impl Fairing for ExtraSecurityHeaders {
 
 /// Returns the origin from the given user-provided host name. If the given host is
 /// some flavor of `localhost`, then the host is returned as-is with an `http` scheme.
-/// If the host is unknown, or no host is given, then our production domain name
+/// If the host is unknown, or no host is given, then the given production origin
 /// is returned.
-fn resource_origin(user_provided_host: Option<&Host<'_>>) -> Absolute<'static> {
+fn resource_origin<'o>(user_provided_host: Option<&Host<'_>>, prod_origin: &'o str) -> &'o str {
 	match user_provided_host {
-		Some(untrustred) if untrustred.domain() == "localhost" => uri!("http://localhost"), // dev
-		Some(untrustred) if untrustred.domain() == "127.0.0.1" => uri!("http://127.0.0.1"), // dev
-		Some(untrustred) if untrustred.domain() == "[::1]" => uri!("http://[::1]"),         // dev
-		Some(_) | None => uri!("https://average.name"),                                     // prod
+		Some(untrustred) if untrustred.domain() == "localhost" => "http://localhost", // dev
+		Some(untrustred) if untrustred.domain() == "127.0.0.1" => "http://127.0.0.1", // dev
+		Some(untrustred) if untrustred.domain() == "[::1]" => "http://[::1]",         // dev
+		Some(_) | None => prod_origin,                                                // prod
 	}
 }
@@ -55,7 +55,7 @@ mod tests {
 	#[test_case("/foo/bar")]
 	#[test_case("/foo/bar/baz")]
 	#[test_case("/foo/bar/42")]
-	fn test_fairing_does_nothing_for_valid_paths(path: &'static str) {
+	fn fairing_does_nothing_for_valid_paths(path: &'static str) {
 		let client = build_client();
 		let res = get(&client, path);
 		assert_eq!(res.status(), Status::NotFound);
@@ -65,10 +65,7 @@ mod tests {
 	#[test_case("/foo/bar/", "/foo/bar")]
 	#[test_case("/foo/bar/baz/", "/foo/bar/baz")]
 	#[test_case("/foo/bar/42/", "/foo/bar/42")]
-	fn test_fairing_redirects_trailing_slashes_appropriately(
-		path: &'static str,
-		dest: &'static str,
-	) {
+	fn fairing_redirects_trailing_slashes_appropriately(path: &'static str, dest: &'static str) {
 		let client = build_client();
 		let res = get(&client, path);
 		assert_eq!(res.status(), Status::MovedPermanently);
@@ -0,0 +1,45 @@
+use http::header;
+use rocket::Response;
+
+/// Adds the given field names to the response's `Vary` header, after the ones it
+/// already has.
+pub fn add_vary(response: &mut Response<'_>, fields: &[&str]) {
+	let mut vary: Vec<String> = response
+		.headers()
+		.get(header::VARY.as_str())
+		.flat_map(|value| value.split(','))
+		.map(str::trim)
+		.filter(|field| !field.is_empty())
+		.map(str::to_owned)
+		.collect();
+	for &field in fields {
+		if !vary.iter().any(|known| known.eq_ignore_ascii_case(field)) {
+			vary.push(field.to_owned());
+		}
+	}
+	response.set_raw_header(header::VARY.as_str(), vary.join(", "));
+}
+
+// MARK: - Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rocket::http::Header;
+
+	#[test]
+	fn merges_fields() {
+		let mut response = Response::build()
+			.header(Header::new("Vary", "Accept-Encoding"))
+			.finalize();
+		add_vary(&mut response, &["Accept", "accept-encoding", "User-Agent"]);
+		assert_eq!(
+			response.headers().get_one("Vary"),
+			Some("Accept-Encoding, Accept, User-Agent")
+		);
+
+		let mut response = Response::new();
+		add_vary(&mut response, &["Accept"]);
+		assert_eq!(response.headers().get_one("Vary"), Some("Accept"));
+	}
+}
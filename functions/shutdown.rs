@@ -0,0 +1,94 @@
+use std::{io, sync::Arc};
+use tokio::sync::watch;
+
+/// A flag, shared between services, that tells them to stop gracefully.
+#[derive(Clone)]
+pub struct Shutdown(Arc<watch::Sender<bool>>);
+
+impl Default for Shutdown {
+	fn default() -> Self {
+		Self(Arc::new(watch::Sender::new(false)))
+	}
+}
+
+impl Shutdown {
+	/// Asks every service to stop accepting connections and finish what it's doing.
+	pub fn trigger(&self) {
+		self.0.send_replace(true);
+	}
+
+	/// Whether shutdown has been triggered.
+	pub fn is_triggered(&self) -> bool {
+		*self.0.borrow()
+	}
+
+	/// Waits until shutdown is triggered. Returns immediately if it already was.
+	pub async fn triggered(&self) {
+		let mut receiver = self.0.subscribe();
+		// Fails only if the sender is dropped, but we're holding it
+		_ = receiver.wait_for(|&triggered| triggered).await;
+	}
+
+	/// Triggers shutdown when the process receives SIGTERM or SIGINT.
+	#[cfg(unix)]
+	pub fn trigger_on_signal(&self) -> io::Result<()> {
+		use tokio::signal::unix::{SignalKind, signal};
+
+		let mut terminate = signal(SignalKind::terminate())?;
+		let mut interrupt = signal(SignalKind::interrupt())?;
+		let shutdown = self.clone();
+		tokio::spawn(async move {
+			let name = tokio::select! {
+				_ = terminate.recv() => "SIGTERM",
+				_ = interrupt.recv() => "SIGINT",
+			};
+			eprintln!("[shutdown] Received {name}; finishing in-flight requests");
+			shutdown.trigger();
+		});
+		Ok(())
+	}
+
+	/// Triggers shutdown when the process receives Ctrl-C.
+	#[cfg(not(unix))]
+	pub fn trigger_on_signal(&self) -> io::Result<()> {
+		let shutdown = self.clone();
+		tokio::spawn(async move {
+			if tokio::signal::ctrl_c().await.is_ok() {
+				eprintln!("[shutdown] Received Ctrl-C; finishing in-flight requests");
+				shutdown.trigger();
+			}
+		});
+		Ok(())
+	}
+}
+
+// MARK: - Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use core::time::Duration;
+	use tokio::time::timeout;
+
+	#[tokio::test]
+	async fn wakes_waiters_on_trigger() {
+		let shutdown = Shutdown::default();
+		assert!(!shutdown.is_triggered(), "Shutdown starts untriggered");
+
+		let waiter = tokio::spawn({
+			let shutdown = shutdown.clone();
+			async move { shutdown.triggered().await }
+		});
+		shutdown.trigger();
+		timeout(Duration::from_secs(1), waiter)
+			.await
+			.expect("Waiter should wake")
+			.expect("Waiter should not panic");
+
+		// Later waiters return right away
+		assert!(shutdown.is_triggered(), "Shutdown stays triggered");
+		timeout(Duration::from_secs(1), shutdown.triggered())
+			.await
+			.expect("Waiter should not block");
+	}
+}
@@ -0,0 +1,246 @@
+use crate::AppError;
+use crate::config::{Service, SupervisorConfig};
+use crate::shutdown::Shutdown;
+use core::time::Duration;
+use std::time::Instant;
+use tokio::task::JoinSet;
+
+/// How the supervisor treats a service that fails.
+#[derive(Clone, Copy)]
+pub struct RestartPolicy {
+	/// The number of consecutive times a service may be restarted before the
+	/// supervisor gives up on it.
+	pub max_restarts: u32,
+
+	/// How long to wait before the first restart. The wait doubles with each
+	/// consecutive failure.
+	pub initial_backoff: Duration,
+
+	/// The longest to wait between restarts. A service that ran at least this
+	/// long before failing is considered to have recovered, and its restart
+	/// count starts over.
+	pub max_backoff: Duration,
+}
+
+impl RestartPolicy {
+	/// Returns how long to wait before restarting a service that has failed
+	/// `failures` times in a row, not counting the current failure.
+	fn backoff(&self, failures: u32) -> Duration {
+		let factor = 2_u32.saturating_pow(failures);
+		self.initial_backoff
+			.saturating_mul(factor)
+			.min(self.max_backoff)
+	}
+}
+
+impl From<&SupervisorConfig> for RestartPolicy {
+	fn from(config: &SupervisorConfig) -> Self {
+		Self {
+			max_restarts: config.max_restarts,
+			initial_backoff: Duration::from_millis(config.initial_backoff_ms),
+			max_backoff: Duration::from_millis(config.max_backoff_ms),
+		}
+	}
+}
+
+/// Runs each of the given services in its own task, restarting any that fail
+/// according to the given policy.
+///
+/// Returns once every service has stopped. If a service uses up its restart
+/// budget, the rest are shut down gracefully, and its error is returned.
+pub async fn supervise<F, Fut>(
+	services: &[Service],
+	policy: RestartPolicy,
+	shutdown: &Shutdown,
+	start: F,
+) -> Result<(), AppError>
+where
+	F: Fn(Service) -> Fut + Clone + Send + 'static,
+	Fut: Future<Output = Result<(), AppError>> + Send + 'static,
+{
+	let mut tasks = JoinSet::new();
+	for &service in services {
+		tasks.spawn(supervise_service(
+			service,
+			policy,
+			shutdown.clone(),
+			start.clone(),
+		));
+	}
+
+	let mut first_error = None;
+	while let Some(result) = tasks.join_next().await {
+		if let Err(err) = result.map_err(AppError::from).flatten() {
+			shutdown.trigger();
+			first_error.get_or_insert(err);
+		}
+	}
+
+	first_error.map_or(Ok(()), Err)
+}
+
+/// Runs the given service until it stops without error, until it has failed
+/// more times in a row than the policy allows, or until it fails during shutdown.
+async fn supervise_service<F, Fut>(
+	service: Service,
+	policy: RestartPolicy,
+	shutdown: Shutdown,
+	start: F,
+) -> Result<(), AppError>
+where
+	F: Fn(Service) -> Fut,
+	Fut: Future<Output = Result<(), AppError>> + Send + 'static,
+{
+	let mut failures: u32 = 0;
+	loop {
+		let started_at = Instant::now();
+
+		// Run each attempt as its own task, so that a panic counts as a failure.
+		// The set stops the task if we're dropped while waiting for it.
+		let mut attempt = JoinSet::new();
+		attempt.spawn(start(service));
+		let err = match attempt.join_next().await {
+			None | Some(Ok(Ok(()))) => return Ok(()),
+			Some(Ok(Err(err))) => err,
+			Some(Err(err)) => AppError::from(err),
+		};
+
+		eprintln!(
+			"[supervisor] {service} service failed with {} error: {err}",
+			err.variant()
+		);
+
+		if shutdown.is_triggered() {
+			return Err(err);
+		}
+		if started_at.elapsed() >= policy.max_backoff {
+			failures = 0;
+		}
+		if failures >= policy.max_restarts {
+			eprintln!("[supervisor] {service} service failed too many times; giving up");
+			return Err(err);
+		}
+
+		let delay = policy.backoff(failures);
+		failures = failures.saturating_add(1);
+		eprintln!(
+			"[supervisor] Restarting {service} service in {}ms (attempt {failures} of {})",
+			delay.as_millis(),
+			policy.max_restarts
+		);
+		tokio::select! {
+			() = tokio::time::sleep(delay) => {}
+			() = shutdown.triggered() => return Err(err),
+		}
+	}
+}
+
+// MARK: - Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::config::ConfigError;
+	use core::sync::atomic::{AtomicU32, Ordering};
+	use std::sync::Arc;
+
+	const POLICY: RestartPolicy = RestartPolicy {
+		max_restarts: 3,
+		initial_backoff: Duration::from_millis(1),
+		max_backoff: Duration::from_millis(5),
+	};
+
+	fn any_service() -> [Service; 1] {
+		let service = Service::all().into_iter().next();
+		[service.expect("At least one service should be enabled")]
+	}
+
+	fn failure() -> AppError {
+		AppError::Config(ConfigError::UnknownArgument("--fail".to_owned()))
+	}
+
+	/// Returns a service starter that fails the given number of times before
+	/// succeeding, along with a counter of how many times it was started.
+	fn flaky_service(
+		failures: u32,
+	) -> (
+		impl Fn(Service) -> futures::future::Ready<Result<(), AppError>> + Clone,
+		Arc<AtomicU32>,
+	) {
+		let starts = Arc::new(AtomicU32::new(0));
+		let counter = Arc::clone(&starts);
+		let start = move |_| {
+			let previous_starts = counter.fetch_add(1, Ordering::SeqCst);
+			futures::future::ready(if previous_starts < failures {
+				Err(failure())
+			} else {
+				Ok(())
+			})
+		};
+		(start, starts)
+	}
+
+	#[test]
+	fn backoff_doubles_up_to_max() {
+		let policy = RestartPolicy {
+			max_restarts: 10,
+			initial_backoff: Duration::from_secs(1),
+			max_backoff: Duration::from_secs(10),
+		};
+		assert_eq!(policy.backoff(0), Duration::from_secs(1));
+		assert_eq!(policy.backoff(1), Duration::from_secs(2));
+		assert_eq!(policy.backoff(3), Duration::from_secs(8));
+		assert_eq!(policy.backoff(4), Duration::from_secs(10));
+		assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(10));
+	}
+
+	#[tokio::test]
+	async fn restarts_failed_service() {
+		let (start, starts) = flaky_service(2);
+		supervise(&any_service(), POLICY, &Shutdown::default(), start)
+			.await
+			.expect("Service should recover");
+		assert_eq!(starts.load(Ordering::SeqCst), 3);
+	}
+
+	#[tokio::test]
+	async fn gives_up_after_restart_budget() {
+		let (start, starts) = flaky_service(u32::MAX);
+		let result = supervise(&any_service(), POLICY, &Shutdown::default(), start).await;
+		assert!(
+			matches!(result, Err(AppError::Config(_))),
+			"Service's own error should be returned"
+		);
+		assert_eq!(starts.load(Ordering::SeqCst), POLICY.max_restarts + 1);
+	}
+
+	#[tokio::test]
+	async fn does_not_restart_during_shutdown() {
+		let (start, starts) = flaky_service(u32::MAX);
+		let shutdown = Shutdown::default();
+		shutdown.trigger();
+		let result = supervise(&any_service(), POLICY, &shutdown, start).await;
+		assert!(
+			matches!(result, Err(AppError::Config(_))),
+			"Service's own error should be returned"
+		);
+		assert_eq!(starts.load(Ordering::SeqCst), 1);
+	}
+
+	#[tokio::test]
+	async fn restarts_panicked_service() {
+		let starts = Arc::new(AtomicU32::new(0));
+		let counter = Arc::clone(&starts);
+		let start = move |_| {
+			let previous_starts = counter.fetch_add(1, Ordering::SeqCst);
+			async move {
+				assert!(previous_starts > 0, "First start panics");
+				Ok(())
+			}
+		};
+		supervise(&any_service(), POLICY, &Shutdown::default(), start)
+			.await
+			.expect("Service should recover");
+		assert_eq!(starts.load(Ordering::SeqCst), 2);
+	}
+}
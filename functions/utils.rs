@@ -1,2 +1,12 @@
+mod escape;
+pub use escape::*;
+
+#[cfg(feature = "http")]
+mod html;
+#[cfg(feature = "http")]
+pub use html::*;
+
+#[cfg(feature = "http")]
 mod memorials;
+#[cfg(feature = "http")]
 pub use memorials::*;
@@ -0,0 +1,30 @@
+/// Escapes the given text for use in HTML or XML text, or a quoted attribute value.
+pub fn escape_html(text: &str) -> String {
+	let mut escaped = String::with_capacity(text.len());
+	for char in text.chars() {
+		match char {
+			'&' => escaped.push_str("&amp;"),
+			'<' => escaped.push_str("&lt;"),
+			'>' => escaped.push_str("&gt;"),
+			'"' => escaped.push_str("&quot;"),
+			'\'' => escaped.push_str("&#39;"),
+			_ => escaped.push(char),
+		}
+	}
+	escaped
+}
+
+// MARK: - Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn escapes_html() {
+		assert_eq!(
+			escape_html("<a href=\"x\">Tom & Jerry's</a>"),
+			"&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&#39;s&lt;/a&gt;"
+		);
+	}
+}
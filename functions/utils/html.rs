@@ -0,0 +1,38 @@
+/// Replaces the contents of the given page's first element with the given tag name,
+/// like `h1`, or returns `None` if the page has no such element.
+pub fn with_element_contents(page: &str, tag: &str, contents: &str) -> Option<String> {
+	let open = format!("<{tag}");
+	let close = format!("</{tag}>");
+	let (before, rest) = page.split_once(&open)?;
+	let (attributes, rest) = rest.split_once('>')?;
+	let (_, after) = rest.split_once(&close)?;
+	Some(format!(
+		"{before}{open}{attributes}>{contents}{close}{after}"
+	))
+}
+
+// MARK: - Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn replaces_element_contents() {
+		let page = "<html><body><h1 id=\"error\">Error 404: Page Not Found</h1><a href=\"/\">Return home</a></body></html>";
+		assert_eq!(
+			with_element_contents(page, "h1", "Error 500: Internal Server Error").as_deref(),
+			Some(
+				"<html><body><h1 id=\"error\">Error 500: Internal Server Error</h1><a href=\"/\">Return home</a></body></html>"
+			)
+		);
+		assert_eq!(
+			with_element_contents(page, "body", "<p>Hi</p>").as_deref(),
+			Some("<html><body><p>Hi</p></body></html>")
+		);
+		assert_eq!(
+			with_element_contents("<p>No heading</p>", "h1", "Error"),
+			None
+		);
+	}
+}
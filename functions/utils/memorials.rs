@@ -1,12 +1,6 @@
 use rand::{Rng, seq::IndexedRandom};
 
-/// A list of names of people to memorialize in X-Clacks-Overhead
-const NAMES: &[&str] = &[
-	"Terry Pratchett", // 28 April 1948 - 12 March 2015
-	"Nex Benedict",    // 11 January 2008 - February 8, 2024
-];
-
-/// Returns a random memorial name.
-pub fn random_name<R: Rng>(rng: &mut R) -> &'static str {
-	NAMES.choose(rng).expect("Names array should not be empty")
+/// Returns a random name from the given memorial names, or `None` if there are none.
+pub fn random_name<'n, R: Rng>(names: &'n [String], rng: &mut R) -> Option<&'n str> {
+	names.choose(rng).map(String::as_str)
 }
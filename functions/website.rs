@@ -0,0 +1,1520 @@
+use crate::config::{Config, HttpConfig, Profile};
+use crate::factories::{self, GemtextClient, TerminalClient, TextStyle, UserAgent, WebFinger};
+use crate::gemtext;
+use crate::middleware::{
+	CacheControl, CachePolicy, Clacks, ContentCoding, CorsAllowAllResponse, CorsOnlyProdResponse,
+	ExtraSecurityHeaders, NegotiatedError, Precompressed, PronounsAcceptable, TrimSlash,
+	Validators, Variant, add_vary, shield,
+};
+use crate::shutdown::Shutdown;
+use core::net::IpAddr;
+use http::header;
+use include_dir::{Dir, include_dir};
+use rocket::{Build, Request, Response, Rocket, State, catch, catchers, get, routes};
+use rocket::{
+	http::{
+		ContentType, Status,
+		uri::{Segments, fmt},
+	},
+	request::{FromParam, FromSegments},
+	response::{Redirect, Responder, content::RawHtml, status::NotFound},
+};
+use std::borrow::Cow;
+use std::{
+	ffi::OsStr,
+	io::Cursor,
+	path::{Path, PathBuf},
+};
+use tokio::fs;
+
+// MARK: - Routes
+
+/// Favicon is always not found
+#[get("/favicon.ico")]
+fn favicon() -> NotFound<&'static str> {
+	NotFound("")
+}
+
+// MARK: Pronouns
+
+#[get("/.well-known/pronouns")]
+fn well_known_pronouns(profile: &State<Profile>) -> CorsAllowAllResponse<String> {
+	CorsAllowAllResponse(profile.pronouns.clone())
+}
+
+// MARK: Fursona
+
+/// Serves my fursona avatar image, without CORS so external readers can access the file.
+#[get("/images/refs/AverageHelper-avatar.png")]
+async fn avatar(source: &State<DistSource>) -> CorsAllowAllResponse<Option<DistFile>> {
+	let path = Path::new("images/refs/AverageHelper-avatar.png");
+	CorsAllowAllResponse(source.file(path).await)
+}
+
+/// Serves fursona.json, without CORS so external readers can access the file.
+#[get("/.well-known/fursona.json")]
+async fn fursona(source: &State<DistSource>) -> CorsAllowAllResponse<Option<DistFile>> {
+	let path = Path::new(".well-known/fursona.json");
+	CorsAllowAllResponse(source.file(path).await)
+}
+
+// MARK: Fediverse aliases
+
+/// A path segment like `@avg`, which may name one of the profile's aliases.
+#[derive(Clone, Copy)]
+struct AliasSegment<'r>(&'r str);
+
+impl<'r> FromParam<'r> for AliasSegment<'r> {
+	type Error = &'r str;
+
+	fn from_param(param: &'r str) -> Result<Self, Self::Error> {
+		param.strip_prefix('@').map(Self).ok_or(param)
+	}
+}
+
+/// Redirects `/@{alias}` to the profile's fediverse account.
+#[get("/<alias>")]
+fn fediverse_alias(alias: AliasSegment<'_>, profile: &State<Profile>) -> Option<Redirect> {
+	profile
+		.aliases
+		.iter()
+		.any(|known| known == alias.0)
+		.then(|| Redirect::found(profile.fediverse.profile_page.clone()))
+}
+
+#[get("/.well-known/webfinger?<resource>&<rel>")]
+fn webfinger<'r>(
+	profile: &'r State<Profile>,
+	resource: Option<&'r str>,
+	rel: Option<Vec<&'r str>>,
+) -> Result<WebFinger<'r>, Status> {
+	match resource {
+		None => Err(Status::BadRequest),
+		Some(resource) => factories::webfinger(profile, resource, rel),
+	}
+}
+
+#[get("/.well-known/nodeinfo")]
+fn nodeinfo(profile: &State<Profile>, user_agent: UserAgent<'_>) -> Result<Redirect, Status> {
+	factories::nodeinfo(profile, user_agent)
+}
+
+// MARK: /dist
+
+// Astro's build, or the fallback site if there isn't one. See build.rs
+static DIST: Dir = include_dir!("$DIST_DIR");
+
+include!(concat!(env!("OUT_DIR"), "/dist_metadata.rs"));
+
+/// Where the site's static files come from.
+enum DistSource {
+	/// The files built into the server, with their precompressed variants.
+	Embedded,
+
+	/// The files in the given directory, read anew for each request.
+	Disk(PathBuf),
+}
+
+impl DistSource {
+	fn from_config(config: &HttpConfig) -> Self {
+		// Without dist on disk, the embedded fallback site is all there is
+		if config.serve_from_disk && Path::new("dist").is_dir() {
+			Self::Disk(PathBuf::from("dist"))
+		} else {
+			Self::Embedded
+		}
+	}
+
+	/// Whether the given path (relative to dist) is a directory.
+	async fn is_dir(&self, path: &Path) -> bool {
+		match self {
+			Self::Embedded => DIST.get_dir(path).is_some(),
+			Self::Disk(dir) => fs::metadata(dir.join(path))
+				.await
+				.is_ok_and(|metadata| metadata.is_dir()),
+		}
+	}
+
+	/// Whether the given path (relative to dist) is a file.
+	async fn is_file(&self, path: &Path) -> bool {
+		match self {
+			Self::Embedded => DIST.get_file(path).is_some(),
+			Self::Disk(dir) => fs::metadata(dir.join(path))
+				.await
+				.is_ok_and(|metadata| metadata.is_file()),
+		}
+	}
+
+	/// The contents of the file at the given path (relative to dist).
+	async fn read(&self, path: &Path) -> Option<Cow<'static, [u8]>> {
+		match self {
+			Self::Embedded => DIST
+				.get_file(path)
+				.map(|file| Cow::Borrowed(file.contents())),
+			Self::Disk(dir) => fs::read(dir.join(path)).await.ok().map(Cow::Owned),
+		}
+	}
+
+	/// The file at the given path (relative to dist), along with the client's
+	/// preferred precompressed variant of it, if it was embedded.
+	async fn file(&self, path: &Path) -> Option<DistFile> {
+		let bytes = self.read(path).await?;
+		let content_type = path
+			.extension()
+			.and_then(OsStr::to_str)
+			.and_then(ContentType::from_extension)
+			.unwrap_or(ContentType::Bytes);
+		let metadata = match self {
+			Self::Embedded => path.to_str().and_then(dist_metadata),
+			Self::Disk(_) => None,
+		};
+		let (validators, variants) = match metadata {
+			Some((validators, variants)) => (Some(validators), variants),
+			None => (None, &[][..]),
+		};
+		Some(DistFile {
+			file: Precompressed {
+				content_type,
+				bytes,
+				validators,
+				variants,
+			},
+			from_disk: matches!(self, Self::Disk(_)),
+		})
+	}
+}
+
+/// A file from dist. Files read from disk may change at any time, so clients must
+/// check for a new copy before using a cached one.
+struct DistFile {
+	file: Precompressed,
+	from_disk: bool,
+}
+
+impl<'r> Responder<'r, 'static> for DistFile {
+	fn respond_to(self, request: &'r Request<'_>) -> rocket::response::Result<'static> {
+		let mut res = self.file.respond_to(request)?;
+		if self.from_disk {
+			res.set_raw_header(header::CACHE_CONTROL.as_str(), "no-cache");
+		}
+		Ok(res)
+	}
+}
+
+// Generated in build.rs:
+include!(concat!(env!("OUT_DIR"), "/text_pages.rs"));
+
+/// A page from dist. If the page has a text rendering and the client is a terminal
+/// program, the client gets the text instead. If the capsule has a version of the
+/// page, HTML responses link to it.
+struct DistPage {
+	file: DistFile,
+	text: Option<(&'static str, &'static str)>,
+	terminal: TerminalClient,
+	alternate: Option<String>,
+}
+
+impl DistPage {
+	fn new(file: DistFile, path: &Path, terminal: TerminalClient) -> Self {
+		Self {
+			file,
+			text: path.to_str().and_then(text_page),
+			terminal,
+			alternate: gemtext_alternate(path),
+		}
+	}
+}
+
+impl<'r> Responder<'r, 'static> for DistPage {
+	fn respond_to(self, request: &'r Request<'_>) -> rocket::response::Result<'static> {
+		let mut res = match (self.text, self.terminal.0) {
+			(Some((plain, ansi)), Some(style)) => {
+				let text = match style {
+					TextStyle::Plain => plain,
+					TextStyle::Ansi => ansi,
+				};
+				let text = match request.rocket().state::<CapsuleDocuments>() {
+					Some(capsule) => {
+						let origin = format!("gemini://{}", capsule.hostname);
+						text.replace(TEXT_CAPSULE_ORIGIN, &origin)
+					}
+					None => text.to_owned(),
+				};
+				Response::build()
+					.header(ContentType::Plain)
+					.raw_header(
+						header::CACHE_CONTROL.as_str(),
+						CachePolicy::Short.header_value(),
+					)
+					.sized_body(text.len(), Cursor::new(text))
+					.finalize()
+			}
+			_ => self.file.respond_to(request)?,
+		};
+		if self.text.is_some() {
+			add_vary(&mut res, &["Accept", "User-Agent"]);
+		}
+		if let Some(alternate) = self.alternate {
+			if res
+				.content_type()
+				.is_some_and(|content_type| content_type.is_html())
+			{
+				res.set_raw_header(
+					header::LINK.as_str(),
+					format!("<{alternate}>; rel=\"alternate\"; type=\"text/gemini\""),
+				);
+			}
+			add_vary(&mut res, &["Accept"]);
+		}
+		Ok(res)
+	}
+}
+
+/// The path of the capsule document that's another version of the dist page at the
+/// given path, if there is one. Only `/contact`, `/support`, and the Ways documents
+/// have one.
+fn gemtext_alternate(path: &Path) -> Option<String> {
+	let path = path.to_str()?;
+	let doc_path = format!("/{}", path.strip_suffix(".html").unwrap_or(path));
+	let has_alternate =
+		matches!(doc_path.as_str(), "/contact" | "/support") || doc_path.starts_with("/ways/");
+	(has_alternate && gemtext::is_document(&doc_path)).then_some(doc_path)
+}
+
+/// The path of a page that the capsule has a version of, like `/contact` or `/ways/ways.html`.
+struct AlternatePath(String);
+
+impl<'r> FromSegments<'r> for AlternatePath {
+	type Error = ();
+
+	fn from_segments(segments: Segments<'r, fmt::Path>) -> Result<Self, Self::Error> {
+		let Ok(path) = PathBuf::from_segments(segments) else {
+			return Err(());
+		};
+		gemtext_alternate(&path).map(Self).ok_or(())
+	}
+}
+
+/// The capsule's version of a page, for clients that prefer gemtext.
+struct GemtextPage(Cow<'static, [u8]>);
+
+impl<'r> Responder<'r, 'static> for GemtextPage {
+	fn respond_to(self, _: &'r Request<'_>) -> rocket::response::Result<'static> {
+		let content_type = ContentType::new("text", "gemini").with_params(("charset", "utf-8"));
+		let mut res = Response::build()
+			.header(content_type)
+			.raw_header(
+				header::CACHE_CONTROL.as_str(),
+				CachePolicy::Short.header_value(),
+			)
+			.sized_body(self.0.len(), Cursor::new(self.0))
+			.finalize();
+		add_vary(&mut res, &["Accept"]);
+		Ok(res)
+	}
+}
+
+// Ranked after `fediverse_alias`, and before `dist`
+#[get("/<path..>", rank = 0)]
+async fn gemtext_page(
+	path: AlternatePath,
+	_client: GemtextClient,
+	capsule: &State<CapsuleDocuments>,
+) -> CorsOnlyProdResponse<Option<GemtextPage>> {
+	let doc = gemtext::document(&path.0, capsule.from_disk).await;
+	CorsOnlyProdResponse(doc.map(GemtextPage))
+}
+
+#[get("/")]
+async fn root(
+	source: &State<DistSource>,
+	terminal: TerminalClient,
+) -> CorsOnlyProdResponse<Option<DistPage>> {
+	let path = Path::new("index.html");
+	let page = source.file(path).await;
+	CorsOnlyProdResponse(page.map(|file| DistPage::new(file, path, terminal)))
+}
+
+// Ranked after `fediverse_alias`
+#[get("/<path..>", rank = 1)]
+async fn dist(
+	path: PathBuf,
+	source: &State<DistSource>,
+	terminal: TerminalClient,
+) -> CorsOnlyProdResponse<Option<DistPage>> {
+	let mut path = path;
+
+	// If a directory, try adding .html and see if that exists.
+	if source.is_dir(&path).await {
+		let adjacent_html = path.with_extension("html");
+		let inner_html = path.join("index.html");
+		if source.is_file(&adjacent_html).await {
+			path = adjacent_html;
+		} else if source.is_file(&inner_html).await {
+			path = inner_html;
+		}
+	} else if !source.is_file(&path).await {
+		let as_html = path.with_extension("html");
+		if source.is_file(&as_html).await {
+			path = as_html;
+		}
+	}
+
+	let page = source.file(&path).await;
+	CorsOnlyProdResponse(page.map(|file| DistPage::new(file, &path, terminal)))
+}
+
+// MARK: Gemini proxy
+
+/// Where the capsule's documents come from, and the hostname that their links name.
+struct CapsuleDocuments {
+	hostname: String,
+	from_disk: bool,
+}
+
+/// Serves the capsule's gemtext documents as HTML pages in the style of the site.
+#[get("/gemini/<path..>")]
+async fn gemini(
+	path: PathBuf,
+	capsule: &State<CapsuleDocuments>,
+	source: &State<DistSource>,
+) -> Result<CorsOnlyProdResponse<RawHtml<String>>, Status> {
+	let segments = path
+		.iter()
+		.map(OsStr::to_str)
+		.collect::<Option<Vec<_>>>()
+		.ok_or(Status::NotFound)?;
+	let doc_path = format!("/{}", segments.join("/"));
+
+	let doc = gemtext::document(&doc_path, capsule.from_disk)
+		.await
+		.ok_or(Status::NotFound)?;
+	let Ok(doc) = str::from_utf8(&doc) else {
+		return Err(Status::InternalServerError);
+	};
+
+	let lines = gemtext::parse(doc);
+	let content = gemtext::to_html(&lines, |link| {
+		gemtext::proxied_link(link, &doc_path, &capsule.hostname)
+	});
+	let template = source.read(Path::new("404.html")).await;
+	let template = template.as_deref().map(String::from_utf8_lossy);
+	let title = gemtext::title(&lines).unwrap_or("Gemini");
+	let page = gemtext::html_page(template.as_deref(), title, &content);
+	Ok(CorsOnlyProdResponse(RawHtml(page)))
+}
+
+// MARK: - Service
+
+/// Describes any error in the format that the client prefers.
+#[catch(default)]
+async fn error(status: Status, request: &Request<'_>) -> NegotiatedError {
+	let not_found_page = match request.rocket().state::<DistSource>() {
+		Some(source) => source.read(Path::new("404.html")).await,
+		None => None,
+	};
+	NegotiatedError {
+		status,
+		not_found_page,
+	}
+}
+
+/// Builds the HTTP website that listens at the given address.
+pub fn http_service(config: &Config, address: IpAddr) -> Rocket<Build> {
+	let source = DistSource::from_config(&config.http);
+	http_service_from(source, config, address)
+}
+
+/// Builds the HTTP website that listens at the given address, and serves the site
+/// from the given source.
+fn http_service_from(source: DistSource, config: &Config, address: IpAddr) -> Rocket<Build> {
+	let profile = config.profile.clone();
+	let redirects = &config.redirects;
+	let capsule = CapsuleDocuments {
+		hostname: config.gemini_hostname(),
+		from_disk: config.gemini.serve_from_disk,
+	};
+	let config = config.rocket_config(address);
+
+	rocket::build()
+		.configure(config)
+		.manage(profile)
+		.manage(source)
+		.manage(capsule)
+		.attach(TrimSlash)
+		.attach(shield())
+		.attach(ExtraSecurityHeaders)
+		.attach(CacheControl)
+		.attach(Clacks)
+		.attach(PronounsAcceptable)
+		.mount(
+			"/",
+			routes![
+				favicon,
+				well_known_pronouns,
+				avatar,
+				fursona,
+				fediverse_alias,
+				webfinger,
+				nodeinfo,
+				root,
+				gemtext_page,
+				dist,
+				gemini,
+			],
+		)
+		.mount("/", [factories::redirect_route(redirects)])
+		.register("/", catchers![error])
+}
+
+/// Launches the given Rocket, and stops it gracefully when `shutdown` is triggered.
+pub async fn launch(rocket: Rocket<Build>, shutdown: &Shutdown) -> Result<(), rocket::Error> {
+	let rocket = rocket.ignite().await?;
+	let handle = rocket.shutdown();
+	let mut launch = core::pin::pin!(rocket.launch());
+
+	tokio::select! {
+		result = &mut launch => {
+			result?;
+		}
+		() = shutdown.triggered() => {
+			// Rocket stops accepting connections, then waits out its grace period
+			handle.notify();
+			launch.await?;
+		}
+	}
+	Ok(())
+}
+
+// MARK: - Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::config::{HttpConfig, RedirectTable};
+	use crate::middleware::{X_CLACKS_OVERHEAD, X_PRONOUNS_ACCEPTABLE};
+	use core::{net::Ipv4Addr, time::Duration};
+	use http::header;
+	use rocket::{
+		State,
+		fairing::AdHoc,
+		http::{ContentType, Header, MediaType},
+		local::blocking::{Client, LocalResponse},
+	};
+	use std::sync::Arc;
+	use test_case::test_case;
+	use tokio::{
+		io::{AsyncReadExt, AsyncWriteExt},
+		net::TcpStream,
+		sync::{Notify, oneshot},
+		time::timeout,
+	};
+
+	fn assert_header(res: &LocalResponse, key: &str, value: &str) {
+		let expectation = format!("{key} value should exist");
+		assert_eq!(res.headers().get_one(key).expect(&expectation), value);
+	}
+
+	fn assert_headers(res: &LocalResponse) {
+		assert!(res.headers().contains(header::CONTENT_SECURITY_POLICY));
+		assert!(
+			res.headers()
+				.get_one(header::CONTENT_SECURITY_POLICY.as_str())
+				.expect("Content-Security-Policy value should exist")
+				.contains("upgrade-insecure-requests")
+		);
+		assert_header(res, header::REFERRER_POLICY.as_str(), "no-referrer");
+		assert!(res.headers().contains(header::STRICT_TRANSPORT_SECURITY));
+		assert!(res.headers().contains(header::X_CONTENT_TYPE_OPTIONS));
+		assert!(res.headers().contains(header::X_FRAME_OPTIONS));
+		assert!(res.headers().contains(X_CLACKS_OVERHEAD));
+		assert!(res.headers().contains(X_PRONOUNS_ACCEPTABLE));
+	}
+
+	fn assert_cors(res: &LocalResponse, allowed_origin: &'static str) {
+		let actual_allowed_origin = res
+			.headers()
+			.get_one(header::ACCESS_CONTROL_ALLOW_ORIGIN.as_str());
+		assert_eq!(actual_allowed_origin, Some(allowed_origin));
+	}
+
+	fn assert_redir(res: &LocalResponse, location: &str) {
+		assert_eq!(
+			res.headers()
+				.get_one(header::LOCATION.as_str())
+				.expect("Location header should be present"),
+			location
+		);
+	}
+
+	fn assert_status(res: &LocalResponse, status_code: Status) {
+		assert_eq!(res.status(), status_code, "Status should be {status_code}");
+	}
+
+	fn assert_content_type(res: &LocalResponse, content_type: ContentType) {
+		assert_eq!(res.content_type(), Some(content_type));
+	}
+
+	/// The path of the given file in the embedded site, which is the fallback site
+	/// unless Astro has built `dist`.
+	fn dist_path(path: &str) -> String {
+		format!("{}/{}", env!("DIST_DIR"), path.trim_start_matches('/'))
+	}
+
+	fn assert_file_contents_match(response_contents: &str, expected_file_path: &str) {
+		let file_contents =
+			std::fs::read_to_string(expected_file_path).expect("File should exist and be readable");
+		assert_eq!(response_contents, file_contents);
+	}
+
+	fn assert_file_bytes_match(response_contents: &[u8], expected_file_path: &str) {
+		let file_contents =
+			std::fs::read(expected_file_path).expect("File should exist and be readable");
+		assert_eq!(response_contents, file_contents);
+	}
+
+	/// Rocket sends the size of a sized body as `Content-Length`, even for `HEAD` requests.
+	fn assert_content_length(res: &LocalResponse, expected_file_path: &str) {
+		let file_len = std::fs::metadata(expected_file_path)
+			.expect("File should exist")
+			.len();
+		let size = res.body().preset_size().expect("Body should be sized");
+		assert_eq!(u64::try_from(size).ok(), Some(file_len));
+	}
+
+	fn build_client() -> Client {
+		build_client_with(Profile::default())
+	}
+
+	fn build_client_with(profile: Profile) -> Client {
+		let config = Config {
+			profile,
+			..Config::default()
+		};
+		let address = IpAddr::V6(core::net::Ipv6Addr::LOCALHOST);
+		Client::tracked(http_service(&config, address)).expect("Test client should launch")
+	}
+
+	struct Origin(&'static str);
+
+	impl From<Origin> for Header<'static> {
+		fn from(value: Origin) -> Self {
+			Self::new(header::ORIGIN.as_str(), value.0)
+		}
+	}
+
+	struct UserAgent(String);
+
+	impl From<UserAgent> for Header<'static> {
+		fn from(value: UserAgent) -> Self {
+			Self::new(header::USER_AGENT.as_str(), value.0)
+		}
+	}
+
+	fn get<'r>(client: &'r Client, path: &'static str) -> LocalResponse<'r> {
+		let req = client.get(path).header(Origin("https://average.name"));
+		req.dispatch()
+	}
+	fn get_with_origin<'r>(
+		client: &'r Client,
+		path: &'static str,
+		origin: Option<&'static str>,
+	) -> LocalResponse<'r> {
+		let mut req = client.get(path);
+		if let Some(origin) = origin {
+			req = req.header(Origin(origin));
+		}
+		req.dispatch()
+	}
+	fn get_with_user_agent<'r>(
+		client: &'r Client,
+		path: &'r str,
+		user_agent: &'r str,
+	) -> LocalResponse<'r> {
+		let req = client.get(path).header(UserAgent(user_agent.to_owned()));
+		req.dispatch()
+	}
+	fn head<'r>(client: &'r Client, path: &'r str) -> LocalResponse<'r> {
+		let req = client.head(path);
+		req.dispatch()
+	}
+
+	fn response_bytes(res: LocalResponse<'_>) -> Vec<u8> {
+		let bytes = res.into_bytes().expect("Body should download safely");
+		let hundred_mb = 100_000_000;
+		assert!(bytes.len() < hundred_mb, "Body should be under 100 MB");
+		bytes
+	}
+
+	fn response_body(res: LocalResponse) -> String {
+		let body_bytes = response_bytes(res);
+		String::from_utf8(body_bytes).expect("Body should be valid UTF-8")
+	}
+
+	#[test]
+	fn answers_favicon() {
+		let client = build_client();
+		let res = get(&client, "/favicon.ico");
+		assert_status(&res, Status::NotFound);
+		assert_headers(&res);
+		let body = response_body(res);
+		assert!(body.is_empty());
+		client.terminate();
+	}
+
+	#[test]
+	fn answers_pronouns() {
+		let client = build_client();
+		let res = get(&client, "/.well-known/pronouns");
+		assert_status(&res, Status::Ok);
+		assert_headers(&res);
+		assert_cors(&res, "*");
+		let text = response_body(res);
+		assert_eq!(text, "she/her");
+		client.terminate();
+	}
+
+	#[test]
+	fn serves_webfinger() {
+		let client = build_client();
+		{
+			let res = get(
+				&client,
+				"/.well-known/webfinger?resource=acct:average@average.name",
+			);
+			assert_status(&res, Status::Ok);
+			assert_headers(&res);
+			assert_cors(&res, "*");
+			let content_type = res.content_type().expect("Content-Type should be sent");
+			let mime = "application/jrd+json; charset=UTF-8"
+				.parse::<MediaType>()
+				.expect("MIME string should be valid");
+			assert_eq!(content_type.0, mime);
+
+			let body = res.into_string().expect("Result should be a string");
+			let result: WebFinger =
+				serde_json::from_str(&body).expect("Result should be a JSON string");
+			assert_eq!(result.subject, "acct:avghelper@gts.average.name");
+			assert_eq!(result.links.len(), 2);
+		}
+		client.terminate();
+	}
+
+	#[test]
+	fn serves_webfinger_with_rel() {
+		let client = build_client();
+		{
+			let res = get(
+				&client,
+				"/.well-known/webfinger?resource=acct:average@average.name&rel=self",
+			);
+			assert_status(&res, Status::Ok);
+			assert_headers(&res);
+			assert_cors(&res, "*");
+			let content_type = res.content_type().expect("Content-Type should be sent");
+			let mime = "application/jrd+json; charset=UTF-8"
+				.parse::<MediaType>()
+				.expect("MIME string should be valid");
+			assert_eq!(content_type.0, mime);
+
+			let body = res.into_string().expect("Result should be a string");
+			let result: WebFinger =
+				serde_json::from_str(&body).expect("Result should be a JSON string");
+			assert_eq!(result.subject, "acct:avghelper@gts.average.name");
+			assert_eq!(result.links.len(), 1);
+		}
+		client.terminate();
+	}
+
+	#[test]
+	fn webfinger_fails_without_resource_param() {
+		let client = build_client();
+		{
+			let res = get(&client, "/.well-known/webfinger");
+			assert_status(&res, Status::BadRequest);
+			assert_headers(&res);
+			assert_content_type(&res, ContentType::Plain);
+			assert_eq!(response_body(res), "400 Bad Request");
+
+			let res = client
+				.get("/.well-known/webfinger")
+				.header(Header::new(header::ACCEPT.as_str(), "application/json"))
+				.dispatch();
+			assert_status(&res, Status::BadRequest);
+			assert_content_type(&res, ContentType::new("application", "problem+json"));
+		}
+		client.terminate();
+	}
+
+	#[test]
+	fn serves_nodeinfo() {
+		let client = build_client();
+		{
+			let res = get(&client, "/.well-known/nodeinfo");
+			assert_status(&res, Status::NotFound);
+			assert_headers(&res);
+
+			let res2 =
+				get_with_user_agent(&client, "/.well-known/nodeinfo", "GitHub-NodeinfoQuery");
+			assert_status(&res2, Status::Found);
+			assert_headers(&res2);
+		}
+		client.terminate();
+	}
+
+	#[test]
+	fn serves_static_files() {
+		let file_paths = vec![
+			("/robots.txt", ContentType::Plain, "https://average.name"),
+			("/.well-known/fursona.json", ContentType::JSON, "*"),
+			("/index.html", ContentType::HTML, "https://average.name"),
+			("/contact.html", ContentType::HTML, "https://average.name"),
+		];
+
+		let client = build_client();
+		for (path, mime, origin) in file_paths {
+			let res = get(&client, path);
+			assert_status(&res, Status::Ok);
+			assert_headers(&res);
+			assert_cors(&res, origin);
+			assert_content_type(&res, mime.clone());
+			let response_contents = response_body(res);
+			let expected_file_path = dist_path(path);
+			assert_file_contents_match(&response_contents, &expected_file_path);
+		}
+
+		client.terminate();
+	}
+
+	#[test]
+	fn omits_cors_header_for_unknown_origin() {
+		let file_paths = vec![
+			("/robots.txt", ContentType::Plain),
+			("/index.html", ContentType::HTML),
+			("/contact.html", ContentType::HTML),
+		];
+
+		let client = build_client();
+		for (path, mime) in file_paths {
+			let res = get_with_origin(&client, path, None);
+			assert_status(&res, Status::Ok);
+			assert_headers(&res);
+			assert_content_type(&res, mime.clone());
+			let has_allowed_origin = res
+				.headers()
+				.contains(header::ACCESS_CONTROL_ALLOW_ORIGIN.as_str());
+			assert!(!has_allowed_origin);
+			let response_contents = response_body(res);
+			let expected_file_path = dist_path(path);
+			assert_file_contents_match(&response_contents, &expected_file_path);
+		}
+		client.terminate();
+	}
+
+	#[test]
+	fn serves_static_files_without_extension() {
+		let file_paths = vec![("/", "index.html"), ("/contact", "contact.html")];
+
+		let client = build_client();
+		for (path, file_name) in file_paths {
+			let res = get(&client, path);
+			assert_status(&res, Status::Ok);
+			assert_headers(&res);
+			assert_content_type(&res, ContentType::HTML);
+			let response_contents = response_body(res);
+			let expected_file_path = dist_path(file_name);
+			assert_file_contents_match(&response_contents, &expected_file_path);
+		}
+
+		client.terminate();
+	}
+
+	#[test]
+	#[cfg_attr(not(dist), ignore = "needs the sitemaps that Astro builds")]
+	fn serves_sitemaps() {
+		let file_paths = vec![
+			("/sitemap.html", ContentType::HTML),
+			("/sitemap", ContentType::HTML),
+			("/sitemap-index.xml", ContentType::XML),
+			("/sitemap-0.xml", ContentType::XML),
+		];
+
+		let client = build_client();
+		for (path, mime) in file_paths {
+			let res = get(&client, path);
+			assert_status(&res, Status::Ok);
+			assert_headers(&res);
+			assert_cors(&res, "https://average.name");
+			assert_content_type(&res, mime.clone());
+
+			let res = get_with_origin(&client, path, None);
+			assert_status(&res, Status::Ok);
+			let has_allowed_origin = res
+				.headers()
+				.contains(header::ACCESS_CONTROL_ALLOW_ORIGIN.as_str());
+			assert!(!has_allowed_origin);
+		}
+		client.terminate();
+	}
+
+	#[test]
+	fn serves_404_for_unknown_page() {
+		let file_paths = vec![
+			"/foo_bar_nothing_to_see_here", //
+			"/contact/no-thanks",
+		];
+
+		let client = build_client();
+		for path in file_paths {
+			let res = client
+				.get(path)
+				.header(Header::new(header::ACCEPT.as_str(), "text/html"))
+				.dispatch();
+			assert_status(&res, Status::NotFound);
+			assert_headers(&res);
+			assert_content_type(&res, ContentType::HTML);
+			let response_contents = response_body(res);
+			assert_file_contents_match(&response_contents, &dist_path("404.html"));
+
+			// Like curl
+			let res = get(&client, path);
+			assert_status(&res, Status::NotFound);
+			assert_content_type(&res, ContentType::Plain);
+			assert_eq!(response_body(res), "404 Not Found");
+		}
+
+		client.terminate();
+	}
+
+	fn assert_would_serve_file(path: &'static str, content_type: ContentType) {
+		let client = build_client();
+		let res = head(&client, path);
+		assert_status(&res, Status::Ok);
+		assert_headers(&res);
+		assert_content_type(&res, content_type);
+		assert_content_length(&res, &dist_path(path));
+
+		// CORS should permit any origin
+		assert_cors(&res, "*");
+
+		let response_contents = response_bytes(res);
+		assert_eq!(response_contents.len(), 0);
+		client.terminate();
+	}
+
+	fn assert_serves_file(path: &'static str, content_type: ContentType) {
+		let client = build_client();
+		let res = get(&client, path);
+		assert_status(&res, Status::Ok);
+		assert_headers(&res);
+		assert_content_type(&res, content_type);
+		assert_content_length(&res, &dist_path(path));
+
+		// CORS should permit any origin
+		assert_cors(&res, "*");
+
+		let response_contents = response_bytes(res);
+		assert_file_bytes_match(&response_contents, &dist_path(path));
+
+		client.terminate();
+	}
+
+	#[test]
+	fn serves_fursona_ref() {
+		let path = "/images/refs/AverageHelper-avatar.png";
+		assert_would_serve_file(path, ContentType::PNG);
+		assert_serves_file("/images/refs/AverageHelper-avatar.png", ContentType::PNG);
+	}
+
+	#[test]
+	fn serves_fursona_json() {
+		assert_would_serve_file("/.well-known/fursona.json", ContentType::JSON);
+		assert_serves_file("/.well-known/fursona.json", ContentType::JSON);
+	}
+
+	#[test]
+	fn revalidates_static_files() {
+		let file_paths = [
+			"/",
+			"/contact",
+			"/robots.txt",
+			"/images/refs/AverageHelper-avatar.png",
+			"/.well-known/fursona.json",
+		];
+
+		let client = build_client();
+		for path in file_paths {
+			let res = get(&client, path);
+			assert_status(&res, Status::Ok);
+			let etag = res
+				.headers()
+				.get_one(header::ETAG.as_str())
+				.expect("ETag should be sent")
+				.to_owned();
+			let last_modified = res
+				.headers()
+				.get_one(header::LAST_MODIFIED.as_str())
+				.expect("Last-Modified should be sent")
+				.to_owned();
+			let cache_control = res
+				.headers()
+				.get_one(header::CACHE_CONTROL.as_str())
+				.expect("Cache-Control should be sent")
+				.to_owned();
+
+			let res = client
+				.get(path)
+				.header(Header::new(header::IF_NONE_MATCH.as_str(), etag.clone()))
+				.dispatch();
+			assert_status(&res, Status::NotModified);
+			assert_headers(&res);
+			assert_header(&res, header::ETAG.as_str(), &etag);
+			assert_header(&res, header::CACHE_CONTROL.as_str(), &cache_control);
+			let body = res.into_bytes().unwrap_or_default();
+			assert!(body.is_empty(), "Body should be empty");
+
+			let res = client
+				.get(path)
+				.header(Header::new(
+					header::IF_MODIFIED_SINCE.as_str(),
+					last_modified,
+				))
+				.dispatch();
+			assert_status(&res, Status::NotModified);
+
+			let res = client
+				.get(path)
+				.header(Header::new(header::IF_NONE_MATCH.as_str(), "\"stale\""))
+				.dispatch();
+			assert_status(&res, Status::Ok);
+			assert!(!response_bytes(res).is_empty(), "Body should be sent");
+		}
+		client.terminate();
+	}
+
+	#[test]
+	fn sets_cache_control() {
+		let client = build_client();
+		{
+			let res = get(&client, "/contact");
+			assert_header(
+				&res,
+				header::CACHE_CONTROL.as_str(),
+				"public, max-age=300, must-revalidate",
+			);
+			let res = get(&client, "/.well-known/fursona.json");
+			assert_header(
+				&res,
+				header::CACHE_CONTROL.as_str(),
+				"public, max-age=86400",
+			);
+			let res = get(&client, "/foo_bar_nothing_to_see_here");
+			assert_header(&res, header::CACHE_CONTROL.as_str(), "no-store");
+		}
+		client.terminate();
+	}
+
+	#[test]
+	fn serves_byte_ranges() {
+		let client = build_client();
+		for path in ["/contact.html", "/images/refs/AverageHelper-avatar.png"] {
+			let file_contents =
+				std::fs::read(dist_path(path)).expect("File should exist and be readable");
+			let len = file_contents.len();
+
+			let res = get(&client, path);
+			assert_status(&res, Status::Ok);
+			assert_header(&res, header::ACCEPT_RANGES.as_str(), "bytes");
+
+			let res = client
+				.get(path)
+				.header(Header::new(header::RANGE.as_str(), "bytes=1-4"))
+				.dispatch();
+			assert_status(&res, Status::PartialContent);
+			assert_headers(&res);
+			assert_header(
+				&res,
+				header::CONTENT_RANGE.as_str(),
+				&format!("bytes 1-4/{len}"),
+			);
+			assert_eq!(
+				response_bytes(res),
+				file_contents
+					.get(1..=4)
+					.expect("File should be long enough")
+			);
+
+			let res = client
+				.get(path)
+				.header(Header::new(header::RANGE.as_str(), "bytes=0-0,-1"))
+				.dispatch();
+			assert_status(&res, Status::PartialContent);
+			let content_type = res.content_type().expect("Content-Type should be sent");
+			assert_eq!(content_type.sub(), "byteranges");
+
+			let res = client
+				.get(path)
+				.header(Header::new(header::RANGE.as_str(), format!("bytes={len}-")))
+				.dispatch();
+			assert_status(&res, Status::RangeNotSatisfiable);
+			assert_header(
+				&res,
+				header::CONTENT_RANGE.as_str(),
+				&format!("bytes */{len}"),
+			);
+		}
+		client.terminate();
+	}
+
+	#[test]
+	fn serves_precompressed_files() {
+		let client = build_client();
+		for (encoding, extension) in [("gzip", "gz"), ("br", "br"), ("zstd", "zst")] {
+			let res = client
+				.get("/.well-known/fursona.json")
+				.header(Header::new(header::ACCEPT_ENCODING.as_str(), encoding))
+				.dispatch();
+			assert_status(&res, Status::Ok);
+			assert_headers(&res);
+			assert_cors(&res, "*");
+			assert_content_type(&res, ContentType::JSON);
+			assert_header(&res, header::CONTENT_ENCODING.as_str(), encoding);
+			assert_header(&res, header::VARY.as_str(), "Accept-Encoding");
+
+			let compressed_path = format!(
+				"{}/dist/.well-known/fursona.json.{extension}",
+				env!("OUT_DIR")
+			);
+			assert_file_bytes_match(&response_bytes(res), &compressed_path);
+		}
+
+		// Already compressed
+		{
+			let res = client
+				.get("/images/refs/AverageHelper-avatar.png")
+				.header(Header::new(
+					header::ACCEPT_ENCODING.as_str(),
+					"gzip, br, zstd",
+				))
+				.dispatch();
+			assert_status(&res, Status::Ok);
+			assert!(!res.headers().contains(header::CONTENT_ENCODING.as_str()));
+			assert!(!res.headers().contains(header::VARY.as_str()));
+		}
+		client.terminate();
+	}
+
+	#[test]
+	fn serves_files_from_disk() {
+		let source = DistSource::Disk(PathBuf::from(env!("DIST_DIR")));
+		let address = IpAddr::V6(core::net::Ipv6Addr::LOCALHOST);
+		let client = Client::tracked(http_service_from(source, &Config::default(), address))
+			.expect("Test client should launch");
+		{
+			let res = client
+				.get("/contact")
+				.header(Header::new(header::ACCEPT_ENCODING.as_str(), "gzip"))
+				.dispatch();
+			assert_status(&res, Status::Ok);
+			assert_headers(&res);
+			assert_content_type(&res, ContentType::HTML);
+			assert_header(&res, header::CACHE_CONTROL.as_str(), "no-cache");
+			assert!(!res.headers().contains(header::ETAG.as_str()));
+			assert!(!res.headers().contains(header::CONTENT_ENCODING.as_str()));
+			assert_content_length(&res, &dist_path("contact.html"));
+			assert_file_contents_match(&response_body(res), &dist_path("contact.html"));
+
+			let res = client
+				.get("/foo_bar_nothing_to_see_here")
+				.header(Header::new(header::ACCEPT.as_str(), "text/html"))
+				.dispatch();
+			assert_status(&res, Status::NotFound);
+			assert_file_contents_match(&response_body(res), &dist_path("404.html"));
+		}
+		client.terminate();
+	}
+
+	#[test]
+	fn serves_text_to_terminals() {
+		let text_path = |extension: &str| format!("{}/text/index.{extension}", env!("OUT_DIR"));
+		let client = build_client();
+		{
+			let res = get_with_user_agent(&client, "/", "curl/8.4.0");
+			assert_status(&res, Status::Ok);
+			assert_headers(&res);
+			assert_content_type(&res, ContentType::Plain);
+			assert_header(&res, header::VARY.as_str(), "Accept, User-Agent");
+			assert_header(
+				&res,
+				header::CACHE_CONTROL.as_str(),
+				"public, max-age=300, must-revalidate",
+			);
+			assert_file_contents_match(&response_body(res), &text_path("ansi"));
+
+			let res = get_with_user_agent(&client, "/", "Wget/1.21.4");
+			assert_content_type(&res, ContentType::Plain);
+			assert_file_contents_match(&response_body(res), &text_path("txt"));
+
+			let res = client
+				.get("/")
+				.header(Header::new(header::ACCEPT.as_str(), "text/plain"))
+				.dispatch();
+			assert_content_type(&res, ContentType::Plain);
+			assert_file_contents_match(&response_body(res), &text_path("txt"));
+
+			let res = get(&client, "/");
+			assert_content_type(&res, ContentType::HTML);
+			assert_header(
+				&res,
+				header::VARY.as_str(),
+				"Accept-Encoding, Accept, User-Agent",
+			);
+		}
+		client.terminate();
+	}
+
+	#[test]
+	fn links_terminals_to_capsule_routes() {
+		let client = build_client();
+		{
+			let res = get_with_user_agent(&client, "/ways", "Wget/1.21.4");
+			assert_content_type(&res, ContentType::Plain);
+			let text = res.into_string().unwrap_or_default();
+			let hostname = Config::default().gemini_hostname();
+			assert!(
+				text.contains(&format!("<gemini://{hostname}/search>")),
+				"{text}"
+			);
+			assert!(!text.contains(TEXT_CAPSULE_ORIGIN), "{text}");
+		}
+		client.terminate();
+	}
+
+	#[test_case("/contact", "src/content/gemtext/contact.gmi" ; "contact")]
+	#[test_case("/contact.html", "src/content/gemtext/contact.gmi" ; "contact with extension")]
+	#[test_case("/support", "src/content/gemtext/support.gmi" ; "support")]
+	#[test_case("/ways/ways", concat!(env!("OUT_DIR"), "/ways/ways.gmi") ; "ways document")]
+	fn serves_gemtext_when_asked(path: &'static str, gemtext_path: &str) {
+		let client = build_client();
+		{
+			let res = client
+				.get(path)
+				.header(Header::new(header::ACCEPT.as_str(), "text/gemini"))
+				.dispatch();
+			assert_status(&res, Status::Ok);
+			assert_headers(&res);
+			assert_header(
+				&res,
+				header::CONTENT_TYPE.as_str(),
+				"text/gemini; charset=utf-8",
+			);
+			assert_header(&res, header::VARY.as_str(), "Accept");
+			assert_file_contents_match(&response_body(res), gemtext_path);
+		}
+		client.terminate();
+	}
+
+	#[test]
+	fn serves_html_unless_gemtext_asked() {
+		let client = build_client();
+		{
+			let res = client
+				.get("/")
+				.header(Header::new(header::ACCEPT.as_str(), "text/gemini"))
+				.dispatch();
+			assert_status(&res, Status::Ok);
+			assert_content_type(&res, ContentType::HTML);
+
+			let res = client
+				.get("/contact")
+				.header(Header::new(
+					header::ACCEPT.as_str(),
+					"text/html, text/gemini;q=0.9",
+				))
+				.dispatch();
+			assert_ne!(
+				res.headers().get_one(header::CONTENT_TYPE.as_str()),
+				Some("text/gemini; charset=utf-8")
+			);
+		}
+		client.terminate();
+	}
+
+	#[test]
+	fn links_to_gemtext_version() {
+		let client = build_client();
+		{
+			let res = get(&client, "/contact");
+			assert_status(&res, Status::Ok);
+			assert_content_type(&res, ContentType::HTML);
+			assert_header(
+				&res,
+				header::LINK.as_str(),
+				"</contact>; rel=\"alternate\"; type=\"text/gemini\"",
+			);
+			let vary = res
+				.headers()
+				.get_one(header::VARY.as_str())
+				.expect("Vary value should exist");
+			assert!(vary.split(", ").any(|field| field == "Accept"));
+
+			let res = get(&client, "/");
+			assert_eq!(res.headers().get_one(header::LINK.as_str()), None);
+		}
+		client.terminate();
+	}
+
+	#[test]
+	fn serves_capsule_as_html() {
+		let client = build_client();
+		{
+			let res = get(&client, "/gemini");
+			assert_status(&res, Status::Ok);
+			assert_headers(&res);
+			assert_content_type(&res, ContentType::HTML);
+			let body = response_body(res);
+			assert!(body.contains("<title>Hello, world!</title>"));
+			assert!(body.contains("<h1>Hello, world!</h1>"));
+			assert!(body.contains("<a href=\"/gemini/ways\">Ways folder</a>"));
+			assert!(body.contains("<a href=\"/humans.txt\">humans.txt</a>"));
+			assert!(body.contains("<a href=\"https://average.name\">HTTP version</a>"));
+			assert!(!body.contains("<script"));
+
+			let res = get(&client, "/gemini/ways/ways");
+			assert_status(&res, Status::Ok);
+			let body = response_body(res);
+			assert!(body.contains("<a href=\"/gemini/ways\">Return to Ways</a>"));
+
+			let res = get(&client, "/gemini/contact");
+			assert_status(&res, Status::Ok);
+
+			let res = get(&client, "/gemini/index");
+			assert_status(&res, Status::NotFound);
+
+			let res = get(&client, "/gemini/humans.txt");
+			assert_status(&res, Status::NotFound);
+		}
+		client.terminate();
+	}
+
+	// TODO: Test that all internal links go where they're supposed to go
+
+	/// The `Location` of the given response, which should be a redirect.
+	fn redirect_location(res: &LocalResponse) -> String {
+		assert!(
+			res.status().class().is_redirection(),
+			"Status {} should redirect",
+			res.status()
+		);
+		res.headers()
+			.get_one(header::LOCATION.as_str())
+			.expect("Location header should be present")
+			.to_owned()
+	}
+
+	/// Checks that the given local location serves something, after at most one
+	/// more redirect.
+	fn assert_lands(client: &Client, location: &str) {
+		let res = client.get(location.to_owned()).dispatch();
+		let res = if res.status().class().is_redirection() {
+			let next = redirect_location(&res);
+			if !next.starts_with('/') {
+				return;
+			}
+			client.get(next).dispatch()
+		} else {
+			res
+		};
+		assert!(
+			!res.status().class().is_redirection() && res.status() != Status::NotFound,
+			"{location} should land on a page, not {}",
+			res.status()
+		);
+	}
+
+	/// The given local location with `.html` after its path, unless the path names
+	/// a directory or already has an extension, as the redirect table describes.
+	fn html_variant(location: &str) -> String {
+		let end = location.find(['?', '#']).unwrap_or(location.len());
+		let (path, rest) = location.split_at(end);
+		let name = path.rsplit('/').next().unwrap_or_default();
+		if name.is_empty() || name.contains('.') {
+			location.to_owned()
+		} else {
+			format!("{path}.html{rest}")
+		}
+	}
+
+	#[test]
+	fn redirects() {
+		let client = build_client();
+		{
+			for rule in &RedirectTable::default().rules {
+				let path = rule.example();
+				let res = client.get(format!("{path}?q=1")).dispatch();
+				assert_status(&res, Status::new(rule.status.code()));
+				assert_headers(&res);
+				let target = redirect_location(&res);
+				assert!(target.contains("q=1"), "{target} should keep the query");
+
+				let res = client.get(format!("{path}.html?q=1")).dispatch();
+				assert_status(&res, Status::new(rule.status.code()));
+				let html_target = redirect_location(&res);
+				if target.starts_with('/') && rule.html {
+					assert_eq!(html_target, html_variant(&target));
+				} else {
+					assert_eq!(html_target, target);
+				}
+
+				if target.starts_with('/') {
+					assert_lands(&client, &target);
+					assert_lands(&client, &html_target);
+				}
+			}
+
+			// Not a page, so no .html
+			let res = get(&client, "/pronouns.html");
+			assert_redir(&res, "/.well-known/pronouns");
+			assert_lands(&client, "/pronouns.html");
+
+			let profile = Profile::default();
+			for alias in &profile.aliases {
+				let res = client.get(format!("/@{alias}")).dispatch();
+				assert_status(&res, Status::Found);
+				assert_headers(&res);
+				assert_redir(&res, &profile.fediverse.profile_page);
+			}
+		}
+		client.terminate();
+	}
+
+	#[test]
+	fn uses_given_redirects() {
+		let config = Config {
+			redirects: toml::from_str(
+				"[[redirect]]\nfrom = \"/old\"\nto = \"/new\"\nstatus = 301\n",
+			)
+			.expect("Table should parse"),
+			..Config::default()
+		};
+		let address = IpAddr::V6(core::net::Ipv6Addr::LOCALHOST);
+		let client =
+			Client::tracked(http_service(&config, address)).expect("Test client should launch");
+		{
+			let res = get(&client, "/old");
+			assert_status(&res, Status::MovedPermanently);
+			assert_redir(&res, "/new");
+
+			let res = get(&client, "/old.html");
+			assert_status(&res, Status::MovedPermanently);
+			assert_redir(&res, "/new.html");
+
+			let res = get(&client, "/how");
+			assert_status(&res, Status::NotFound);
+		}
+		client.terminate();
+	}
+
+	#[test]
+	fn uses_given_profile() {
+		let client = build_client_with(Profile {
+			origin: "https://example.com".to_owned(),
+			aliases: vec!["me".to_owned()],
+			pronouns: "they/them".to_owned(),
+			memorials: vec!["Ada Lovelace".to_owned()],
+			..Profile::default()
+		});
+
+		{
+			let res = get_with_origin(&client, "/.well-known/pronouns", None);
+			assert_header(&res, X_PRONOUNS_ACCEPTABLE, "en:they/them");
+			assert_header(&res, X_CLACKS_OVERHEAD, "GNU Ada Lovelace");
+			assert_eq!(response_body(res), "they/them");
+
+			let res = get_with_origin(&client, "/", Some("https://example.com"));
+			assert_cors(&res, "https://example.com");
+			let res = get(&client, "/");
+			assert!(
+				!res.headers()
+					.contains(header::ACCESS_CONTROL_ALLOW_ORIGIN.as_str()),
+				"Default origin should not get CORS access"
+			);
+
+			let res = get(&client, "/@me");
+			assert_status(&res, Status::Found);
+			assert_redir(&res, "https://gts.average.name/@avghelper");
+			let res = get(&client, "/@avg");
+			assert_status(&res, Status::NotFound);
+		}
+		client.terminate();
+	}
+
+	/// A route that announces that it started, then takes a moment to respond.
+	#[get("/slow")]
+	async fn slow(started: &State<Arc<Notify>>) -> &'static str {
+		started.notify_one();
+		tokio::time::sleep(Duration::from_millis(100)).await;
+		"Done"
+	}
+
+	#[tokio::test]
+	async fn finishes_in_flight_requests_on_shutdown() {
+		let config = Config {
+			http: HttpConfig {
+				port: 0, // any free port
+				..HttpConfig::default()
+			},
+			..Config::default()
+		};
+		let started = Arc::new(Notify::new());
+		let (port_sender, port) = oneshot::channel();
+		let rocket = http_service(&config, IpAddr::V4(Ipv4Addr::LOCALHOST))
+			.manage(Arc::clone(&started))
+			.mount("/test", routes![slow])
+			.attach(AdHoc::on_liftoff("Port", |rocket| {
+				_ = port_sender.send(rocket.config().port);
+				Box::pin(async {})
+			}));
+
+		let shutdown = Shutdown::default();
+		let server = tokio::spawn({
+			let shutdown = shutdown.clone();
+			async move { launch(rocket, &shutdown).await }
+		});
+		let address = (
+			Ipv4Addr::LOCALHOST,
+			port.await.expect("Rocket should lift off"),
+		);
+
+		let mut stream = TcpStream::connect(address)
+			.await
+			.expect("Rocket should accept connections");
+		stream
+			.write_all(b"GET /test/slow HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+			.await
+			.expect("Request should send");
+		started.notified().await;
+		shutdown.trigger();
+
+		let mut response = String::new();
+		stream
+			.read_to_string(&mut response)
+			.await
+			.expect("Response should be readable");
+		assert!(
+			response.starts_with("HTTP/1.1 200 OK"),
+			"In-flight request should succeed: {response}"
+		);
+		assert!(response.ends_with("Done"), "Response should be complete");
+
+		timeout(Duration::from_secs(10), server)
+			.await
+			.expect("Rocket should stop after draining")
+			.expect("Rocket should not panic")
+			.expect("Rocket should stop cleanly");
+		assert!(
+			TcpStream::connect(address).await.is_err(),
+			"Rocket should refuse new connections"
+		);
+	}
+}